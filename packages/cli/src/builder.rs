@@ -1,12 +1,17 @@
 use std::{
-    fs::{create_dir_all, read_to_string},
-    path::PathBuf,
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{create_dir_all, read_dir, read_to_string},
+    path::{Path, PathBuf},
 };
 
 use anyhow::anyhow;
-use dioscript_runtime::types::Value;
+use colored::*;
+use dioscript_parser::ast::DioscriptAst;
+use dioscript_runtime::{a11y, error::Error, types::Value};
+use rayon::prelude::*;
 
-use crate::BuildArgs;
+use crate::{crash, lint, report, BuildArgs};
 
 pub fn build(args: &BuildArgs) -> anyhow::Result<String> {
     let target = &args.target;
@@ -14,43 +19,233 @@ pub fn build(args: &BuildArgs) -> anyhow::Result<String> {
     let out_dir = &args.out_dir;
     let template = &args.template;
 
-    let build_target = BuildTarget::from_str(&target);
+    let build_target = BuildTarget::from_str(target);
     let file_path = PathBuf::from(file_name);
-    let file_content = read_to_string(&file_path)?;
-    let file_stem = file_path.file_stem().unwrap().to_str().unwrap();
-
-    let template = if let Some(v) = template {
-        let file = PathBuf::from(v);
-        if !file.is_file() {
-            "<dioscript />".to_string()
-        } else {
-            let string = read_to_string(file)?;
-            string
+
+    // template given by `--template` is used for every page, otherwise it is looked up next
+    // to each page.
+    let template = match template {
+        Some(v) => {
+            let file = PathBuf::from(v);
+            if !file.is_file() {
+                return Err(anyhow!("template file `{}` not found", file.display()));
+            }
+            Some(read_to_string(file)?)
         }
-    } else {
-        "<dioscript />".to_string()
+        None => None,
     };
 
     match build_target {
         BuildTarget::Static => {
-            let ast = dioscript_parser::ast::DioscriptAst::from_string(&file_content)?;
-            let mut runtime = dioscript_runtime::Runtime::new();
-            let result = runtime.execute_ast(ast)?;
-            if let Value::Element(e) = result {
-                let html = template.replace("<dioscript />", &e.to_html());
-                if !PathBuf::from(out_dir).is_dir() {
-                    create_dir_all(out_dir)?;
+            if !file_path.is_dir() {
+                return build_page(&file_path, template.as_deref(), args);
+            }
+            // every `.ds` file in directory is an independent page, build them in parallel.
+            let mut pages = vec![];
+            for entry in read_dir(&file_path)? {
+                let path = entry?.path();
+                if path.is_file() && path.extension().map(|v| v == "ds").unwrap_or(false) {
+                    pages.push(path);
+                }
+            }
+            pages.sort();
+            if pages.is_empty() {
+                return Err(anyhow!("no `.ds` file found in `{}`", file_path.display()));
+            }
+            let results = pages
+                .par_iter()
+                .map(|page| build_page(page, template.as_deref(), args))
+                .collect::<Vec<_>>();
+
+            let mut outputs = vec![];
+            let mut errors = vec![];
+            for (page, result) in pages.iter().zip(results) {
+                match result {
+                    Ok(v) => outputs.push(v),
+                    Err(e) => errors.push(format!("{}: {}", page.display(), e)),
                 }
-                std::fs::write(format!("{}/{}.html", out_dir, file_stem), html)?;
-                return Ok(format!("{}/{}.html", out_dir, file_stem));
+            }
+            if !errors.is_empty() {
+                return Err(anyhow!(
+                    "{} page(s) build failed:\n{}",
+                    errors.len(),
+                    errors.join("\n")
+                ));
+            }
+            let index = page_path(out_dir, OsStr::new("index"))
+                .display()
+                .to_string();
+            if outputs.contains(&index) {
+                Ok(index)
             } else {
-                return Err(anyhow!("result data type is not Element"));
+                Ok(outputs.remove(0))
             }
         }
-        BuildTarget::Unknown => {
-            return Err(anyhow!("dioscript not support `{target}` builder."));
+        BuildTarget::Unknown => Err(anyhow!("dioscript not support `{target}` builder.")),
+    }
+}
+
+fn build_page(
+    file_path: &Path,
+    template: Option<&str>,
+    args: &BuildArgs,
+) -> anyhow::Result<String> {
+    let out_dir = &args.out_dir;
+    crash::set_file(file_path);
+    let file_content = read_to_string(file_path)?;
+    let file_stem = file_path
+        .file_stem()
+        .ok_or_else(|| anyhow!("`{}` is not a file", file_path.display()))?;
+    let template = match template {
+        Some(v) => v.to_string(),
+        None => find_template(file_path, file_stem)?,
+    };
+
+    let mut runtime = dioscript_runtime::Runtime::new();
+    if let Some(parent) = file_path.parent() {
+        runtime.set_root_path(parent);
+    }
+    let mut ast = DioscriptAst::from_string(&file_content).map_err(Error::from)?;
+    ast.optimize();
+    let mut result = match runtime.execute_ast(ast) {
+        Ok(result) => result,
+        Err(mut e) => {
+            e.locate(&file_content);
+            let file = file_path.display().to_string();
+            return Err(anyhow!(report::runtime_error(&file, &file_content, &e)));
         }
+    };
+    if args.sanitize {
+        result.sanitize();
+    }
+    if args.a11y {
+        let file = file_path.display().to_string();
+        let issues = a11y::check(&result);
+        // pages are built in parallel, so problems of a page are printed at once.
+        if !issues.is_empty() {
+            let lines = lint::issue_lines(&file, &file_content, &issues);
+            println!("{}", lines.join("\n").yellow());
+        }
+    }
+    let html = match result {
+        Value::Dict(slots) => {
+            let (html, warnings) = fill_slots(&template, &slots)?;
+            if !warnings.is_empty() {
+                let file = file_path.display().to_string();
+                let lines: Vec<String> = warnings.iter().map(|v| format!("{file} {v}")).collect();
+                println!("{}", lines.join("\n").yellow());
+            }
+            html
+        }
+        value => template.replace("<dioscript />", &result_html(&value)?),
+    };
+    if !PathBuf::from(out_dir).is_dir() {
+        create_dir_all(out_dir)?;
     }
+    let output = page_path(out_dir, file_stem);
+    std::fs::write(&output, html)?;
+    Ok(output.display().to_string())
+}
+
+// every entry fills the `<dioscript name="key" />` slots of the template. template is scanned
+// once, so html of an entry is never searched for slots of the other entries. slots without
+// entry are left empty, entries and slots which do not match are returned as warnings.
+fn fill_slots(
+    template: &str,
+    slots: &HashMap<String, Value>,
+) -> anyhow::Result<(String, Vec<String>)> {
+    const OPEN: &str = "<dioscript name=\"";
+    const CLOSE: &str = "\" />";
+    let mut html = String::with_capacity(template.len());
+    let mut rendered: HashMap<&str, String> = HashMap::new();
+    let mut unfilled: Vec<&str> = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let end = match after.find(CLOSE) {
+            Some(end) if !after[..end].contains(['"', '<', '>']) => end,
+            // not a slot, kept as it is.
+            _ => {
+                html.push_str(&rest[..start + OPEN.len()]);
+                rest = after;
+                continue;
+            }
+        };
+        html.push_str(&rest[..start]);
+        let name = &after[..end];
+        match slots.get(name) {
+            Some(value) => {
+                if !rendered.contains_key(name) {
+                    rendered.insert(name, result_html(value)?);
+                }
+                html.push_str(&rendered[name]);
+            }
+            None => {
+                if !unfilled.contains(&name) {
+                    unfilled.push(name);
+                }
+            }
+        }
+        rest = &after[end + CLOSE.len()..];
+    }
+    html.push_str(rest);
+
+    let mut warnings: Vec<String> = unfilled
+        .iter()
+        .map(|name| format!("template slot `{name}` is not filled by the result."))
+        .collect();
+    let mut unknown: Vec<&String> = slots
+        .keys()
+        .filter(|name| !rendered.contains_key(name.as_str()))
+        .collect();
+    unknown.sort();
+    for name in unknown {
+        warnings.push(format!(
+            "result entry `{name}` has no slot in the template."
+        ));
+    }
+    Ok((html, warnings))
+}
+
+// strings are written as they are, lists are joined.
+fn result_html(value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::Element(e) => Ok(e.to_html()),
+        Value::String(v) => Ok(v.to_string()),
+        Value::List(list) => {
+            let mut html = String::new();
+            for item in list.iter() {
+                html.push_str(&result_html(item)?);
+            }
+            Ok(html)
+        }
+        value => Err(anyhow!(
+            "result data type `{}` cannot be built to html",
+            value.value_name()
+        )),
+    }
+}
+
+// `templates/<name>.html` of the page first, then the shared `template.html`, both next to the
+// script. pages without template get the placeholder only.
+fn find_template(file_path: &Path, file_stem: &OsStr) -> anyhow::Result<String> {
+    let dir = file_path.parent().unwrap_or(Path::new(""));
+    let mut name = file_stem.to_os_string();
+    name.push(".html");
+    for file in [dir.join("templates").join(name), dir.join("template.html")] {
+        if file.is_file() {
+            return Ok(read_to_string(file)?);
+        }
+    }
+    Ok("<dioscript />".to_string())
+}
+
+// joined instead of formatted with `/`, which is not a separator in windows verbatim paths
+// (`\\?\UNC\server\share`). stem is kept as `OsStr`, it may not be valid utf-8.
+fn page_path(out_dir: &str, file_stem: &OsStr) -> PathBuf {
+    let mut name = file_stem.to_os_string();
+    name.push(".html");
+    Path::new(out_dir).join(name)
 }
 
 pub enum BuildTarget {
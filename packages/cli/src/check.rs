@@ -0,0 +1,61 @@
+use std::{fs::read_to_string, path::PathBuf};
+
+use colored::*;
+use dioscript_parser::ast::{DioscriptAst, Span};
+use dioscript_runtime::error::Location;
+
+use crate::{crash, CheckArgs};
+
+// parse and analyze script without executing it, returns the number of problems.
+pub fn check(args: &CheckArgs) -> anyhow::Result<usize> {
+    let file_path = PathBuf::from(&args.file);
+    crash::set_file(&file_path);
+    let code = read_to_string(&file_path)?;
+
+    // every syntax error is reported, the partial ast is not analyzed.
+    let (ast, diagnostics) = DioscriptAst::from_string_lossy(&code);
+    for diagnostic in diagnostics.iter() {
+        match (diagnostic.line, diagnostic.column) {
+            (Some(line), Some(column)) => println!(
+                "{}:{}:{} {}",
+                args.file,
+                line,
+                column,
+                diagnostic.message.red().bold()
+            ),
+            _ => println!("{} {}", args.file, diagnostic.message.red().bold()),
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Ok(diagnostics.len());
+    }
+    for warning in ast.warnings() {
+        println!(
+            "{} {}",
+            location(&args.file, &code, warning.span()),
+            warning.to_string().yellow()
+        );
+    }
+
+    let mut runtime = dioscript_runtime::Runtime::new();
+    if let Some(parent) = file_path.parent() {
+        runtime.set_root_path(parent);
+    }
+    let issues = runtime.analyze(&ast);
+    for issue in issues.iter() {
+        println!(
+            "{} {}",
+            location(&args.file, &code, issue.span),
+            issue.to_string().red().bold()
+        );
+    }
+    Ok(issues.len())
+}
+
+// `file:line:col` of the statement, like syntax errors are printed.
+fn location(file: &str, code: &str, span: Span) -> String {
+    match Location::new(code, span) {
+        Some(location) => format!("{}:{}:{}", file, location.line, location.column),
+        None => file.to_string(),
+    }
+}
@@ -0,0 +1,100 @@
+use dioscript_runtime::{module::ModuleItem, Runtime};
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context, Helper,
+};
+
+// module item which can be completed after `path::`.
+struct Entry {
+    path: Vec<String>,
+    name: String,
+    display: String,
+    replacement: String,
+}
+
+// completes module paths of runtime: `std::str<Tab>` => `std::string::`.
+#[derive(Default)]
+pub struct ReplHelper {
+    entries: Vec<Entry>,
+}
+
+impl ReplHelper {
+    // modules can be bound while executing, so items are collected again before every input.
+    pub fn refresh(&mut self, runtime: &Runtime) {
+        self.entries.clear();
+        self.collect(runtime, vec![]);
+    }
+
+    fn collect(&mut self, runtime: &Runtime, path: Vec<String>) {
+        let items = match runtime.module_items(&path) {
+            Some(items) => items,
+            None => return,
+        };
+        for (name, item) in items {
+            let (display, replacement) = match &item {
+                ModuleItem::SubModule(_) => (format!("{name}::"), format!("{name}::")),
+                // arity hint: `len(1)`, `join(1+)` for variadic functions.
+                ModuleItem::Function(f) => match f.arity() {
+                    (arity, true) => (format!("{name}({arity}+)"), name.clone()),
+                    (arity, false) => (format!("{name}({arity})"), name.clone()),
+                },
+                ModuleItem::Variable(_) => (name.clone(), name.clone()),
+            };
+            self.entries.push(Entry {
+                path: path.clone(),
+                name: name.clone(),
+                display,
+                replacement,
+            });
+            if let ModuleItem::SubModule(_) = item {
+                let mut sub = path.clone();
+                sub.push(name);
+                self.collect(runtime, sub);
+            }
+        }
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .map(|v| v + 1)
+            .unwrap_or(0);
+        let word = &line[start..pos];
+        let (path, partial) = match word.rsplit_once("::") {
+            Some((path, partial)) => (path.split("::").collect::<Vec<_>>(), partial),
+            None => (vec![], word),
+        };
+        let candidates = self
+            .entries
+            .iter()
+            .filter(|v| v.path == path && v.name.starts_with(partial))
+            .map(|v| Pair {
+                display: v.display.clone(),
+                replacement: v.replacement.clone(),
+            })
+            .collect();
+        Ok((pos - partial.len(), candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
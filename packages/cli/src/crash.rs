@@ -0,0 +1,97 @@
+use std::{
+    backtrace::Backtrace,
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use colored::*;
+
+const ISSUES: &str = "https://github.com/mrxiaozhuox/dioscript/issues";
+
+thread_local! {
+    // file handled by current thread, build pages run on rayon threads.
+    static FILE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+// replace default panic output with a local crash report, nothing is sent anywhere.
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        // output piped into a reader that quit early (`ds explain 38 | head`) is not a crash.
+        if is_broken_pipe(info) {
+            std::process::exit(0);
+        }
+        let report = crash_report(info);
+        let name = format!(
+            "ds-crash-{}.txt",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|v| v.as_millis())
+                .unwrap_or(0)
+        );
+        let path = std::env::temp_dir().join(name);
+        eprintln!(
+            "\n[ds] {} {}",
+            "ds crashed:".red().bold(),
+            panic_message(info)
+        );
+        match std::fs::write(&path, report) {
+            Ok(_) => eprintln!(
+                "[ds] crash report is written to {}",
+                path.display().to_string().cyan()
+            ),
+            Err(e) => eprintln!("[ds] crash report cannot be written: {e}"),
+        }
+        eprintln!("[ds] please file an issue at {ISSUES} and attach the report.\n");
+    }));
+}
+
+// record file handled by current thread, it is hashed into crash report.
+pub fn set_file(path: &Path) {
+    FILE.with(|v| *v.borrow_mut() = Some(path.to_path_buf()));
+}
+
+fn crash_report(info: &PanicHookInfo) -> String {
+    // only a hash of file path is kept, so report does not reveal local directories.
+    let file = FILE.with(|v| {
+        v.borrow().as_ref().map(|path| {
+            let mut hasher = DefaultHasher::new();
+            path.hash(&mut hasher);
+            format!("{:016x}", hasher.finish())
+        })
+    });
+    let location = info
+        .location()
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!(
+        "ds version: {}\nplatform: {} {}\nmessage: {}\nlocation: {}\nfile hash: {}\n\nbacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        panic_message(info),
+        location,
+        file.unwrap_or_else(|| "none".to_string()),
+        Backtrace::force_capture()
+    )
+}
+
+// `println!` panics when stdout is closed, the message ends with the `EPIPE` io error.
+fn is_broken_pipe(info: &PanicHookInfo) -> bool {
+    let message = panic_message(info);
+    message.starts_with("failed printing to std") && message.contains("Broken pipe")
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
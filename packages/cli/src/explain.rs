@@ -0,0 +1,68 @@
+use colored::*;
+
+use crate::ExplainArgs;
+
+// extended explanations of error codes, they are embedded in the binary.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("E0001", include_str!("explain/E0001.md")),
+    ("E0002", include_str!("explain/E0002.md")),
+    ("E0003", include_str!("explain/E0003.md")),
+    ("E0004", include_str!("explain/E0004.md")),
+    ("E0005", include_str!("explain/E0005.md")),
+    ("E0006", include_str!("explain/E0006.md")),
+    ("E0007", include_str!("explain/E0007.md")),
+    ("E0010", include_str!("explain/E0010.md")),
+    ("E0011", include_str!("explain/E0011.md")),
+    ("E0012", include_str!("explain/E0012.md")),
+    ("E0013", include_str!("explain/E0013.md")),
+    ("E0014", include_str!("explain/E0014.md")),
+    ("E0015", include_str!("explain/E0015.md")),
+    ("E0016", include_str!("explain/E0016.md")),
+    ("E0017", include_str!("explain/E0017.md")),
+    ("E0018", include_str!("explain/E0018.md")),
+    ("E0019", include_str!("explain/E0019.md")),
+    ("E0020", include_str!("explain/E0020.md")),
+    ("E0021", include_str!("explain/E0021.md")),
+    ("E0022", include_str!("explain/E0022.md")),
+    ("E0023", include_str!("explain/E0023.md")),
+    ("E0024", include_str!("explain/E0024.md")),
+    ("E0025", include_str!("explain/E0025.md")),
+    ("E0026", include_str!("explain/E0026.md")),
+    ("E0027", include_str!("explain/E0027.md")),
+    ("E0028", include_str!("explain/E0028.md")),
+    ("E0029", include_str!("explain/E0029.md")),
+    ("E0030", include_str!("explain/E0030.md")),
+    ("E0031", include_str!("explain/E0031.md")),
+    ("E0032", include_str!("explain/E0032.md")),
+    ("E0033", include_str!("explain/E0033.md")),
+    ("E0034", include_str!("explain/E0034.md")),
+    ("E0035", include_str!("explain/E0035.md")),
+    ("E0036", include_str!("explain/E0036.md")),
+    ("E0037", include_str!("explain/E0037.md")),
+    ("E0038", include_str!("explain/E0038.md")),
+];
+
+pub fn explain(args: &ExplainArgs) -> anyhow::Result<()> {
+    // `e13`, `13` and `E0013` are the same code.
+    let digits = args.code.trim_start_matches(['E', 'e']);
+    let code = match digits.parse::<u32>() {
+        Ok(number) => format!("E{number:04}"),
+        Err(_) => return Err(anyhow::anyhow!("`{}` is not an error code", args.code)),
+    };
+    let text = match EXPLANATIONS.iter().find(|(v, _)| *v == code) {
+        Some((_, text)) => text,
+        None => return Err(anyhow::anyhow!("no explanation for error code `{code}`")),
+    };
+    println!("{}\n", code.red().bold());
+    let mut example = false;
+    for line in text.lines() {
+        if line.starts_with("```") {
+            example = !example;
+        } else if example {
+            println!("    {}", line.cyan());
+        } else {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+// history file of playground: `--history` option, `DS_HISTORY` variable, then state directory
+// of user: `$XDG_STATE_HOME/dioscript/history`, `~/.local/state/dioscript/history` or
+// `%LOCALAPPDATA%\dioscript\history` on windows.
+pub fn history_path(option: Option<&str>) -> Option<PathBuf> {
+    if let Some(path) = option {
+        return Some(PathBuf::from(path));
+    }
+    if let Some(path) = env_path("DS_HISTORY") {
+        return Some(path);
+    }
+    let dir = if cfg!(windows) {
+        env_path("LOCALAPPDATA")?
+    } else {
+        match env_path("XDG_STATE_HOME") {
+            Some(dir) => dir,
+            None => env_path("HOME")?.join(".local").join("state"),
+        }
+    };
+    Some(dir.join("dioscript").join("history"))
+}
+
+// empty variables are treated as unset.
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
@@ -0,0 +1,51 @@
+use std::{fs::read_to_string, path::PathBuf};
+
+use anyhow::anyhow;
+use colored::*;
+use dioscript_parser::ast::DioscriptAst;
+use dioscript_runtime::{
+    a11y::{self, A11yIssue},
+    error::{Error, Location},
+};
+
+use crate::{crash, report, LintArgs};
+
+// execute script and check accessibility of the rendered elements, returns the number of problems.
+pub fn lint(args: &LintArgs) -> anyhow::Result<usize> {
+    let file_path = PathBuf::from(&args.file);
+    crash::set_file(&file_path);
+    let code = read_to_string(&file_path)?;
+
+    let mut runtime = dioscript_runtime::Runtime::new();
+    if let Some(parent) = file_path.parent() {
+        runtime.set_root_path(parent);
+    }
+    let ast = DioscriptAst::from_string(&code).map_err(Error::from)?;
+    let result = match runtime.execute_ast(ast) {
+        Ok(result) => result,
+        Err(mut e) => {
+            e.locate(&code);
+            return Err(anyhow!(report::runtime_error(&args.file, &code, &e)));
+        }
+    };
+    let issues = a11y::check(&result);
+    for line in issue_lines(&args.file, &code, &issues) {
+        println!("{}", line.red().bold());
+    }
+    Ok(issues.len())
+}
+
+// `file:line:column message`, the position is left out for elements without source.
+pub fn issue_lines(file: &str, source: &str, issues: &[A11yIssue]) -> Vec<String> {
+    let mut lines = vec![];
+    for issue in issues {
+        match issue.span.and_then(|v| Location::new(source, v)) {
+            Some(location) => lines.push(format!(
+                "{file}:{}:{} {issue}",
+                location.line, location.column
+            )),
+            None => lines.push(format!("{file} {issue}")),
+        }
+    }
+    lines
+}
@@ -1,9 +1,25 @@
-use std::{fs::File, io::Write, path::PathBuf, time::Instant};
+use std::{
+    fs::File,
+    io::{IsTerminal, Write},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    time::Instant,
+};
 
 use clap::{Args, Parser, Subcommand};
 use colored::*;
+use completion::ReplHelper;
+use dioscript_parser::ast::DioAstStatement;
+use rustyline::{error::ReadlineError, history::DefaultHistory, CompletionType, Config, Editor};
 
 mod builder;
+mod check;
+mod completion;
+mod crash;
+mod explain;
+mod history;
+mod lint;
+mod report;
 
 #[derive(Parser)]
 #[command(name = "ds")]
@@ -17,19 +33,22 @@ struct Dsc {
 #[derive(Subcommand)]
 enum Commands {
     Build(BuildArgs),
+    Check(CheckArgs),
+    Explain(ExplainArgs),
+    Lint(LintArgs),
     Playground(PlaygroundArgs),
 }
 
 #[derive(Args)]
 pub struct BuildArgs {
-    /// `.ds` file path
+    /// `.ds` file path, or directory to build every `.ds` page in it
     file: String,
 
     /// build target
     #[arg(long, default_value = "static")]
     target: String,
 
-    /// html template for build
+    /// html template for build, `templates/<name>.html` or `template.html` next to the page by default
     #[arg(long)]
     template: Option<String>,
 
@@ -44,26 +63,69 @@ pub struct BuildArgs {
     /// use quiet mode
     #[arg(long, default_value_t = false)]
     quiet: bool,
+
+    /// check accessibility of built pages, problems are reported as warnings
+    #[arg(long, default_value_t = false)]
+    a11y: bool,
+
+    /// treat page results as untrusted: escape text, remove elements and attributes which run script
+    #[arg(long, default_value_t = false)]
+    sanitize: bool,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// `.ds` file path
+    file: String,
+}
+
+#[derive(Args)]
+pub struct LintArgs {
+    /// `.ds` file path, it is executed and its result is checked
+    file: String,
 }
 
 #[derive(Args)]
-pub struct PlaygroundArgs {}
+pub struct ExplainArgs {
+    /// error code, e.g. `E0013`
+    code: String,
+}
+
+#[derive(Args)]
+pub struct PlaygroundArgs {
+    /// history file, default is `dioscript/history` in state directory of user
+    #[arg(long)]
+    history: Option<String>,
+
+    /// max number of history entries, `0` disables history file
+    #[arg(long, default_value_t = 1000)]
+    history_size: usize,
+}
 
 pub fn main() {
+    crash::install();
+    // windows console prints escape codes of colors as text, unless it is switched to
+    // virtual terminal mode (available since windows 10).
+    #[cfg(windows)]
+    let _ = colored::control::set_virtual_terminal(true);
     let cli = Dsc::parse();
     match &cli.command {
         Commands::Build(args) => {
             let timer = Instant::now();
-            let r = builder::build(&args);
+            let r = builder::build(args);
             let duration = timer.elapsed();
             match r {
                 Err(e) => {
-                    println!("[ds] Build failed: {}", e.to_string().red().bold());
+                    println!("[ds] {}\n{}", "Build failed:".red().bold(), e);
                     std::process::exit(1);
                 }
                 Ok(v) => {
                     if args.open {
-                        let _ = opener::open(&v);
+                        // not `canonicalize`: browsers do not open its `\\?\` verbatim paths.
+                        let path = std::path::absolute(&v).unwrap_or_else(|_| PathBuf::from(&v));
+                        if let Err(e) = opener::open(&path) {
+                            println!("[ds] {} {e}", "HTML file cannot be opened:".yellow());
+                        }
                     }
                     if !args.quiet {
                         println!();
@@ -86,7 +148,35 @@ pub fn main() {
                 }
             }
         }
-        Commands::Playground(_args) => {
+        Commands::Check(args) => match check::check(args) {
+            Ok(0) => println!("[ds] 🚀 {}", "no problems found.".green().bold()),
+            Ok(count) => {
+                println!("[ds] {} problem(s) found.", count.to_string().red().bold());
+                std::process::exit(1);
+            }
+            Err(e) => {
+                println!("[ds] Check failed: {}", e.to_string().red().bold());
+                std::process::exit(1);
+            }
+        },
+        Commands::Lint(args) => match lint::lint(args) {
+            Ok(0) => println!("[ds] 🚀 {}", "no problems found.".green().bold()),
+            Ok(count) => {
+                println!("[ds] {} problem(s) found.", count.to_string().red().bold());
+                std::process::exit(1);
+            }
+            Err(e) => {
+                println!("[ds] Lint failed:\n{e}");
+                std::process::exit(1);
+            }
+        },
+        Commands::Explain(args) => {
+            if let Err(e) = explain::explain(args) {
+                println!("[ds] {}", e.to_string().red().bold());
+                std::process::exit(1);
+            }
+        }
+        Commands::Playground(args) => {
             println!("\n{}", "Welcome to `Dioscript` playground!".blue().bold());
             println!(
                 "{}",
@@ -96,19 +186,90 @@ pub fn main() {
             );
             let mut record = String::new();
             let mut code_buffer: Vec<_> = Vec::new();
-            let mut readline = rustyline::DefaultEditor::new().expect("init stdin failed.");
+            // candidates are listed with arity hints, instead of cycled through.
+            let config = Config::builder()
+                .completion_type(CompletionType::List)
+                .max_history_size(args.history_size)
+                .expect("history size is invalid.")
+                .auto_add_history(true)
+                .build();
+            let mut readline: Editor<ReplHelper, DefaultHistory> =
+                Editor::with_config(config).expect("init stdin failed.");
+            readline.set_helper(Some(ReplHelper::default()));
+            let mut history = match args.history_size {
+                0 => None,
+                _ => history::history_path(args.history.as_deref()),
+            };
+            if let Some(path) = &history {
+                if let Some(parent) = path.parent() {
+                    let _ = std::fs::create_dir_all(parent);
+                }
+                // file does not exist in first session.
+                let _ = readline.load_history(path);
+            }
             let mut runtime = dioscript_runtime::Runtime::new();
+            // `Ctrl-C` stops running code instead of the playground, line editor handles it
+            // itself while reading input.
+            let interrupt = runtime.interrupt_handle();
+            let handle = interrupt.clone();
+            if let Err(e) = ctrlc::set_handler(move || handle.interrupt()) {
+                println!("[ds] {} {e}", "Ctrl-C cannot be handled:".yellow());
+            }
+            // definitions are kept for later inputs, unless every input runs in a child scope.
+            let mut isolate = false;
             loop {
-                let input = readline.readline(">> ").unwrap();
+                // continued lines are indented by open braces: `... {2}`.
+                let depth = brace_depth(&code_buffer);
+                if let Some(helper) = readline.helper_mut() {
+                    helper.refresh(&runtime);
+                }
+                let prompt = match depth {
+                    0 => ">> ".to_string(),
+                    depth => format!("... {{{depth}}} "),
+                };
+                let input =
+                    match readline.readline_with_initial(&prompt, (&"    ".repeat(depth), "")) {
+                        Ok(input) => input,
+                        // `Ctrl-C` drops current line, `Ctrl-D` leaves like `.quit`.
+                        Err(ReadlineError::Interrupted) => continue,
+                        Err(_) => ".quit".to_string(),
+                    };
+                // new entries are appended under file lock, so sessions running together keep
+                // history of each other.
+                if let Some(path) = &history {
+                    if let Err(e) = readline.append_history(path) {
+                        println!("[ds] {} {e}", "history cannot be saved:".yellow());
+                        history = None;
+                    }
+                }
+                let input = input.trim().to_string();
                 if input == ".execute" || input == "." {
                     let code = code_buffer.join("\n");
                     let ast = dioscript_parser::ast::DioscriptAst::from_string(&code);
                     println!("{:#?}", ast);
                     match ast {
                         Ok(ast) => {
-                            let result = runtime.execute_ast(ast);
+                            interrupt.reset();
+                            for warning in ast.warnings() {
+                                println!("[ds] Warning: {}", warning.to_string().yellow());
+                            }
+                            // interpreter panic is reported, and playground keeps running.
+                            let result = catch_unwind(AssertUnwindSafe(|| match isolate {
+                                true => runtime.execute_ast(ast),
+                                false => runtime.execute_ast_in_scope(ast),
+                            }));
                             match result {
-                                Ok(r) => {
+                                Err(_) => {
+                                    runtime = dioscript_runtime::Runtime::new();
+                                    runtime.set_interrupt_handle(interrupt.clone());
+                                    println!(
+                                        "[ds] {}\n",
+                                        "runtime is restarted, defined variables are cleared."
+                                            .yellow()
+                                            .bold()
+                                    );
+                                }
+                                Ok(Ok(r)) => {
                                     if !r.as_none() {
                                         println!("\n[ds] Result: {:#?}\n", r);
                                     } else {
@@ -118,18 +279,28 @@ pub fn main() {
                                         );
                                     }
                                 }
-                                Err(e) => {
-                                    println!(
-                                        "\n[ds] Runtime error: {}\n",
-                                        e.to_string().red().bold()
-                                    );
+                                Ok(Err(mut e)) => {
+                                    e.locate(&code);
+                                    println!("\n{}\n", report::runtime_error("input", &code, &e));
                                 }
                             }
                         }
                         Err(e) => {
-                            println!("\n[ds] Parse failed: {}\n", e.to_string().red().bold());
+                            let diagnostic = e.diagnostic();
+                            let location = match (diagnostic.line, diagnostic.column) {
+                                (Some(line), Some(column)) => {
+                                    format!(" (line {line}, column {column})")
+                                }
+                                _ => String::new(),
+                            };
+                            println!(
+                                "\n[ds] Parse failed{}: {}\n",
+                                location,
+                                e.to_string().red().bold()
+                            );
                         }
                     }
+                    runtime.collect_garbage();
                     record = code;
                     code_buffer = Vec::new();
                 } else if input == ".undo" || input == ".u" {
@@ -143,6 +314,58 @@ pub fn main() {
                         "\n🚀 {}\n",
                         "deleted all recorded code line.".yellow().bold()
                     );
+                } else if let Some(name) = input.strip_prefix(".help ") {
+                    match function_docs(&runtime, &record, name.trim()) {
+                        Some(docs) => println!("\n{}\n", docs.cyan()),
+                        None => println!("\n{}\n", "no docs found.".yellow().bold()),
+                    }
+                } else if let Some(command) = input.strip_prefix(".scope") {
+                    match command.trim() {
+                        "push" => {
+                            runtime.push_scope();
+                            println!(
+                                "\n🔰 {}\n",
+                                format!("scope {} is pushed.", runtime.scope_depth())
+                                    .cyan()
+                                    .bold()
+                            );
+                        }
+                        "pop" => {
+                            let depth = runtime.scope_depth();
+                            if runtime.pop_scope() {
+                                println!(
+                                    "\n🚀 {}\n",
+                                    format!("scope {depth} is popped, its definitions are dropped.")
+                                        .yellow()
+                                        .bold()
+                                );
+                            } else {
+                                println!("\n{}\n", "root scope cannot be popped.".yellow().bold());
+                            }
+                        }
+                        "isolate" => {
+                            isolate = !isolate;
+                            let state = match isolate {
+                                true => "every input runs in a child scope.",
+                                false => "definitions of inputs are kept.",
+                            };
+                            println!("\n🔰 {}\n", state.cyan().bold());
+                        }
+                        _ => println!(
+                            "\nscope depth: {}, isolate: {}\nuse `.scope push`, `.scope pop` or `.scope isolate`.\n",
+                            runtime.scope_depth(),
+                            isolate
+                        ),
+                    }
+                } else if input == ".reset" || input == ".r" {
+                    runtime = dioscript_runtime::Runtime::new();
+                    runtime.set_interrupt_handle(interrupt.clone());
+                    println!(
+                        "\n🚀 {}\n",
+                        "runtime is reset, defined variables are cleared."
+                            .yellow()
+                            .bold()
+                    );
                 } else if input == ".trace" || input == ".t" {
                     runtime.trace();
                 } else if input == ".save" || input == ".s" {
@@ -154,9 +377,60 @@ pub fn main() {
                     println!("\n👋 {}\n", "Bye!".green().bold());
                     break;
                 } else {
-                    code_buffer.push(input);
+                    let line = indent_line(&input, depth);
+                    // closing brace is dedented, redraw the line which was filled with indent.
+                    if input.starts_with('}') && std::io::stdout().is_terminal() {
+                        println!("\x1b[1A\r\x1b[2K{prompt}{line}");
+                    }
+                    code_buffer.push(line);
                 }
             }
         }
     }
 }
+
+// open `{` in recorded lines, braces in strings and comments are skipped.
+fn brace_depth(lines: &[String]) -> usize {
+    let mut depth = 0usize;
+    for line in lines {
+        let mut chars = line.chars().peekable();
+        let mut in_string = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if in_string => {
+                    chars.next();
+                }
+                '"' => in_string = !in_string,
+                '/' if !in_string && chars.peek() == Some(&'/') => break,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    depth
+}
+
+// line indented by depth of braces, a line starts with `}` closes one level first.
+fn indent_line(line: &str, depth: usize) -> String {
+    let depth = if line.starts_with('}') {
+        depth.saturating_sub(1)
+    } else {
+        depth
+    };
+    format!("{}{line}", "    ".repeat(depth))
+}
+
+// docs of function from runtime (prelude and modules), or defined in last executed code.
+fn function_docs(runtime: &dioscript_runtime::Runtime, code: &str, name: &str) -> Option<String> {
+    if let Some(docs) = runtime.function_docs(name) {
+        return Some(docs);
+    }
+    let ast = dioscript_parser::ast::DioscriptAst::from_string(code).ok()?;
+    ast.stats.into_iter().find_map(|stat| match stat.kind {
+        DioAstStatement::FunctionDefine(define) if define.name.as_deref() == Some(name) => {
+            define.docs
+        }
+        _ => None,
+    })
+}
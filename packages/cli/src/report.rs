@@ -0,0 +1,125 @@
+use std::{fs::read_to_string, path::Path};
+
+use colored::*;
+use dioscript_runtime::error::RuntimeError;
+
+// runtime error with the failed source line, a caret under the statement, the running
+// functions and a hint to fix it. `colored` leaves colors out when `NO_COLOR` is set.
+pub fn runtime_error(file: &str, source: &str, e: &RuntimeError) -> String {
+    let mut lines = vec![format!(
+        "{} {}",
+        format!("error[{}]:", e.code()).red().bold(),
+        e.root().to_string().bold()
+    )];
+    let mut pad = String::new();
+    if let Some(location) = e.location() {
+        let number = location.line.to_string();
+        pad = " ".repeat(number.len());
+        // statements of used files are shown from that file, its path is relative to the script.
+        let (file, used) = match &location.file {
+            Some(path) => {
+                let path = script_dir(file).join(path);
+                let used = read_to_string(&path).unwrap_or_default();
+                (path.display().to_string(), Some(used))
+            }
+            None => (file.to_string(), None),
+        };
+        let code = used
+            .as_deref()
+            .unwrap_or(source)
+            .lines()
+            .nth(location.line - 1)
+            .unwrap_or("");
+        // keep tabs before the statement, so the caret stays under it.
+        let indent: String = code
+            .chars()
+            .take(location.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = location.snippet.chars().count().max(1);
+        lines.push(format!(
+            "{pad}{} {file}:{}:{}",
+            "-->".blue().bold(),
+            location.line,
+            location.column
+        ));
+        lines.push(format!("{pad} {}", "|".blue().bold()));
+        lines.push(format!(
+            "{} {} {code}",
+            number.blue().bold(),
+            "|".blue().bold()
+        ));
+        lines.push(format!(
+            "{pad} {} {indent}{}",
+            "|".blue().bold(),
+            "^".repeat(width).red().bold()
+        ));
+    }
+    if !e.trace().is_empty() {
+        lines.push(format!("{pad} {} stack trace:", "=".blue().bold()));
+        for frame in e.trace() {
+            let call_site = match &frame.location {
+                Some(location) => {
+                    let path = match &location.file {
+                        Some(path) => script_dir(file).join(path).display().to_string(),
+                        None => file.to_string(),
+                    };
+                    format!(", called at {path}:{}:{}", location.line, location.column)
+                }
+                None => String::new(),
+            };
+            lines.push(format!(
+                "{pad}     at `{}`{call_site}",
+                frame.function.cyan()
+            ));
+        }
+    }
+    if let Some(help) = suggestion(e.root()) {
+        lines.push(format!(
+            "{pad} {} {} {help}",
+            "=".blue().bold(),
+            "help:".green().bold()
+        ));
+    }
+    lines.push(format!(
+        "{pad} {} run `ds explain {}` for more information",
+        "=".blue().bold(),
+        e.code()
+    ));
+    lines.join("\n")
+}
+
+fn suggestion(e: &RuntimeError) -> Option<String> {
+    let help = match e {
+        RuntimeError::VariableNotFound { name } => {
+            format!("define it before it is used: `let {name} = ...;`")
+        }
+        RuntimeError::FunctionNotFound { name } => {
+            format!("define `fn {name}()` or `use` the module which provides it")
+        }
+        RuntimeError::UnknownMethod { value, .. } => {
+            format!("check the method name, or use `typeof` if the value may not be `{value}`")
+        }
+        RuntimeError::UnknownAttribute { .. } | RuntimeError::IndexNotFound { .. } => {
+            "use `??` to fall back to a default value: `value ?? default`".to_string()
+        }
+        RuntimeError::TypeMismatch { .. } => {
+            "convert the value or change the type annotation".to_string()
+        }
+        RuntimeError::ModuleNotFound { .. } => {
+            "modules are bound by the host, script files are used with `use \"file.ds\";`"
+                .to_string()
+        }
+        RuntimeError::IllegalTypeInConditional { .. } => {
+            "conditions must be `boolean`, compare the value instead: `if value != none`"
+                .to_string()
+        }
+        _ => return None,
+    };
+    Some(help)
+}
+
+// used files are relative to the directory of the script.
+fn script_dir(file: &str) -> &Path {
+    Path::new(file).parent().unwrap_or(Path::new("."))
+}
@@ -1,15 +1,49 @@
-use dioscript_runtime::types::Value;
+use std::ops::Range;
+
+use dioscript_runtime::{
+    error::{Error, RuntimeError},
+    types::Value,
+};
 use dioxus::prelude::*;
 
+pub use dioscript_runtime::{interrupt::InterruptHandle, report::ExecutionLimits};
+
+// most fuel a retry of `View` may ask for, a longer run keeps the page busy for too long.
+pub const MAX_FUEL: u64 = 100_000_000;
+
+// fuel of the first run of `View`, see `run_in_chunks`.
+const CHUNK_FUEL: u64 = 100_000;
+
+// script runs on the page thread in chunks, see `run_in_chunks`, so a stopped `interrupt`,
+// e.g. by a button, ends it while it runs. `on_retry` is offered when the fuel of `limits` is
+// used up and it is below `MAX_FUEL`.
 #[allow(non_snake_case)]
 #[component]
-pub fn View(code: String) -> Element {
-    let mut rt = dioscript_runtime::Runtime::new();
-    let result = rt.execute(&code);
-    match result {
+pub fn View(
+    code: String,
+    interrupt: Option<InterruptHandle>,
+    limits: Option<ExecutionLimits>,
+    markers: Option<bool>,
+    on_retry: Option<EventHandler<()>>,
+) -> Element {
+    let outcome = use_resource(use_reactive(
+        (&code, &interrupt, &limits),
+        |(code, interrupt, limits)| run_in_chunks(code, interrupt.unwrap_or_default(), limits),
+    ));
+    let outcome = outcome.read();
+    let outcome = match outcome.as_ref() {
+        Some(outcome) => outcome,
+        None => {
+            return rsx! {
+                div { class: "font-semibold text-gray-500", "Running..." }
+            }
+        }
+    };
+    let issues = outcome.issues.clone();
+    match &outcome.result {
         Ok(result) => {
             let html = match result {
-                Value::String(s) => s,
+                Value::String(s) => s.to_string(),
                 Value::Element(e) => e.to_html(),
                 _ => String::new(),
             };
@@ -18,17 +52,344 @@ pub fn View(code: String) -> Element {
                     id: "dioscript",
                     dangerous_inner_html: "{html}"
                 }
+                Problems { issues }
+            }
+        }
+        Err(Error::Runtime(e)) if matches!(e.root(), RuntimeError::Interrupted) => {
+            rsx! {
+                div { class: "font-semibold text-gray-500", "Execution cancelled." }
+            }
+        }
+        Err(Error::Runtime(_)) if outcome.out_of_fuel => {
+            let fuel = limits.and_then(|v| v.fuel).unwrap_or(0);
+            rsx! {
+                div {
+                    class: "font-semibold text-gray-500",
+                    "Stopped: out of fuel after {fuel} steps, the script may loop forever."
+                }
+                if let (Some(on_retry), true) = (on_retry, fuel < MAX_FUEL) {
+                    button {
+                        class: "bg-indigo-500 hover:bg-indigo-700 text-white text-xs mt-2 py-1 px-2 rounded",
+                        onclick: move |_| on_retry.call(()),
+                        "Retry with more fuel"
+                    }
+                }
+            }
+        }
+        // syntax errors with position are underlined by the editor, see `markers`.
+        Err(Error::Parse(e)) if markers.unwrap_or(false) && e.diagnostic().line.is_some() => {
+            rsx! {
+                Problems { issues }
+            }
+        }
+        Err(Error::Runtime(e)) => {
+            let message = match e.location() {
+                Some(location) if location.file.is_some() => format!(
+                    "Error at {} line {}: {}",
+                    location.file.as_deref().unwrap_or(""),
+                    location.line,
+                    e.root()
+                ),
+                Some(location) => format!("Error at line {}: {}", location.line, e.root()),
+                None => format!("Error: {}", e.root()),
+            };
+            let snippet = e.location().map(|v| v.snippet.trim().to_string());
+            let trace: Vec<String> = e.trace().iter().map(|v| v.function.clone()).collect();
+            rsx! {
+                div { class: "font-semibold", "{message}" }
+                if let Some(snippet) = snippet {
+                    pre { class: "text-xs", "{snippet}" }
+                }
+                for function in trace {
+                    div { class: "text-xs text-gray-500", "at `{function}`" }
+                }
+                Problems { issues }
             }
         }
         Err(e) => {
             let message = e.to_string();
+            let info = e.info();
+            let location = location(info.line, info.column);
             rsx! {
                 div { class: "font-semibold", "Error: {message}" }
+                div { class: "text-xs", "{location}" }
+                Problems { issues }
             }
         },
     }
 }
 
+// script executed by `View`.
+struct Outcome {
+    result: Result<Value, Error>,
+    // call problems found before execution, e.g. unknown function or wrong arguments number.
+    issues: Vec<String>,
+    // stopped for using up the fuel of `limits`.
+    out_of_fuel: bool,
+}
+
+// runtime can not pause a script, so it runs again from the start with doubled fuel, from
+// `CHUNK_FUEL` up to the fuel of `limits`, and the page handles its events between the runs.
+// a script which uses up its fuel takes twice the steps at most, and its `std::print` output
+// is repeated for every run.
+async fn run_in_chunks(
+    code: String,
+    interrupt: InterruptHandle,
+    limits: Option<ExecutionLimits>,
+) -> Outcome {
+    let limits = limits.unwrap_or_default();
+    let issues: Vec<String> = match dioscript_parser::ast::DioscriptAst::from_string(&code) {
+        Ok(ast) => dioscript_runtime::Runtime::new()
+            .analyze(&ast)
+            .iter()
+            .map(|v| v.to_string())
+            .collect(),
+        Err(_) => vec![],
+    };
+    let mut fuel = CHUNK_FUEL;
+    loop {
+        let chunk = match limits.fuel {
+            Some(limit) => fuel.min(limit),
+            None => fuel,
+        };
+        let mut rt = dioscript_runtime::Runtime::new();
+        rt.set_interrupt_handle(interrupt.clone());
+        rt.set_limits(ExecutionLimits {
+            fuel: Some(chunk),
+            ..limits
+        });
+        let result = rt.execute(&code);
+        let out_of_fuel = matches!(
+            &result,
+            Err(Error::Runtime(e)) if matches!(e.root(), RuntimeError::ExecutionLimitExceeded { .. })
+        ) && rt.remaining_fuel() == Some(0);
+        if out_of_fuel && limits.fuel != Some(chunk) {
+            yield_to_page().await;
+            fuel = chunk.saturating_mul(2);
+            continue;
+        }
+        return Outcome {
+            result,
+            issues,
+            out_of_fuel,
+        };
+    }
+}
+
+// let the page handle pending events, e.g. a click on stop button, before the next run.
+async fn yield_to_page() {
+    let mut js = eval("setTimeout(() => dioxus.send(true), 0);");
+    let _ = js.recv().await;
+}
+
+// range of code marked by editor, lines and columns are 1-based like monaco markers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub message: String,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+// every syntax error of code, failed statements are skipped so later errors are marked too.
+// code without syntax errors is executed with `limits`, the statement which raised a runtime
+// error is marked instead.
+pub fn markers(code: &str, limits: Option<ExecutionLimits>) -> Vec<Marker> {
+    let (_, diagnostics) = dioscript_parser::ast::DioscriptAst::from_string_lossy(code);
+    let mut result = vec![];
+    for diagnostic in diagnostics {
+        if let Some(span) = diagnostic.span {
+            result.push(marker(code, span, diagnostic.message));
+        }
+    }
+    if !result.is_empty() {
+        return result;
+    }
+    let mut rt = dioscript_runtime::Runtime::new();
+    if let Some(limits) = limits {
+        rt.set_limits(limits);
+    }
+    if let Err(Error::Runtime(e)) = rt.execute(code) {
+        // statements of used files are not in the editor.
+        let in_code = e.location().map(|v| v.file.is_none()).unwrap_or(false);
+        if let (Some(span), true) = (e.span(), in_code) {
+            result.push(marker(code, span.start..span.end, e.root().to_string()));
+        }
+    }
+    result
+}
+
+fn marker(code: &str, span: Range<usize>, message: String) -> Marker {
+    let (start_line, start_column) = position(&code[..span.start]);
+    let (end_line, mut end_column) = position(&code[..span.end]);
+    // empty span still needs one char to be visible.
+    if (end_line, end_column) == (start_line, start_column) {
+        end_column += 1;
+    }
+    Marker {
+        message,
+        start_line,
+        start_column,
+        end_line,
+        end_column,
+    }
+}
+
+// line and column after text.
+fn position(text: &str) -> (usize, usize) {
+    let line = text.matches('\n').count() + 1;
+    let last = text.rfind('\n').map(|v| v + 1).unwrap_or(0);
+    (line, text[last..].chars().count() + 1)
+}
+
+// position of parse error, empty for errors without it.
+fn location(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!("at line {line}, column {column}"),
+        _ => String::new(),
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn Problems(issues: Vec<String>) -> Element {
+    rsx! {
+        for issue in issues {
+            div { class: "text-xs text-yellow-600", "{issue}" }
+        }
+    }
+}
+
+// generated html source with highlighting, it can be copied or downloaded as a file.
+#[allow(non_snake_case)]
+#[component]
+pub fn SourceView(
+    code: String,
+    interrupt: Option<InterruptHandle>,
+    limits: Option<ExecutionLimits>,
+) -> Element {
+    let mut rt = dioscript_runtime::Runtime::new();
+    if let Some(handle) = interrupt {
+        rt.set_interrupt_handle(handle);
+    }
+    if let Some(limits) = limits {
+        rt.set_limits(limits);
+    }
+    let html = match rt.execute(&code) {
+        Ok(Value::Element(e)) => e.to_pretty_html(),
+        Ok(Value::String(s)) => s.to_string(),
+        Ok(_) => String::new(),
+        Err(e) => {
+            let message = e.to_string();
+            return rsx! {
+                div { class: "font-semibold", "Error: {message}" }
+            };
+        }
+    };
+    let highlighted = highlight_html(&html);
+    let copied = html.clone();
+    rsx! {
+        div {
+            class: "flex flex-row gap-2 mb-2",
+            button {
+                class: "bg-gray-500 hover:bg-gray-700 text-white text-xs py-1 px-2 rounded",
+                onclick: move |_| {
+                    let js = eval("navigator.clipboard.writeText(await dioxus.recv());");
+                    let _ = js.send(copied.clone().into());
+                },
+                "Copy"
+            }
+            button {
+                class: "bg-gray-500 hover:bg-gray-700 text-white text-xs py-1 px-2 rounded",
+                onclick: move |_| {
+                    let js = eval(r#"
+                        let blob = new Blob([await dioxus.recv()], { type: "text/html" });
+                        let link = document.createElement("a");
+                        link.href = URL.createObjectURL(blob);
+                        link.download = "index.html";
+                        link.click();
+                        URL.revokeObjectURL(link.href);
+                    "#);
+                    let _ = js.send(html.clone().into());
+                },
+                "Download"
+            }
+        }
+        div {
+            class: "text-xs w-[550px] h-[630px] overflow-scroll",
+            dangerous_inner_html: "<pre>{highlighted}</pre>"
+        }
+    }
+}
+
+// escape html source and wrap tag names, attribute names and values in colored spans.
+fn highlight_html(source: &str) -> String {
+    let mut output = String::new();
+    let mut in_tag = false;
+    let mut in_value = false;
+    let mut word = String::new();
+    let flush = |word: &mut String, output: &mut String, class: &str| {
+        if !word.is_empty() {
+            output.push_str(&format!("<span class=\"{class}\">{word}</span>"));
+            word.clear();
+        }
+    };
+    for c in source.chars() {
+        match c {
+            '<' if !in_tag => {
+                in_tag = true;
+                output.push_str("&lt;");
+            }
+            '>' if in_tag && !in_value => {
+                let class = word_class(&output);
+                flush(&mut word, &mut output, class);
+                in_tag = false;
+                output.push_str("&gt;");
+            }
+            '"' if in_tag => {
+                if in_value {
+                    word.push_str("&quot;");
+                    flush(&mut word, &mut output, "text-emerald-600");
+                } else {
+                    flush(&mut word, &mut output, "text-amber-600");
+                    word.push_str("&quot;");
+                }
+                in_value = !in_value;
+            }
+            ' ' | '=' if in_tag && !in_value => {
+                let class = word_class(&output);
+                flush(&mut word, &mut output, class);
+                output.push(c);
+            }
+            '/' if in_tag && word.is_empty() => output.push('/'),
+            '&' => push_text(&mut word, &mut output, in_tag, "&amp;"),
+            '<' => push_text(&mut word, &mut output, in_tag, "&lt;"),
+            '>' => push_text(&mut word, &mut output, in_tag, "&gt;"),
+            c if in_tag => word.push(c),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+// the first word of tag is its name, others are attribute names.
+fn word_class(output: &str) -> &'static str {
+    if output.ends_with("&lt;") || output.ends_with("&lt;/") {
+        "text-rose-600"
+    } else {
+        "text-amber-600"
+    }
+}
+
+fn push_text(word: &mut String, output: &mut String, in_tag: bool, text: &str) {
+    if in_tag {
+        word.push_str(text);
+    } else {
+        output.push_str(text);
+    }
+}
+
 #[allow(non_snake_case)]
 #[component]
 pub fn AstView(code: String) -> Element {
@@ -45,8 +406,11 @@ pub fn AstView(code: String) -> Element {
         }
         Err(e) => {
             let message = e.to_string();
+            let info = e.info();
+            let location = location(info.line, info.column);
             rsx! {
                 div { class: "font-semibold", "Error: {message}" }
+                div { class: "text-xs", "{location}" }
             }
         },
     }
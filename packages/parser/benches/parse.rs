@@ -0,0 +1,55 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dioscript_parser::ast::DioscriptAst;
+
+// page of `count` sections with nested elements and loops, 11 lines each.
+fn sections(count: usize) -> String {
+    let mut code = "let items = [\"alpha\", \"beta\", \"gamma\"];\nlet page = div {\n".to_string();
+    code.push_str("    class: \"page\",\n");
+    for i in 0..count {
+        code.push_str(&format!(
+            r#"    section {{
+        id: "section-{i}",
+        h2 {{ class: "title", "Section {i}" }}
+        div {{
+            class: "body",
+            ul {{
+                for item in items {{ return li {{ class: "item", item }}; }}
+            }}
+            p {{ span {{ b {{ i {{ "nested {i}" }} }} }} }}
+        }}
+    }}
+"#
+        ));
+    }
+    code.push_str("};\nreturn page;\n");
+    code
+}
+
+// `depth` elements nested in each other without commas, it was exponential in depth when
+// element items were parsed by backtracking.
+fn nested(depth: usize) -> String {
+    let mut code = "return ".to_string();
+    for _ in 0..depth {
+        code.push_str("div { class: \"x\", ");
+    }
+    code.push_str("\"text\"");
+    for _ in 0..depth {
+        code.push_str(" }");
+    }
+    code.push(';');
+    code
+}
+
+fn parse(c: &mut Criterion) {
+    let code = sections(200);
+    c.bench_function("elements", |b| {
+        b.iter(|| DioscriptAst::from_string(black_box(&code)).unwrap())
+    });
+    let code = nested(24);
+    c.bench_function("nested elements", |b| {
+        b.iter(|| DioscriptAst::from_string(black_box(&code)).unwrap())
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -1,53 +1,410 @@
-use nom::Finish;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+};
+
+use nom::{error::ErrorKind, Finish, Offset};
 
 use crate::{
-    error::ParseError, parser::{parse_rsx, CalcExpr}, types::AstValue
+    element::AstElement,
+    error::{advance_position, Diagnostic, ParseError, ParseWarning},
+    keyword::{
+        check_element, check_expr, check_function, check_statements, check_types, collect_symbols,
+        collect_warnings, object_names,
+    },
+    optimizer::optimize_statements,
+    parser::{
+        deepest_failure, element_node, expr_node, function_node, parse_rsx, parse_statement,
+        CalcExpr, Failure, IResult,
+    },
+    resolver::Resolver,
+    stream::parse_reader,
+    symbol::{self, Symbol},
+    types::AstValue,
 };
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct DioscriptAst {
-    pub stats: Vec<DioAstStatement>,
+    pub stats: Vec<Statement>,
 }
 
 impl DioscriptAst {
     pub fn from_string(message: &str) -> Result<Self, ParseError> {
-        let v = parse_rsx(message).finish();
-        if let Ok((text, ast)) = v {
+        Self::from_string_at(message, 0)
+    }
+
+    // spans of the parsed code start at `offset`, so code of another source, e.g. a used file,
+    // can be told apart from the executed script.
+    pub fn from_string_at(message: &str, offset: usize) -> Result<Self, ParseError> {
+        let mut stats = match parse_rsx(message).finish() {
+            Ok((rest, stats)) if rest.trim().is_empty() => stats,
+            Ok((rest, _)) => {
+                return Err(unmatched_statement(message, rest.trim_start(), 0, (1, 1)))
+            }
+            Err(_) => return Err(parse_failure(message, message, parse_rsx)),
+        };
+        check_statements(&stats)
+            .and_then(|_| check_types(&stats, &object_names(&stats)))
+            .map_err(|e| e.locate(message, 0, (1, 1)))?;
+        Resolver::resolve_statements(&mut stats, message.len() + offset);
+        Ok(DioscriptAst { stats })
+    }
+
+    // warnings of the whole script, e.g. duplicate element attributes.
+    pub fn warnings(&self) -> Vec<ParseWarning> {
+        collect_warnings(&self.stats)
+    }
+
+    // calls and definitions of the whole script, see `Symbols`.
+    pub fn symbols(&self) -> Symbols {
+        collect_symbols(&self.stats)
+    }
+
+    // fold operators of literals before execution, e.g. `2 * 60 * 60` => `7200`.
+    pub fn optimize(&mut self) {
+        optimize_statements(&mut self.stats);
+    }
+
+    // keep parsing after syntax errors, failed statements are skipped to the next statement
+    // boundary and left out of the ast, use for editors which need every problem at once.
+    pub fn from_string_lossy(message: &str) -> (Self, Vec<Diagnostic>) {
+        let mut stats = vec![];
+        let mut diagnostics = vec![];
+        let mut rest = message.trim_start();
+        while !rest.is_empty() {
+            let next = match parse_statement(rest) {
+                Ok((next, stat)) => match check_statements(std::slice::from_ref(&stat)) {
+                    Ok(_) => {
+                        stats.push(stat);
+                        next
+                    }
+                    Err(e) => {
+                        let e = e.locate(message, 0, (1, 1));
+                        diagnostics.push(e.diagnostic());
+                        next
+                    }
+                },
+                Err(_) => {
+                    let error = unmatched_statement(message, rest, 0, (1, 1));
+                    diagnostics.push(error.diagnostic());
+                    skip_statement(rest)
+                }
+            };
+            rest = next.trim_start();
+        }
+        // objects may be defined after the annotations which use them.
+        let objects = object_names(&stats);
+        stats.retain(
+            |stat| match check_types(std::slice::from_ref(stat), &objects) {
+                Ok(_) => true,
+                Err(e) => {
+                    diagnostics.push(e.locate(message, 0, (1, 1)).diagnostic());
+                    false
+                }
+            },
+        );
+        diagnostics.sort_by_key(|v| v.span.as_ref().map(|v| v.start));
+        Resolver::resolve_statements(&mut stats, message.len());
+        (DioscriptAst { stats }, diagnostics)
+    }
+
+    // parse statements incrementally from reader, without loading the whole source first.
+    pub fn from_reader(reader: impl Read) -> Result<Self, ParseError> {
+        parse_reader(reader)
+    }
+}
+
+// parse single expression only, statements and definitions are not included.
+pub fn parse_expression(message: &str) -> Result<CalcExpr, ParseError> {
+    let mut expr = finish_parse(message, expr_node)?;
+    check_expr(&expr).map_err(|e| e.locate(message.trim_end(), 0, (1, 1)))?;
+    Resolver::resolve_expr(&mut expr, message.trim_end().len());
+    Ok(expr)
+}
+
+// parse single function define, e.g. `fn (a, b) { return a + b; }`.
+pub fn parse_function(message: &str) -> Result<FunctionDefine, ParseError> {
+    let mut define = finish_parse(message, function_node)?;
+    check_function(&define).map_err(|e| e.locate(message.trim_end(), 0, (1, 1)))?;
+    Resolver::resolve_function(&mut define, message.trim_end().len());
+    Ok(define)
+}
+
+// parse single element, e.g. `div { class: "a", p { "text" } }`.
+pub fn parse_element(message: &str) -> Result<AstElement, ParseError> {
+    let mut element = finish_parse(message, element_node)?;
+    check_element(&element).map_err(|e| e.locate(message.trim_end(), 0, (1, 1)))?;
+    Resolver::resolve_element(&mut element, message.trim_end().len());
+    Ok(element)
+}
+
+// `source` is the whole input, it is used to locate the failed position.
+fn finish_parse<T>(source: &str, parser: fn(&str) -> IResult<&str, T>) -> Result<T, ParseError> {
+    let message = source.trim();
+    match parser(message).finish() {
+        Ok((text, result)) => {
             if text.trim().is_empty() {
-                Ok(DioscriptAst { stats: ast })
+                Ok(result)
             } else {
                 let content = text.lines().next().unwrap_or("");
+                let offset = source.offset(text.trim_start());
+                let (line, column) = advance_position((1, 1), &source[..offset]);
                 Err(ParseError::UnMatchContent {
                     content: content.to_string(),
+                    offset,
+                    line,
+                    column,
+                    context: vec![],
+                    expected: vec![],
                 })
             }
-        } else {
-            let err = v.err().unwrap();
-            Err(ParseError::ParseFailure {
-                kind: err.code,
-                text: err.input.to_string(),
-            })
         }
+        Err(_) => Err(parse_failure(source, message, parser)),
+    }
+}
+
+// `parser` fails on `message`, a part of `source`. it runs again to find where it fails deepest.
+pub(crate) fn parse_failure<'a, T>(
+    source: &str,
+    message: &'a str,
+    parser: impl FnOnce(&'a str) -> IResult<&'a str, T>,
+) -> ParseError {
+    let failure = deepest_failure(message, parser).unwrap_or_else(|| unknown_failure(message));
+    let offset = source.offset(failure.position);
+    let (line, column) = advance_position((1, 1), &source[..offset]);
+    ParseError::ParseFailure {
+        kind: failure.kind,
+        text: failure.position.to_string(),
+        offset,
+        line,
+        column,
+        context: failure.context,
+        expected: failure.expected,
+    }
+}
+
+// statement at the start of `rest` fails to parse, it is reported where its parsers fail
+// deepest. `rest` is a part of `source`, which starts at byte `base` and at 1-based `start`
+// line and column.
+pub(crate) fn unmatched_statement(
+    source: &str,
+    rest: &str,
+    base: usize,
+    start: (usize, usize),
+) -> ParseError {
+    let failure = deepest_failure(rest, parse_statement).unwrap_or_else(|| unknown_failure(rest));
+    let offset = source.offset(failure.position);
+    let (line, column) = advance_position(start, &source[..offset]);
+    ParseError::UnMatchContent {
+        content: failure.position.lines().next().unwrap_or("").to_string(),
+        offset: base + offset,
+        line,
+        column,
+        context: failure.context,
+        expected: failure.expected,
+    }
+}
+
+// parser does not fail when it runs again, the whole `text` is reported.
+fn unknown_failure(text: &str) -> Failure<'_> {
+    Failure {
+        position: text,
+        kind: ErrorKind::Fail,
+        context: vec![],
+        expected: vec![],
+    }
+}
+
+// rest of code after the failed statement, it ends at `;` or a closed block on top level.
+fn skip_statement(text: &str) -> &str {
+    // brackets which are not closed yet.
+    let mut open = vec![];
+    let mut in_string = false;
+    let mut index = 0;
+    while let Some(c) = text[index..].chars().next() {
+        let rest = &text[index..];
+        index += c.len_utf8();
+        match c {
+            // escaped char is skipped with the backslash.
+            '\\' if in_string => {
+                index += text[index..].chars().next().map_or(0, char::len_utf8);
+            }
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '/' if rest.starts_with("//") => {
+                index += rest.find('\n').unwrap_or(rest.len()) - 1;
+            }
+            '{' | '[' | '(' => open.push(c),
+            // `}` also closes brackets left open inside its block.
+            '}' => {
+                if let Some(block) = open.iter().rposition(|v| *v == '{') {
+                    open.truncate(block);
+                }
+                // the block ends the statement, unless `;` or `else` follows it, e.g. the
+                // element of `return div { ... };`.
+                let after = text[index..].trim_start();
+                if open.is_empty() {
+                    if let Some(next) = after.strip_prefix(';') {
+                        return next;
+                    }
+                    if !after.starts_with("else") {
+                        return &text[index..];
+                    }
+                }
+            }
+            ']' | ')' => {
+                let pair = if c == ']' { '[' } else { '(' };
+                if open.last() == Some(&pair) {
+                    open.pop();
+                }
+            }
+            ';' if open.is_empty() => return &text[index..],
+            _ => {}
+        }
+    }
+    ""
+}
+
+// summary of a script for static analysis. names are keyed by full path: enclosing
+// functions and blocks (`#` and block number), then the name, e.g. `render::#2::item`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    // function calls, method calls of link expression are not included.
+    pub calls: Vec<CallSite>,
+    // named function defines, `None` for a name defined again with other parameters.
+    pub functions: HashMap<String, Option<ParamsType>>,
+    // names bound by variables, parameters, loops, objects and enums. assignments without
+    // `let` may change a variable of any scope, their names are bound at top level.
+    pub bindings: HashSet<String>,
+    // `use a::b;` module paths.
+    pub modules: Vec<Vec<String>>,
+    // `use a::b as c;` => `c`: `["a", "b"]`.
+    pub aliases: HashMap<String, Vec<String>>,
+    // `use "file.ds";` paths.
+    pub files: Vec<String>,
+    // names of `std::module::register("name", ...)` calls with a string literal name.
+    pub registered: Vec<String>,
+}
+
+impl Symbols {
+    // definition of name visible from scope, inner scopes first. the order of statements is
+    // not followed: a name defined after the call is visible too.
+    pub fn lookup(&self, scope: &[String], name: &str) -> Option<Definition<'_>> {
+        for depth in (0..=scope.len()).rev() {
+            let path = symbol_path(&scope[..depth], name);
+            if self.bindings.contains(&path) {
+                return Some(Definition::Binding);
+            }
+            if let Some(params) = self.functions.get(&path) {
+                return Some(Definition::Function(params.as_ref()));
+            }
+        }
+        None
+    }
+}
+
+pub(crate) fn symbol_path(scope: &[String], name: &str) -> String {
+    let mut path = scope.join("::");
+    if !path.is_empty() {
+        path.push_str("::");
     }
+    path.push_str(name);
+    path
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition<'a> {
+    // variable, it may hold any function.
+    Binding,
+    Function(Option<&'a ParamsType>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSite {
+    pub name: FunctionName,
+    // number of arguments, including the piped value.
+    pub arguments: usize,
+    // path of functions and blocks containing the call, see `Symbols`.
+    pub scope: Vec<String>,
+    // statement which contains the call.
+    pub span: Span,
+}
+
+// statement with the code it is parsed from, runtime errors are located by it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub kind: DioAstStatement,
+    pub span: Span,
+    // comments between items of its lists, dicts, tuples, arguments, parameters and chains,
+    // which have no node of their own. comments inside nested statements belong to them.
+    pub comments: Vec<Comment>,
+}
+
+// `// ...` comment without the slashes, trimmed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
+
+// byte range in source code.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DioAstStatement {
     VariableAss(VariableDefine),
+    TupleAss(TupleDefine),
     ReturnValue(CalcExpr),
     IfStatement(ConditionalStatement),
     LoopStatement(LoopStatement),
     LineComment(String),
     FunctionCall(FunctionCall),
+    // `counter.inc();` link expression ending with a method call, it is called for its side
+    // effects and the result is dropped.
+    MethodCall(CalcExpr),
     FunctionDefine(FunctionDefine),
+    Assert(AssertStatement),
+    // `break;` or `break value;`, value becomes result of loop expression.
+    Break(Option<CalcExpr>),
+    // `defer expr;` evaluates expression when current scope exits.
+    Defer(CalcExpr),
+    EnumDefine(EnumDefine),
+    ObjectDefine(ObjectDefine),
 
     ModuleUse(UseStatement),
+    FileUse(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct VariableDefine {
     pub new: bool,
-    pub name: String,
+    pub name: Symbol,
+    // index path of assign target: `a["b"][2] = v;`, `a.b = v;`
+    pub index: Vec<CalcExpr>,
+    // `let x: string = v;`
+    pub annotation: Option<String>,
+    // `outer x = v;` or `global x = v;` assign variable defined outside of function.
+    pub scope: Option<AssignScope>,
+    pub expr: CalcExpr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssignScope {
+    // variable where current function is defined, a nested function captures it.
+    Outer,
+    // variable outside all functions.
+    Global,
+}
+
+// `let (a, b) = expr;` assign both items of a tuple.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TupleDefine {
+    pub new: bool,
+    pub names: (Symbol, Symbol),
     pub expr: CalcExpr,
 }
 
@@ -59,15 +416,15 @@ pub struct FunctionCall {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum FunctionName {
-    Single(String),
-    Namespace(Vec<String>),
+    Single(Symbol),
+    Namespace(Vec<Symbol>),
 }
 
 impl ToString for FunctionName {
     fn to_string(&self) -> String {
         match self {
             FunctionName::Single(s) => s.to_string(),
-            FunctionName::Namespace(n) => n.join("::"),
+            FunctionName::Namespace(n) => symbol::join(n, "::"),
         }
     }
 }
@@ -81,36 +438,174 @@ impl FunctionName {
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionDefine {
     pub name: Option<String>,
+    // `/// ...` comments before the define.
+    pub docs: Option<String>,
+    // `@memoize` lines before the define, nearest one is applied first.
+    pub decorators: Vec<FunctionName>,
     pub params: ParamsType,
-    pub inner: Vec<DioAstStatement>,
+    // type annotation of each `List` parameter: `fn add(a: number, b) -> number`.
+    pub param_types: Vec<Option<String>>,
+    pub return_type: Option<String>,
+    pub inner: Vec<Statement>,
+    // variables used in function but defined outside, filled by resolver.
+    pub captures: Vec<Symbol>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParamsType {
-    Variable(String),
-    List(Vec<String>),
+    // `fn f(a, @rest)`: fixed parameters, then a list of remaining arguments.
+    Variable(Vec<Symbol>, Symbol),
+    List(Vec<Symbol>),
+}
+
+impl ParamsType {
+    // parameter names in binding order, the variadic one is the last.
+    pub fn names(&self) -> Vec<Symbol> {
+        match self {
+            ParamsType::Variable(fixed, rest) => fixed.iter().chain([rest]).cloned().collect(),
+            ParamsType::List(list) => list.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConditionalStatement {
     pub condition: CalcExpr,
-    pub inner: Vec<DioAstStatement>,
-    pub otherwise: Option<Vec<DioAstStatement>>,
+    pub inner: Vec<Statement>,
+    pub otherwise: Option<Vec<Statement>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct LoopStatement {
     pub execute_type: LoopExecuteType,
-    pub inner: Vec<DioAstStatement>,
+    pub inner: Vec<Statement>,
 }
 
+// `use http::client(>=1.2, <2) as web;`
 #[derive(Debug, Clone, PartialEq)]
-pub struct UseStatement(pub Vec<String>);
+pub struct UseStatement {
+    pub path: Vec<Symbol>,
+    pub requirements: Vec<VersionRequirement>,
+    pub alias: Option<String>,
+}
+
+impl UseStatement {
+    // name used in script, the alias or the last segment of path.
+    pub fn name(&self) -> &str {
+        match &self.alias {
+            Some(alias) => alias,
+            None => self.path.last().map(|v| v.as_str()).unwrap_or_default(),
+        }
+    }
+}
+
+// semantic version, missing parts are zero: `1.2` => `1.2.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let parts = text
+            .split('.')
+            .map(|v| v.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        if parts.len() > 3 {
+            return None;
+        }
+        let part = |i: usize| parts.get(i).copied().unwrap_or(0);
+        Some(Version {
+            major: part(0),
+            minor: part(1),
+            patch: part(2),
+        })
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VersionOp {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    // `^1.2` or `1.2`: same major version and not older.
+    Compatible,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionRequirement {
+    pub op: VersionOp,
+    pub version: Version,
+}
+
+impl VersionRequirement {
+    pub fn matches(&self, version: &Version) -> bool {
+        let required = &self.version;
+        match self.op {
+            VersionOp::Exact => version == required,
+            VersionOp::Greater => version > required,
+            VersionOp::GreaterEq => version >= required,
+            VersionOp::Less => version < required,
+            VersionOp::LessEq => version <= required,
+            VersionOp::Compatible => version.major == required.major && version >= required,
+        }
+    }
+}
+
+impl std::fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self.op {
+            VersionOp::Exact => "=",
+            VersionOp::Greater => ">",
+            VersionOp::GreaterEq => ">=",
+            VersionOp::Less => "<",
+            VersionOp::LessEq => "<=",
+            VersionOp::Compatible => "^",
+        };
+        write!(f, "{op}{}", self.version)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectDefine {
+    pub name: String,
+    pub fields: Vec<VariableDefine>,
+    pub methods: Vec<FunctionDefine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDefine {
+    pub name: String,
+    pub variants: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertStatement {
+    pub expr: CalcExpr,
+    // source code of the asserted expression, use for failure message.
+    pub source: String,
+    pub message: Option<CalcExpr>,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LoopExecuteType {
     Conditional(CalcExpr),
-    Iter { iter: AstValue, var: String },
+    // `index` is bound by `for (i, item) in items`.
+    Iter {
+        iter: AstValue,
+        var: Symbol,
+        index: Option<Symbol>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -121,6 +616,12 @@ pub enum CalculateMark {
     Minus,
     Multiply,
     Divide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 
     Equal,
     NotEqual,
@@ -141,6 +642,12 @@ impl ToString for CalculateMark {
             CalculateMark::Minus => "-".to_string(),
             CalculateMark::Multiply => "*".to_string(),
             CalculateMark::Divide => "/".to_string(),
+            CalculateMark::Power => "**".to_string(),
+            CalculateMark::BitAnd => "&".to_string(),
+            CalculateMark::BitOr => "|".to_string(),
+            CalculateMark::BitXor => "^".to_string(),
+            CalculateMark::Shl => "<<".to_string(),
+            CalculateMark::Shr => ">>".to_string(),
 
             CalculateMark::Equal => "==".to_string(),
             CalculateMark::NotEqual => "!=".to_string(),
@@ -161,6 +668,12 @@ impl CalculateMark {
             "-" => Self::Minus,
             "*" => Self::Multiply,
             "/" => Self::Divide,
+            "**" => Self::Power,
+            "&" => Self::BitAnd,
+            "|" => Self::BitOr,
+            "^" => Self::BitXor,
+            "<<" => Self::Shl,
+            ">>" => Self::Shr,
 
             "==" => Self::Equal,
             "!=" => Self::NotEqual,
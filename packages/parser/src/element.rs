@@ -1,12 +1,19 @@
 use std::collections::HashMap;
 
-use crate::{parser::CalcExpr, types::AstValue};
+use crate::{ast::Span, parser::CalcExpr, types::AstValue};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct AstElement {
     pub name: String,
     pub attributes: HashMap<String, AstValue>,
+    // classes of `div.card.wide` shorthand, they are put before the value of `class` attribute
+    // when the element is rendered.
+    pub classes: Vec<String>,
+    // attribute keys defined more than once, the last value is kept in `attributes`.
+    pub duplicate_attributes: Vec<String>,
     pub content: Vec<AstElementContentType>,
+    // source range of the element, rendered elements keep it to report where they come from.
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -16,4 +23,8 @@ pub enum AstElementContentType {
     Condition(crate::ast::ConditionalStatement),
     Loop(crate::ast::LoopStatement),
     InlineExpr(CalcExpr),
+    // `// ...` line comment between items, kept for tooling and skipped by runtime.
+    Comment(String),
+    // `do { ... }` statements executed in a child scope, returned value is appended.
+    Block(Vec<crate::ast::Statement>),
 }
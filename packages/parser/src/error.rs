@@ -1,9 +1,271 @@
+use std::ops::Range;
+
 use nom::error::ErrorKind;
 
+use crate::ast::Span;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParseError {
-    #[error("[ParseFailed] parser match failed - {kind:?} : {text}")]
-    ParseFailure { kind: ErrorKind, text: String },
-    #[error("[ParseFailed] have unmatch content: `{content}`")]
-    UnMatchContent { content: String },
-}
\ No newline at end of file
+    #[error(
+        "[ParseFailed] parser match failed - {kind:?}{} : {text}",
+        within(context)
+    )]
+    ParseFailure {
+        kind: ErrorKind,
+        text: String,
+        // byte offset of `text` in source code, and its 1-based line and column.
+        offset: usize,
+        line: usize,
+        column: usize,
+        // `context` labels of the parsers which were running, outermost first.
+        context: Vec<String>,
+        // symbols and keywords which the parsers expected at `text`, e.g. `` `]` ``.
+        expected: Vec<String>,
+    },
+    #[error("[ParseFailed] have unmatch content: `{content}`{}", within(context))]
+    UnMatchContent {
+        // failed line, from the deepest position the statement parsers reached.
+        content: String,
+        offset: usize,
+        line: usize,
+        column: usize,
+        context: Vec<String>,
+        expected: Vec<String>,
+    },
+    #[error("[ParseFailed] `{keyword}` is a reserved keyword, can not be used as {usage} name")]
+    ReservedKeyword {
+        keyword: String,
+        usage: String,
+        // statement which uses the keyword.
+        position: Option<Position>,
+    },
+    #[error("[ParseFailed] parameter `{name}` is defined more than once")]
+    DuplicateParameter {
+        name: String,
+        // statement which defines the function.
+        position: Option<Position>,
+    },
+    #[error("[ParseFailed] variant `{name}` of enum `{enum_name}` is defined more than once")]
+    DuplicateVariant {
+        name: String,
+        enum_name: String,
+        // statement which defines the enum.
+        position: Option<Position>,
+    },
+    #[error("[ParseFailed] type `{name}` is neither a builtin type nor an object of the script")]
+    UnknownType {
+        name: String,
+        // statement which uses the type.
+        position: Option<Position>,
+    },
+    #[error("[ReadFailed] {0}")]
+    ReadFailure(#[from] std::io::Error),
+}
+
+// problems found while parsing which do not stop the script.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    #[error("[DuplicateAttribute] `{attribute}` is defined more than once in `{element}`, the last one is used")]
+    DuplicateAttribute {
+        element: String,
+        attribute: String,
+        // statement which contains the element.
+        span: Span,
+    },
+    #[error("[UseShadowed] `{name}` of `use {current};` shadows `use {previous};`, rename one of them with `as`")]
+    UseShadowed {
+        name: String,
+        previous: String,
+        current: String,
+        // the shadowing `use` statement.
+        span: Span,
+    },
+}
+
+impl ParseWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseWarning::DuplicateAttribute { span, .. }
+            | ParseWarning::UseShadowed { span, .. } => *span,
+        }
+    }
+}
+
+// source range of a problem found after parsing, e.g. a reserved keyword.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub span: Span,
+    // 1-based line and column (counted in chars) of span start.
+    pub line: usize,
+    pub column: usize,
+}
+
+// structured form of parse error, editors can mark the failed range with it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+    pub message: String,
+    // 1-based line and column (counted in chars) of failed position.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    // byte range of failed code in source. syntax errors end at the end of failed line,
+    // other errors cover the statement which contains them.
+    pub span: Option<Range<usize>>,
+    // symbols and keywords the parsers expected at the failed position, e.g.
+    // `` ["`,`", "`]`"] ``. names, numbers and strings are not listed.
+    pub expected: Vec<String>,
+    // labels of the parsers running at the failed position, outermost first, e.g.
+    // `["if statement", "expression"]`.
+    pub context: Vec<String>,
+}
+
+// former name of `Diagnostic`.
+#[deprecated(note = "renamed to `Diagnostic`")]
+pub type ErrorReport = Diagnostic;
+
+impl ParseError {
+    // stable code of the error kind, `ds explain <code>` describes it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::ParseFailure { .. } => "E0001",
+            ParseError::UnMatchContent { .. } => "E0002",
+            ParseError::ReservedKeyword { .. } => "E0003",
+            ParseError::DuplicateParameter { .. } => "E0004",
+            ParseError::ReadFailure(_) => "E0005",
+            ParseError::UnknownType { .. } => "E0006",
+            ParseError::DuplicateVariant { .. } => "E0007",
+        }
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ParseError::ParseFailure { offset, .. } | ParseError::UnMatchContent { offset, .. } => {
+                Some(*offset)
+            }
+            ParseError::ReservedKeyword { position, .. }
+            | ParseError::DuplicateParameter { position, .. }
+            | ParseError::DuplicateVariant { position, .. }
+            | ParseError::UnknownType { position, .. } => position.map(|v| v.span.start),
+            ParseError::ReadFailure(_) => None,
+        }
+    }
+
+    // checker errors are marked with the innermost statement which contains them. its span
+    // is not resolved yet, it counts the input left after the statement, see `locate`.
+    pub(crate) fn at(mut self, span: Span) -> Self {
+        if let ParseError::ReservedKeyword { position, .. }
+        | ParseError::DuplicateParameter { position, .. }
+        | ParseError::DuplicateVariant { position, .. }
+        | ParseError::UnknownType { position, .. } = &mut self
+        {
+            position.get_or_insert(Position {
+                span,
+                line: 0,
+                column: 0,
+            });
+        }
+        self
+    }
+
+    // resolve position marked by `at`. unresolved spans count from the end of `source`, which
+    // starts at byte `base` and at 1-based `start` line and column.
+    pub(crate) fn locate(mut self, source: &str, base: usize, start: (usize, usize)) -> Self {
+        if let ParseError::ReservedKeyword { position, .. }
+        | ParseError::DuplicateParameter { position, .. }
+        | ParseError::DuplicateVariant { position, .. }
+        | ParseError::UnknownType { position, .. } = &mut self
+        {
+            if let Some(position) = position {
+                let from = source.len().saturating_sub(position.span.start);
+                let to = source.len().saturating_sub(position.span.end);
+                let (line, column) = advance_position(start, source.get(..from).unwrap_or(""));
+                *position = Position {
+                    span: Span {
+                        start: base + from,
+                        end: base + to,
+                    },
+                    line,
+                    column,
+                };
+            }
+        }
+        self
+    }
+
+    #[deprecated(note = "use `diagnostic`, errors keep their position")]
+    pub fn report(&self, _source: &str) -> Diagnostic {
+        self.diagnostic()
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let (position, span) = match self {
+            ParseError::ParseFailure {
+                text,
+                offset,
+                line,
+                column,
+                ..
+            } => (Some((*line, *column)), Some(line_span(*offset, text))),
+            ParseError::UnMatchContent {
+                content,
+                offset,
+                line,
+                column,
+                ..
+            } => (Some((*line, *column)), Some(line_span(*offset, content))),
+            ParseError::ReservedKeyword { position, .. }
+            | ParseError::DuplicateParameter { position, .. }
+            | ParseError::DuplicateVariant { position, .. }
+            | ParseError::UnknownType { position, .. } => (
+                position.map(|v| (v.line, v.column)),
+                position.map(|v| v.span.start..v.span.end),
+            ),
+            ParseError::ReadFailure(_) => (None, None),
+        };
+        let (expected, context) = match self {
+            ParseError::ParseFailure {
+                expected, context, ..
+            }
+            | ParseError::UnMatchContent {
+                expected, context, ..
+            } => (expected.clone(), context.clone()),
+            ParseError::ReservedKeyword { .. }
+            | ParseError::DuplicateParameter { .. }
+            | ParseError::DuplicateVariant { .. } => (vec!["identifier".to_string()], vec![]),
+            ParseError::UnknownType { .. } => (vec!["type".to_string()], vec![]),
+            ParseError::ReadFailure(_) => (vec![], vec![]),
+        };
+        Diagnostic {
+            message: self.to_string(),
+            line: position.map(|v| v.0),
+            column: position.map(|v| v.1),
+            span,
+            expected,
+            context,
+        }
+    }
+}
+
+// ` (parsing variable > list)` for labels `["variable", "list"]`.
+fn within(context: &[String]) -> String {
+    if context.is_empty() {
+        return String::new();
+    }
+    format!(" (parsing {})", context.join(" > "))
+}
+
+// failed code from `offset` to the end of its line.
+fn line_span(offset: usize, text: &str) -> Range<usize> {
+    offset..offset + text.find('\n').unwrap_or(text.len())
+}
+
+// 1-based line and column (counted in chars) at the end of `text`, which starts at `start`.
+pub(crate) fn advance_position(start: (usize, usize), text: &str) -> (usize, usize) {
+    match text.rfind('\n') {
+        Some(index) => (
+            start.0 + text.matches('\n').count(),
+            text[index + 1..].chars().count() + 1,
+        ),
+        None => (start.0, start.1 + text.chars().count()),
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        symbol_path, CallSite, ConditionalStatement, DioAstStatement, FunctionCall, FunctionDefine,
+        FunctionName, LoopExecuteType, LoopStatement, Span, Statement, Symbols,
+    },
+    element::{AstElement, AstElementContentType},
+    error::{ParseError, ParseWarning},
+    parser::{CalcExpr, LinkExprPart},
+    symbol::{self, Symbol},
+    types::AstValue,
+};
+
+// words used by the grammar, they can not be used as names.
+pub const RESERVED_KEYWORDS: &[&str] = &[
+    "assert", "else", "enum", "false", "fn", "for", "if", "in", "let", "object", "return", "true",
+    "use", "while",
+];
+
+// words which are keywords only where the grammar expects them, so scripts written before they
+// were added keep working: `let outer = 1;`, `return is;`. a statement starting with one of them
+// followed by what the keyword takes is parsed as the keyword: `break x;`, `defer f();`,
+// `outer x = 1;`, and `typeof` before an operand is the operator, so a function named `typeof`
+// can not be called as `typeof(x)`.
+pub const CONTEXTUAL_KEYWORDS: &[&str] =
+    &["break", "defer", "do", "global", "is", "outer", "typeof"];
+
+// types of values which annotations may name, besides objects defined in the script. `any`
+// matches every value.
+pub const TYPE_NAMES: &[&str] = &[
+    "any", "boolean", "dict", "element", "enum", "function", "list", "none", "number", "object",
+    "string", "tuple",
+];
+
+pub fn is_reserved(name: &str) -> bool {
+    RESERVED_KEYWORDS.contains(&name)
+}
+
+// reject reserved keywords which are used as variable, function, parameter or type names,
+// and parameters or enum variants which are defined more than once.
+pub(crate) fn check_statements(stats: &[Statement]) -> Result<(), ParseError> {
+    Checker::default().statements(stats)
+}
+
+pub(crate) fn check_function(func: &FunctionDefine) -> Result<(), ParseError> {
+    Checker::default().function(func)
+}
+
+pub(crate) fn check_expr(expr: &CalcExpr) -> Result<(), ParseError> {
+    Checker::default().expr(expr)
+}
+
+pub(crate) fn check_element(element: &AstElement) -> Result<(), ParseError> {
+    Checker::default().element(element)
+}
+
+// names of objects defined anywhere in the script, annotations may use them as types.
+pub(crate) fn object_names(stats: &[Statement]) -> HashSet<String> {
+    let mut checker = Checker::default();
+    let _ = checker.statements(stats);
+    checker.objects
+}
+
+// reject annotations of `stats` which name neither a builtin type nor one of `objects`.
+pub(crate) fn check_types(
+    stats: &[Statement],
+    objects: &HashSet<String>,
+) -> Result<(), ParseError> {
+    let mut checker = Checker::default();
+    let _ = checker.statements(stats);
+    let unknown = checker
+        .types
+        .into_iter()
+        .find(|(name, _)| !TYPE_NAMES.contains(&name.as_str()) && !objects.contains(name));
+    match unknown {
+        Some((name, span)) => Err(ParseError::UnknownType {
+            name,
+            position: None,
+        }
+        .at(span)),
+        None => Ok(()),
+    }
+}
+
+// problems which do not stop the script, e.g. duplicate element attributes.
+pub(crate) fn collect_warnings(stats: &[Statement]) -> Vec<ParseWarning> {
+    let mut checker = Checker::default();
+    let _ = checker.statements(stats);
+    checker.warnings
+}
+
+// calls and definitions of the whole script, used for analysis before execution.
+pub(crate) fn collect_symbols(stats: &[Statement]) -> Symbols {
+    let mut checker = Checker::default();
+    let _ = checker.statements(stats);
+    checker.symbols
+}
+
+#[derive(Default)]
+struct Checker {
+    warnings: Vec<ParseWarning>,
+    symbols: Symbols,
+    // name => path of `use` statements, used to find shadowed names.
+    uses: HashMap<String, Vec<Symbol>>,
+    // functions and blocks containing current statement, see `Symbols`.
+    scope: Vec<String>,
+    // number of blocks entered, it names the next block.
+    blocks: usize,
+    // innermost statement being checked, warnings and calls are located by it.
+    span: Span,
+    // type annotations with the statement which contains them, see `check_types`.
+    types: Vec<(String, Span)>,
+    // names of defined objects.
+    objects: HashSet<String>,
+}
+
+impl Checker {
+    fn name(&mut self, name: &str, usage: &str) -> Result<(), ParseError> {
+        if is_reserved(name) {
+            return Err(ParseError::ReservedKeyword {
+                keyword: name.to_string(),
+                usage: usage.to_string(),
+                position: None,
+            });
+        }
+        if !matches!(
+            usage,
+            "function" | "field" | "enum variant" | "type" | "module"
+        ) {
+            self.symbols.bindings.insert(self.path(name));
+        }
+        match usage {
+            "type" => self.types.push((name.to_string(), self.span)),
+            "object" => {
+                self.objects.insert(name.to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn path(&self, name: &str) -> String {
+        symbol_path(&self.scope, name)
+    }
+
+    // run `f` in scope of named function, or of a new block for `None`.
+    fn scoped(
+        &mut self,
+        name: Option<&str>,
+        f: impl FnOnce(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        let segment = match name {
+            Some(name) => name.to_string(),
+            None => {
+                self.blocks += 1;
+                format!("#{}", self.blocks)
+            }
+        };
+        self.scope.push(segment);
+        let result = f(self);
+        self.scope.pop();
+        result
+    }
+
+    fn call_site(&mut self, call: &FunctionCall, arguments: usize) {
+        if let FunctionName::Namespace(path) = &call.name {
+            let register = path.ends_with(&[Symbol::new("module"), Symbol::new("register")]);
+            if let (true, Some(AstValue::String(name))) = (register, call.arguments.first()) {
+                self.symbols.registered.push(name.to_string());
+            }
+        }
+        self.symbols.calls.push(CallSite {
+            name: call.name.clone(),
+            arguments,
+            scope: self.scope.clone(),
+            span: self.span,
+        });
+    }
+
+    fn statements(&mut self, stats: &[Statement]) -> Result<(), ParseError> {
+        for stat in stats {
+            let outer = std::mem::replace(&mut self.span, stat.span);
+            self.statement(&stat.kind).map_err(|e| e.at(stat.span))?;
+            self.span = outer;
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, func: &FunctionDefine) -> Result<(), ParseError> {
+        if let Some(name) = &func.name {
+            self.name(name, "function")?;
+        }
+        // decorator is called with the defined function.
+        for decorator in func.decorators.iter() {
+            self.symbols.calls.push(CallSite {
+                name: decorator.clone(),
+                arguments: 1,
+                scope: self.scope.clone(),
+                span: self.span,
+            });
+        }
+        self.scoped(func.name.as_deref(), |this| {
+            let params = func.params.names();
+            for (i, v) in params.iter().enumerate() {
+                this.name(v, "parameter")?;
+                if params[..i].contains(v) {
+                    return Err(ParseError::DuplicateParameter {
+                        name: v.to_string(),
+                        position: None,
+                    });
+                }
+            }
+            let annotations = func.param_types.iter().chain([&func.return_type]);
+            for annotation in annotations.flatten() {
+                this.name(annotation, "type")?;
+            }
+            this.statements(&func.inner)
+        })
+    }
+
+    fn statement(&mut self, stat: &DioAstStatement) -> Result<(), ParseError> {
+        match stat {
+            DioAstStatement::VariableAss(var) => {
+                self.name(&var.name, "variable")?;
+                if !var.new || var.scope.is_some() {
+                    self.symbols.bindings.insert(var.name.to_string());
+                }
+                if let Some(annotation) = &var.annotation {
+                    self.name(annotation, "type")?;
+                }
+                for index in var.index.iter() {
+                    self.expr(index)?;
+                }
+                self.expr(&var.expr)
+            }
+            DioAstStatement::TupleAss(var) => {
+                self.name(&var.names.0, "variable")?;
+                self.name(&var.names.1, "variable")?;
+                if !var.new {
+                    self.symbols.bindings.insert(var.names.0.to_string());
+                    self.symbols.bindings.insert(var.names.1.to_string());
+                }
+                self.expr(&var.expr)
+            }
+            DioAstStatement::ReturnValue(expr)
+            | DioAstStatement::Defer(expr)
+            | DioAstStatement::MethodCall(expr) => self.expr(expr),
+            DioAstStatement::Break(value) => value.iter().try_for_each(|v| self.expr(v)),
+            DioAstStatement::IfStatement(cond) => self.condition(cond),
+            DioAstStatement::LoopStatement(data) => self.loop_statement(data),
+            DioAstStatement::FunctionCall(call) => {
+                self.call_site(call, call.arguments.len());
+                self.call(call)
+            }
+            DioAstStatement::FunctionDefine(define) => {
+                if let Some(name) = &define.name {
+                    let path = self.path(name);
+                    if define.decorators.is_empty() {
+                        let params = Some(define.params.clone());
+                        let known = self.symbols.functions.entry(path).or_insert(params.clone());
+                        if *known != params {
+                            *known = None;
+                        }
+                    } else {
+                        // decorated name holds the decorator result, which may be any function.
+                        self.symbols.bindings.insert(path);
+                    }
+                }
+                self.function(define)
+            }
+            DioAstStatement::Assert(assert) => {
+                self.expr(&assert.expr)?;
+                if let Some(message) = &assert.message {
+                    self.expr(message)?;
+                }
+                Ok(())
+            }
+            DioAstStatement::EnumDefine(define) => {
+                self.name(&define.name, "enum")?;
+                for (i, variant) in define.variants.iter().enumerate() {
+                    self.name(variant, "enum variant")?;
+                    if define.variants[..i].contains(variant) {
+                        return Err(ParseError::DuplicateVariant {
+                            name: variant.to_string(),
+                            enum_name: define.name.to_string(),
+                            position: None,
+                        });
+                    }
+                }
+                Ok(())
+            }
+            DioAstStatement::ObjectDefine(define) => {
+                self.name(&define.name, "object")?;
+                for field in define.fields.iter() {
+                    self.name(&field.name, "field")?;
+                    self.expr(&field.expr)?;
+                }
+                for method in define.methods.iter() {
+                    self.function(method)?;
+                }
+                Ok(())
+            }
+            DioAstStatement::ModuleUse(u) => {
+                if let Some(alias) = &u.alias {
+                    self.name(alias, "module")?;
+                    let path = u.path.iter().map(|v| v.to_string()).collect();
+                    self.symbols.aliases.insert(alias.clone(), path);
+                }
+                let name = u.name().to_string();
+                if let Some(previous) = self.uses.insert(name.clone(), u.path.clone()) {
+                    if previous != u.path {
+                        self.warnings.push(ParseWarning::UseShadowed {
+                            name,
+                            previous: symbol::join(&previous, "::"),
+                            current: symbol::join(&u.path, "::"),
+                            span: self.span,
+                        });
+                    }
+                }
+                let path = u.path.iter().map(|v| v.to_string()).collect();
+                self.symbols.modules.push(path);
+                Ok(())
+            }
+            DioAstStatement::FileUse(path) => {
+                self.symbols.files.push(path.clone());
+                Ok(())
+            }
+            DioAstStatement::LineComment(_) => Ok(()),
+        }
+    }
+
+    fn condition(&mut self, cond: &ConditionalStatement) -> Result<(), ParseError> {
+        self.expr(&cond.condition)?;
+        self.scoped(None, |this| this.statements(&cond.inner))?;
+        if let Some(otherwise) = &cond.otherwise {
+            self.scoped(None, |this| this.statements(otherwise))?;
+        }
+        Ok(())
+    }
+
+    fn loop_statement(&mut self, data: &LoopStatement) -> Result<(), ParseError> {
+        match &data.execute_type {
+            LoopExecuteType::Conditional(cond) => {
+                self.expr(cond)?;
+                self.scoped(None, |this| this.statements(&data.inner))
+            }
+            LoopExecuteType::Iter { iter, var, index } => {
+                self.value(iter)?;
+                self.scoped(None, |this| {
+                    this.name(var, "variable")?;
+                    if let Some(index) = index {
+                        this.name(index, "variable")?;
+                    }
+                    this.statements(&data.inner)
+                })
+            }
+        }
+    }
+
+    fn call(&mut self, call: &FunctionCall) -> Result<(), ParseError> {
+        for arg in call.arguments.iter() {
+            self.value(arg)?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &CalcExpr) -> Result<(), ParseError> {
+        match expr {
+            CalcExpr::Value(v) => self.value(v),
+            CalcExpr::LinkExpr(link) => {
+                self.expr(&link.this)?;
+                for part in link.list.iter() {
+                    if let LinkExprPart::FunctionCall(call) = part {
+                        self.call(call)?;
+                    }
+                }
+                Ok(())
+            }
+            CalcExpr::Pipe(l, call) => {
+                self.expr(l)?;
+                // piped value is the first argument.
+                self.call_site(call, call.arguments.len() + 1);
+                self.call(call)
+            }
+            CalcExpr::TypeOf(v) | CalcExpr::Is(v, _) => self.expr(v),
+            CalcExpr::Call(f, arguments) => {
+                self.expr(f)?;
+                arguments.iter().try_for_each(|v| self.value(v))
+            }
+            CalcExpr::Add(l, r)
+            | CalcExpr::Sub(l, r)
+            | CalcExpr::Mul(l, r)
+            | CalcExpr::Div(l, r)
+            | CalcExpr::Mod(l, r)
+            | CalcExpr::Pow(l, r)
+            | CalcExpr::BitAnd(l, r)
+            | CalcExpr::BitOr(l, r)
+            | CalcExpr::BitXor(l, r)
+            | CalcExpr::Shl(l, r)
+            | CalcExpr::Shr(l, r)
+            | CalcExpr::Eq(l, r)
+            | CalcExpr::Ne(l, r)
+            | CalcExpr::Gt(l, r)
+            | CalcExpr::Lt(l, r)
+            | CalcExpr::Ge(l, r)
+            | CalcExpr::Le(l, r)
+            | CalcExpr::And(l, r)
+            | CalcExpr::Or(l, r)
+            | CalcExpr::Coalesce(l, r)
+            | CalcExpr::Tuple(l, r) => {
+                self.expr(l)?;
+                self.expr(r)
+            }
+        }
+    }
+
+    fn value(&mut self, value: &AstValue) -> Result<(), ParseError> {
+        match value {
+            AstValue::VariableIndex((_, index)) => self.expr(index),
+            AstValue::VariableSlice((_, slice)) => {
+                let parts = [&slice.start, &slice.end, &slice.step];
+                for v in parts.into_iter().flatten() {
+                    self.expr(v)?;
+                }
+                Ok(())
+            }
+            AstValue::List(list) => list.iter().try_for_each(|v| self.value(v)),
+            AstValue::Dict(dict) => dict.values().try_for_each(|v| self.value(v)),
+            AstValue::Tuple((a, b)) => {
+                self.value(a)?;
+                self.value(b)
+            }
+            AstValue::Element(element) => self.element(element),
+            AstValue::FunctionCaller(call) => {
+                self.call_site(call, call.arguments.len());
+                self.call(call)
+            }
+            AstValue::FunctionDefine(define) => self.function(define),
+            AstValue::Loop(data) => self.loop_statement(data),
+            AstValue::None
+            | AstValue::String(_)
+            | AstValue::Number(_)
+            | AstValue::Boolean(_)
+            | AstValue::Variable(_)
+            | AstValue::Local(_) => Ok(()),
+        }
+    }
+
+    fn element(&mut self, element: &AstElement) -> Result<(), ParseError> {
+        for attribute in element.duplicate_attributes.iter() {
+            self.warnings.push(ParseWarning::DuplicateAttribute {
+                element: element.name.clone(),
+                attribute: attribute.clone(),
+                span: self.span,
+            });
+        }
+        for v in element.attributes.values() {
+            self.value(v)?;
+        }
+        for content in element.content.iter() {
+            match content {
+                AstElementContentType::Children(e) => self.element(e)?,
+                AstElementContentType::Condition(cond) => self.condition(cond)?,
+                AstElementContentType::Loop(data) => self.loop_statement(data)?,
+                AstElementContentType::InlineExpr(expr) => self.expr(expr)?,
+                AstElementContentType::Block(stats) => {
+                    self.scoped(None, |this| this.statements(stats))?
+                }
+                AstElementContentType::Content(_) | AstElementContentType::Comment(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
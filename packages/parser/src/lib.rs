@@ -5,5 +5,12 @@ pub mod parser;
 pub mod types;
 
 pub mod error;
+pub mod keyword;
+pub mod session;
+pub mod stream;
+pub mod symbol;
+
+mod optimizer;
+mod resolver;
 
 pub mod ast;
@@ -0,0 +1,228 @@
+use crate::{
+    ast::{ConditionalStatement, DioAstStatement, FunctionCall, LoopExecuteType, Statement},
+    element::{AstElement, AstElementContentType},
+    parser::{CalcExpr, LinkExprPart},
+    types::AstValue,
+};
+
+// pre-evaluate operators whose operands are literals, e.g. `2 * 60 * 60` or `"a" + "b"`.
+// only operators with the same result as runtime are folded: mismatched types, `%` and bit
+// operators are left for runtime, so errors are still reported when the code runs.
+pub(crate) fn optimize_statements(stats: &mut [Statement]) {
+    for stat in stats {
+        statement(&mut stat.kind);
+    }
+}
+
+fn statement(stat: &mut DioAstStatement) {
+    match stat {
+        DioAstStatement::VariableAss(var) => {
+            for index in var.index.iter_mut() {
+                expr(index);
+            }
+            expr(&mut var.expr);
+        }
+        DioAstStatement::TupleAss(var) => expr(&mut var.expr),
+        DioAstStatement::ReturnValue(v)
+        | DioAstStatement::Defer(v)
+        | DioAstStatement::MethodCall(v) => expr(v),
+        DioAstStatement::Break(v) => {
+            if let Some(v) = v {
+                expr(v);
+            }
+        }
+        DioAstStatement::IfStatement(cond) => condition(cond),
+        DioAstStatement::LoopStatement(data) => loop_type(&mut data.execute_type, &mut data.inner),
+        DioAstStatement::FunctionCall(v) => call(v),
+        DioAstStatement::FunctionDefine(define) => optimize_statements(&mut define.inner),
+        DioAstStatement::Assert(assert) => {
+            expr(&mut assert.expr);
+            if let Some(message) = &mut assert.message {
+                expr(message);
+            }
+        }
+        DioAstStatement::ObjectDefine(define) => {
+            for field in define.fields.iter_mut() {
+                expr(&mut field.expr);
+            }
+            for method in define.methods.iter_mut() {
+                optimize_statements(&mut method.inner);
+            }
+        }
+        DioAstStatement::LineComment(_)
+        | DioAstStatement::EnumDefine(_)
+        | DioAstStatement::ModuleUse(_)
+        | DioAstStatement::FileUse(_) => {}
+    }
+}
+
+fn condition(cond: &mut ConditionalStatement) {
+    expr(&mut cond.condition);
+    optimize_statements(&mut cond.inner);
+    if let Some(otherwise) = &mut cond.otherwise {
+        optimize_statements(otherwise);
+    }
+}
+
+fn loop_type(execute_type: &mut LoopExecuteType, inner: &mut [Statement]) {
+    match execute_type {
+        LoopExecuteType::Conditional(cond) => expr(cond),
+        LoopExecuteType::Iter { iter, .. } => value(iter),
+    }
+    optimize_statements(inner);
+}
+
+fn call(call: &mut FunctionCall) {
+    for arg in call.arguments.iter_mut() {
+        value(arg);
+    }
+}
+
+fn expr(e: &mut CalcExpr) {
+    match e {
+        CalcExpr::Value(v) => value(v),
+        CalcExpr::LinkExpr(link) => {
+            expr(&mut link.this);
+            for part in link.list.iter_mut() {
+                if let LinkExprPart::FunctionCall(v) = part {
+                    call(v);
+                }
+            }
+        }
+        CalcExpr::Pipe(l, v) => {
+            expr(l);
+            call(v);
+        }
+        CalcExpr::TypeOf(v) | CalcExpr::Is(v, _) => expr(v),
+        CalcExpr::Call(f, arguments) => {
+            expr(f);
+            for arg in arguments.iter_mut() {
+                value(arg);
+            }
+        }
+        CalcExpr::Add(l, r)
+        | CalcExpr::Sub(l, r)
+        | CalcExpr::Mul(l, r)
+        | CalcExpr::Div(l, r)
+        | CalcExpr::Mod(l, r)
+        | CalcExpr::Pow(l, r)
+        | CalcExpr::BitAnd(l, r)
+        | CalcExpr::BitOr(l, r)
+        | CalcExpr::BitXor(l, r)
+        | CalcExpr::Shl(l, r)
+        | CalcExpr::Shr(l, r)
+        | CalcExpr::Eq(l, r)
+        | CalcExpr::Ne(l, r)
+        | CalcExpr::Gt(l, r)
+        | CalcExpr::Lt(l, r)
+        | CalcExpr::Ge(l, r)
+        | CalcExpr::Le(l, r)
+        | CalcExpr::And(l, r)
+        | CalcExpr::Or(l, r)
+        | CalcExpr::Coalesce(l, r)
+        | CalcExpr::Tuple(l, r) => {
+            expr(l);
+            expr(r);
+        }
+    }
+    if let Some(v) = fold(e) {
+        *e = CalcExpr::Value(v);
+    }
+}
+
+fn fold(e: &CalcExpr) -> Option<AstValue> {
+    use AstValue::{Boolean, Number, String};
+    let (l, r) = match e {
+        CalcExpr::Add(l, r)
+        | CalcExpr::Sub(l, r)
+        | CalcExpr::Mul(l, r)
+        | CalcExpr::Div(l, r)
+        | CalcExpr::Pow(l, r)
+        | CalcExpr::Eq(l, r)
+        | CalcExpr::Ne(l, r)
+        | CalcExpr::Gt(l, r)
+        | CalcExpr::Lt(l, r)
+        | CalcExpr::Ge(l, r)
+        | CalcExpr::Le(l, r)
+        | CalcExpr::And(l, r)
+        | CalcExpr::Or(l, r) => match (l.as_ref(), r.as_ref()) {
+            (CalcExpr::Value(l), CalcExpr::Value(r)) => (l, r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let value = match (e, l, r) {
+        (CalcExpr::Add(..), Number(l), Number(r)) => Number(l + r),
+        (CalcExpr::Sub(..), Number(l), Number(r)) => Number(l - r),
+        (CalcExpr::Mul(..), Number(l), Number(r)) => Number(l * r),
+        (CalcExpr::Div(..), Number(l), Number(r)) => Number(l / r),
+        (CalcExpr::Pow(..), Number(l), Number(r)) => Number(l.powf(*r)),
+        (CalcExpr::Gt(..), Number(l), Number(r)) => Boolean(l > r),
+        (CalcExpr::Lt(..), Number(l), Number(r)) => Boolean(l < r),
+        (CalcExpr::Ge(..), Number(l), Number(r)) => Boolean(l >= r),
+        (CalcExpr::Le(..), Number(l), Number(r)) => Boolean(l <= r),
+        (CalcExpr::Eq(..), Number(l), Number(r)) => Boolean(l == r),
+        (CalcExpr::Ne(..), Number(l), Number(r)) => Boolean(l != r),
+        (CalcExpr::Add(..), String(l), String(r)) => String(format!("{l}{r}")),
+        (CalcExpr::Eq(..), String(l), String(r)) => Boolean(l == r),
+        (CalcExpr::Ne(..), String(l), String(r)) => Boolean(l != r),
+        (CalcExpr::Eq(..), Boolean(l), Boolean(r)) => Boolean(l == r),
+        (CalcExpr::Ne(..), Boolean(l), Boolean(r)) => Boolean(l != r),
+        (CalcExpr::And(..), Boolean(l), Boolean(r)) => Boolean(*l && *r),
+        (CalcExpr::Or(..), Boolean(l), Boolean(r)) => Boolean(*l || *r),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn value(v: &mut AstValue) {
+    match v {
+        AstValue::VariableIndex((_, index)) => expr(index),
+        AstValue::VariableSlice((_, slice)) => {
+            let parts = [&mut slice.start, &mut slice.end, &mut slice.step];
+            for v in parts.into_iter().flatten() {
+                expr(v);
+            }
+        }
+        AstValue::List(list) => {
+            for v in list.iter_mut() {
+                value(v);
+            }
+        }
+        AstValue::Dict(dict) => {
+            for v in dict.values_mut() {
+                value(v);
+            }
+        }
+        AstValue::Tuple((a, b)) => {
+            value(a);
+            value(b);
+        }
+        AstValue::Element(e) => element(e),
+        AstValue::FunctionCaller(v) => call(v),
+        AstValue::FunctionDefine(define) => optimize_statements(&mut define.inner),
+        AstValue::Loop(data) => loop_type(&mut data.execute_type, &mut data.inner),
+        AstValue::None
+        | AstValue::String(_)
+        | AstValue::Number(_)
+        | AstValue::Boolean(_)
+        | AstValue::Variable(_)
+        | AstValue::Local(_) => {}
+    }
+}
+
+fn element(e: &mut AstElement) {
+    for v in e.attributes.values_mut() {
+        value(v);
+    }
+    for content in e.content.iter_mut() {
+        match content {
+            AstElementContentType::Children(e) => element(e),
+            AstElementContentType::Condition(cond) => condition(cond),
+            AstElementContentType::Loop(data) => loop_type(&mut data.execute_type, &mut data.inner),
+            AstElementContentType::InlineExpr(v) => expr(v),
+            AstElementContentType::Block(stats) => optimize_statements(stats),
+            AstElementContentType::Content(_) | AstElementContentType::Comment(_) => {}
+        }
+    }
+}
@@ -1,28 +1,175 @@
-use std::collections::HashMap;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use nom::{
     branch::alt,
-    bytes::complete::{
-        escaped, tag, tag_no_case, take_till1, take_until, take_while, take_while1, take_while_m_n,
+    bytes::complete::{escaped, take_till1, take_until, take_while, take_while1, take_while_m_n},
+    character::complete::{
+        alpha1, alphanumeric1, digit1, multispace0, multispace1, one_of, satisfy, space0, space1,
     },
-    character::complete::{alpha1, alphanumeric1, char, digit1, multispace0, space0, space1},
-    combinator::{map, opt, peek, value},
-    error::context,
-    multi::{fold_many0, many0, separated_list0, separated_list1},
+    combinator::{consumed, map, map_opt, not, opt, peek, recognize, value, verify},
+    error::{context, ContextError, ErrorKind},
+    multi::{fold_many0, many0, many0_count, many1, separated_list0, separated_list1},
     number::complete::double,
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
-    IResult,
+    Finish, InputLength,
 };
 
 use crate::{
     ast::{
-        ConditionalStatement, DioAstStatement, FunctionCall, FunctionDefine, LoopStatement,
-        ParamsType, UseStatement, FunctionName, VariableDefine,
+        parse_failure, AssertStatement, AssignScope, Comment, ConditionalStatement,
+        DioAstStatement, EnumDefine, FunctionCall, FunctionDefine, FunctionName, LoopStatement,
+        ObjectDefine, ParamsType, Span, Statement, TupleDefine, UseStatement, VariableDefine,
+        Version, VersionOp, VersionRequirement,
     },
     element::{AstElement, AstElementContentType},
-    types::AstValue,
+    error::ParseError,
+    symbol::Symbol,
+    types::{AstValue, SliceIndex},
 };
 
+pub(crate) type IResult<I, O> = nom::IResult<I, O, SyntaxError<I>>;
+
+// nom error of the parsers. nom drops errors of failed alternatives and of the item which
+// ends a repetition, so while `deepest_failure` runs a parser, the failure deepest in the
+// input is recorded besides, with the `context` labels around it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SyntaxError<I> {
+    pub input: I,
+    pub kind: ErrorKind,
+}
+
+impl<I: InputLength> nom::error::ParseError<I> for SyntaxError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        if TRACKING.load(Ordering::Relaxed) > 0 {
+            record_failure(input.input_len(), kind);
+        }
+        SyntaxError { input, kind }
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I: InputLength> ContextError<I> for SyntaxError<I> {
+    fn add_context(_: I, label: &'static str, other: Self) -> Self {
+        if TRACKING.load(Ordering::Relaxed) > 0 {
+            record_context(other.input.input_len(), label);
+        }
+        other
+    }
+}
+
+// failure of `deepest_failure`, `position` is the input left there.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Failure<'a> {
+    pub position: &'a str,
+    pub kind: ErrorKind,
+    // labels of the parsers around it, outermost first.
+    pub context: Vec<String>,
+    // symbols which the tried alternatives expected there, e.g. `]` after a list item.
+    pub expected: Vec<String>,
+}
+
+// recorded failure: length of the input left there, its kind, labels (innermost first) and
+// expected symbols.
+type Deepest = (usize, ErrorKind, Vec<&'static str>, Vec<String>);
+
+// threads running `deepest_failure`. checking a thread local on every failure slows parsing
+// down, other threads only record into their own `DEEPEST` meanwhile.
+static TRACKING: AtomicUsize = AtomicUsize::new(0);
+
+#[cold]
+fn record_failure(left: usize, kind: ErrorKind) {
+    DEEPEST.with(|v| {
+        let mut deepest = v.borrow_mut();
+        if left < deepest.0 {
+            deepest.3.clear();
+        }
+        // the last failure there is the one which propagates, symbols expected by the others
+        // are kept.
+        if left <= deepest.0 {
+            deepest.0 = left;
+            deepest.1 = kind;
+            deepest.2.clear();
+        }
+    });
+}
+
+#[cold]
+fn record_expected(left: usize, symbol: String) {
+    DEEPEST.with(|v| {
+        let mut deepest = v.borrow_mut();
+        if left == deepest.0 && !deepest.3.contains(&symbol) {
+            deepest.3.push(symbol);
+        }
+    });
+}
+
+#[cold]
+fn record_context(left: usize, label: &'static str) {
+    DEEPEST.with(|v| {
+        let mut deepest = v.borrow_mut();
+        if left == deepest.0 {
+            deepest.2.push(label);
+        }
+    });
+}
+
+// where `parser` fails deepest in `message`, none if it does not fail.
+pub(crate) fn deepest_failure<'a, T>(
+    message: &'a str,
+    parser: impl FnOnce(&'a str) -> IResult<&'a str, T>,
+) -> Option<Failure<'a>> {
+    DEEPEST.with(|v| *v.borrow_mut() = (usize::MAX, ErrorKind::Fail, vec![], vec![]));
+    TRACKING.fetch_add(1, Ordering::Relaxed);
+    let failed = parser(message).is_err();
+    TRACKING.fetch_sub(1, Ordering::Relaxed);
+    let (left, kind, labels, expected) =
+        DEEPEST.with(|v| v.replace((0, ErrorKind::Fail, vec![], vec![])));
+    if !failed || left > message.len() {
+        return None;
+    }
+    Some(Failure {
+        position: &message[message.len() - left..],
+        kind,
+        context: labels.iter().rev().map(|v| v.to_string()).collect(),
+        expected: expected.iter().map(|v| format!("`{v}`")).collect(),
+    })
+}
+
+// `tag` and `char` of nom, which record the expected symbol when they fail. failures inside
+// other nom parsers, e.g. the exponent of a number, are not what grammar expects.
+fn tag<'a>(symbol: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |input| {
+        let result = nom::bytes::complete::tag(symbol)(input);
+        if result.is_err() && TRACKING.load(Ordering::Relaxed) > 0 {
+            record_expected(input.len(), symbol.to_string());
+        }
+        result
+    }
+}
+
+fn char<'a>(symbol: char) -> impl Fn(&'a str) -> IResult<&'a str, char> {
+    move |input| {
+        let result = nom::character::complete::char(symbol)(input);
+        if result.is_err() && TRACKING.load(Ordering::Relaxed) > 0 {
+            record_expected(input.len(), symbol.to_string());
+        }
+        result
+    }
+}
+
+enum ObjectItem {
+    Field(VariableDefine),
+    Method(FunctionDefine),
+    Comment,
+}
+
 enum AttributeType {
     Attribute((String, AstValue)),
     Content(String),
@@ -30,6 +177,8 @@ enum AttributeType {
     InlineExpr(CalcExpr),
     Condition(ConditionalStatement),
     Loop(LoopStatement),
+    Block(Vec<Statement>),
+    Comment(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -41,6 +190,12 @@ pub enum CalcExpr {
     Mul(Box<CalcExpr>, Box<CalcExpr>),
     Div(Box<CalcExpr>, Box<CalcExpr>),
     Mod(Box<CalcExpr>, Box<CalcExpr>),
+    Pow(Box<CalcExpr>, Box<CalcExpr>),
+    BitAnd(Box<CalcExpr>, Box<CalcExpr>),
+    BitOr(Box<CalcExpr>, Box<CalcExpr>),
+    BitXor(Box<CalcExpr>, Box<CalcExpr>),
+    Shl(Box<CalcExpr>, Box<CalcExpr>),
+    Shr(Box<CalcExpr>, Box<CalcExpr>),
     Eq(Box<CalcExpr>, Box<CalcExpr>),
     Ne(Box<CalcExpr>, Box<CalcExpr>),
     Gt(Box<CalcExpr>, Box<CalcExpr>),
@@ -49,11 +204,22 @@ pub enum CalcExpr {
     Le(Box<CalcExpr>, Box<CalcExpr>),
     And(Box<CalcExpr>, Box<CalcExpr>),
     Or(Box<CalcExpr>, Box<CalcExpr>),
+    Coalesce(Box<CalcExpr>, Box<CalcExpr>),
+    // `return a, b;` packs both values into a tuple.
+    Tuple(Box<CalcExpr>, Box<CalcExpr>),
+    // `value |> f(2)` => `f(value, 2)`
+    Pipe(Box<CalcExpr>, FunctionCall),
+    // `typeof value` => type name string.
+    TypeOf(Box<CalcExpr>),
+    // `value is number` => whether value has the type (or object) name.
+    Is(Box<CalcExpr>, String),
+    // `(fn (x) { return x + 1; })(41)` => call value of parenthesized expression.
+    Call(Box<CalcExpr>, Vec<AstValue>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct LinkExpr {
-    pub this: AstValue,
+    pub this: Box<CalcExpr>,
     pub list: Vec<LinkExprPart>,
 }
 
@@ -61,6 +227,8 @@ pub struct LinkExpr {
 pub enum LinkExprPart {
     Field(String),
     FunctionCall(FunctionCall),
+    // `?.` mark: short-circuit the link expression when current value is none.
+    Optional,
 }
 
 struct TypeParser;
@@ -104,20 +272,26 @@ impl TypeParser {
         context(
             "string",
             alt((
-                tag("\"\""),
+                nom::bytes::complete::tag("\"\""),
                 delimited(tag("\""), TypeParser::string_format, tag("\"")),
             )),
         )(message)
     }
 
     pub fn boolean(message: &str) -> IResult<&str, bool> {
-        let parse_true = value(true, tag_no_case("true"));
-        let parse_false = value(false, tag_no_case("false"));
+        let parse_true = value(true, keyword("true"));
+        let parse_false = value(false, keyword("false"));
         alt((parse_true, parse_false))(message)
     }
 
     pub fn number(message: &str) -> IResult<&str, f64> {
-        double(message)
+        // `double` also accepts `inf` and `nan`, which would eat identifiers like `info`.
+        map(
+            verify(consumed(double), |(text, _): &(&str, f64)| {
+                text.contains(|c: char| c.is_ascii_digit())
+            }),
+            |(_, v)| v,
+        )(message)
     }
 
     pub fn list(message: &str) -> IResult<&str, Vec<AstValue>> {
@@ -125,31 +299,60 @@ impl TypeParser {
             "list",
             delimited(
                 tag("["),
-                separated_list0(
-                    tag(","),
-                    delimited(multispace0, TypeParser::parse, multispace0),
-                ),
-                delimited(opt(tag(",")), multispace0, tag("]")),
+                separated_list0(tag(","), delimited(trivia, TypeParser::parse, trivia)),
+                delimited(opt(tag(",")), trivia, tag("]")),
             ),
         )(message)
     }
 
-    fn variable(message: &str) -> IResult<&str, String> {
+    fn variable(message: &str) -> IResult<&str, Symbol> {
         context(
             "variable",
-            map(VariableParser::parse_var_name, |v| v.to_string()),
+            map(VariableParser::parse_var_name, Symbol::from),
         )(message)
     }
 
-    fn variable_index(message: &str) -> IResult<&str, (String, Box<AstValue>)> {
+    fn variable_index(message: &str) -> IResult<&str, (Symbol, Box<CalcExpr>)> {
         context(
             "variable index",
             map(
                 tuple((
                     VariableParser::parse_var_name,
-                    delimited(tag("["), TypeParser::parse_index_type, tag("]")),
+                    delimited(tag("["), TypeParser::parse_index, tag("]")),
+                )),
+                |v| (Symbol::from(v.0), Box::new(v.1)),
+            ),
+        )(message)
+    }
+
+    fn slice_part(message: &str) -> IResult<&str, Option<CalcExpr>> {
+        opt(TypeParser::parse_index)(message)
+    }
+
+    // `list[start:end:step]`, every part is optional.
+    fn variable_slice(message: &str) -> IResult<&str, (Symbol, SliceIndex)> {
+        let part = TypeParser::slice_part;
+        context(
+            "variable slice",
+            map(
+                tuple((
+                    VariableParser::parse_var_name,
+                    delimited(
+                        tag("["),
+                        tuple((part, char(':'), part, opt(preceded(char(':'), part)))),
+                        tag("]"),
+                    ),
                 )),
-                |v| (v.0.to_string(), Box::new(v.1)),
+                |(name, (start, _, end, step))| {
+                    (
+                        Symbol::from(name),
+                        SliceIndex {
+                            start: start.map(Box::new),
+                            end: end.map(Box::new),
+                            step: step.flatten().map(Box::new),
+                        },
+                    )
+                },
             ),
         )(message)
     }
@@ -163,9 +366,14 @@ impl TypeParser {
                     separated_list0(
                         tag(","),
                         separated_pair(
-                            delimited(multispace0, TypeParser::string, multispace0),
+                            // keys may be unquoted like element attributes: `{ name: "x" }`
+                            delimited(
+                                trivia,
+                                alt((TypeParser::string, ElementParser::parse_attr_name)),
+                                trivia,
+                            ),
                             tag(":"),
-                            delimited(multispace0, TypeParser::parse, multispace0),
+                            delimited(trivia, TypeParser::parse, trivia),
                         ),
                     ),
                     |tuple_vec: Vec<(&str, AstValue)>| {
@@ -175,7 +383,7 @@ impl TypeParser {
                             .collect()
                     },
                 ),
-                delimited(opt(tag(",")), multispace0, tag("}")),
+                delimited(opt(tag(",")), trivia, tag("}")),
             ),
         )(message)
     }
@@ -187,9 +395,9 @@ impl TypeParser {
                 tag("("),
                 map(
                     separated_pair(
-                        delimited(multispace0, TypeParser::parse, multispace0),
+                        delimited(trivia, TypeParser::parse, trivia),
                         tag(","),
-                        delimited(multispace0, TypeParser::parse, multispace0),
+                        delimited(trivia, TypeParser::parse, trivia),
                     ),
                     |pair: (AstValue, AstValue)| (Box::new(pair.0), Box::new(pair.1)),
                 ),
@@ -198,15 +406,9 @@ impl TypeParser {
         )(message)
     }
 
-    fn parse_index_type(message: &str) -> IResult<&str, AstValue> {
-        context(
-            "value",
-            alt((
-                map(TypeParser::number, AstValue::Number),
-                map(TypeParser::string, |s| AstValue::String(String::from(s))),
-                map(TypeParser::variable, AstValue::Variable),
-            )),
-        )(message)
+    // any expression inside `[]`: `list[i + 1]`, `dict[key]`.
+    fn parse_index(message: &str) -> IResult<&str, CalcExpr> {
+        context("index", delimited(space0, CalculateParser::expr, space0))(message)
     }
 
     pub fn parse(message: &str) -> IResult<&str, AstValue> {
@@ -219,10 +421,17 @@ impl TypeParser {
                 map(TypeParser::list, AstValue::List),
                 map(TypeParser::dict, AstValue::Dict),
                 map(TypeParser::tuple, AstValue::Tuple),
+                map(
+                    alt((StatementParser::parse_for, StatementParser::parse_while)),
+                    |v| AstValue::Loop(Box::new(v)),
+                ),
                 map(ElementParser::parse, AstValue::Element),
-                map(FunctionParser::call, AstValue::FunctionCaller),
+                // before call, `fn(x) { ... }` is not a call of `fn`.
                 map(FunctionParser::define, AstValue::FunctionDefine),
+                map(FunctionParser::lambda, AstValue::FunctionDefine),
+                map(FunctionParser::call, AstValue::FunctionCaller),
                 map(TypeParser::variable_index, AstValue::VariableIndex),
+                map(TypeParser::variable_slice, AstValue::VariableSlice),
                 map(TypeParser::variable, AstValue::Variable),
             )),
         )(message)
@@ -244,24 +453,131 @@ impl VariableParser {
         )(message)
     }
 
+    // `: number` type annotation after variable or parameter name.
+    fn parse_annotation(message: &str) -> IResult<&str, String> {
+        preceded(tuple((space0, char(':'), space0)), Self::parse_var_name)(message)
+    }
+
+    // parameter list with optional type annotations: `a: number, b`
+    fn parse_params(message: &str) -> IResult<&str, (Vec<Symbol>, Vec<Option<String>>)> {
+        map(
+            separated_list0(
+                tag(","),
+                delimited(
+                    trivia,
+                    pair(
+                        map(Self::parse_var_name, Symbol::from),
+                        opt(Self::parse_annotation),
+                    ),
+                    trivia,
+                ),
+            ),
+            |v| v.into_iter().unzip(),
+        )(message)
+    }
+
+    // fixed parameters, and optional `@rest` at the end which collects remaining arguments.
+    // parameters may span lines and end with a `,`.
+    fn parse_signature(message: &str) -> IResult<&str, (ParamsType, Vec<Option<String>>)> {
+        let rest = || {
+            delimited(
+                trivia,
+                preceded(tag("@"), map(Self::parse_var_name, Symbol::from)),
+                trivia,
+            )
+        };
+        terminated(
+            alt((
+                map(rest(), |rest| (ParamsType::Variable(vec![], rest), vec![])),
+                map(
+                    pair(Self::parse_params, opt(preceded(tag(","), rest()))),
+                    |((params, types), rest)| match rest {
+                        Some(rest) => (ParamsType::Variable(params, rest), types),
+                        None => (ParamsType::List(params), types),
+                    },
+                ),
+            )),
+            pair(opt(tag(",")), trivia),
+        )(message)
+    }
+
     fn parse(message: &str) -> IResult<&str, VariableDefine> {
         context(
             "variable",
             map(
                 tuple((
-                    opt(terminated(tag("let"), space1)),
-                    terminated(Self::parse_var_name, delimited(space0, tag("="), space0)),
+                    // `outer` and `global` are names too when no name follows: `outer = 1;`.
+                    opt(terminated(
+                        alt((
+                            value(None, tag("let")),
+                            value(Some(AssignScope::Outer), tag("outer")),
+                            value(Some(AssignScope::Global), tag("global")),
+                        )),
+                        pair(space1, peek(Self::parse_var_name)),
+                    )),
+                    terminated(
+                        tuple((
+                            Self::parse_var_name,
+                            many0(alt((
+                                delimited(tag("["), TypeParser::parse_index, tag("]")),
+                                // member is same as string index: `el.attributes` => `el["attributes"]`
+                                map(
+                                    preceded(
+                                        char('.'),
+                                        alt((
+                                            Self::parse_var_name,
+                                            map(digit1, |v: &str| v.to_string()),
+                                        )),
+                                    ),
+                                    |v| CalcExpr::Value(AstValue::String(v)),
+                                ),
+                            ))),
+                            opt(Self::parse_annotation),
+                        )),
+                        delimited(space0, tag("="), space0),
+                    ),
                     CalculateParser::expr,
                     tag(";"),
                 )),
                 |v| VariableDefine {
-                    new: v.0.is_some(),
-                    name: v.1.to_string(),
+                    new: v.0 == Some(None),
+                    name: Symbol::from(v.1 .0),
+                    index: v.1 .1,
+                    annotation: v.1 .2,
+                    scope: v.0.flatten(),
                     expr: v.2,
                 },
             ),
         )(message)
     }
+
+    fn parse_tuple(message: &str) -> IResult<&str, TupleDefine> {
+        context(
+            "tuple variable",
+            map(
+                tuple((
+                    opt(terminated(tag("let"), space1)),
+                    delimited(
+                        pair(char('('), space0),
+                        separated_pair(
+                            Self::parse_var_name,
+                            delimited(space0, char(','), space0),
+                            Self::parse_var_name,
+                        ),
+                        pair(space0, char(')')),
+                    ),
+                    delimited(space0, tag("="), space0),
+                    CalculateParser::expr,
+                    tag(";"),
+                )),
+                |(new, (a, b), _, expr, _)| TupleDefine {
+                    new: new.is_some(),
+                    names: (Symbol::from(a), Symbol::from(b)),
+                    expr,
+                },
+            ),
+        )(message)
+    }
 }
 
 struct CalculateParser;
@@ -270,52 +586,91 @@ impl CalculateParser {
         delimited(
             space0,
             alt((
+                map(
+                    preceded(pair(keyword("typeof"), space0), Self::factor),
+                    |v| CalcExpr::TypeOf(Box::new(v)),
+                ),
                 map(Self::link, CalcExpr::LinkExpr),
                 map(TypeParser::parse, CalcExpr::Value),
-                delimited(char('('), Self::expr, char(')')),
+                Self::parens,
             )),
             space0,
         )(input)
     }
 
+    // `(expr)`, argument lists right after it call the value: `(f)(1)(2)`.
+    fn parens(input: &str) -> IResult<&str, CalcExpr> {
+        map(
+            pair(
+                delimited(char('('), Self::expr, char(')')),
+                many0(delimited(char('('), FunctionParser::arguments, char(')'))),
+            ),
+            |(expr, calls)| {
+                calls
+                    .into_iter()
+                    .fold(expr, |f, args| CalcExpr::Call(Box::new(f), args))
+            },
+        )(input)
+    }
+
     fn link(input: &str) -> IResult<&str, LinkExpr> {
         delimited(
             space0,
             map(
                 tuple((
-                    terminated(
-                        TypeParser::parse,
-                        delimited(multispace0, tag("."), multispace0),
-                    ),
-                    separated_list1(
-                        delimited(multispace0, tag("."), multispace0),
+                    alt((map(TypeParser::parse, CalcExpr::Value), Self::parens)),
+                    many1(pair(
+                        delimited(
+                            trivia,
+                            alt((value(true, tag("?.")), value(false, tag(".")))),
+                            trivia,
+                        ),
                         alt((
-                            map(FunctionParser::call_single_name, |v| LinkExprPart::FunctionCall(v)),
+                            map(FunctionParser::call_single_name, LinkExprPart::FunctionCall),
                             map(
                                 alt((
                                     VariableParser::parse_var_name,
                                     map(digit1, |v: &str| v.to_string()),
                                 )),
-                                |v| LinkExprPart::Field(v),
+                                LinkExprPart::Field,
                             ),
                         )),
-                    ),
+                    )),
                 )),
-                |v| LinkExpr {
-                    this: v.0,
-                    list: v.1,
+                |(this, parts)| {
+                    let mut list = vec![];
+                    for (optional, part) in parts {
+                        if optional {
+                            list.push(LinkExprPart::Optional);
+                        }
+                        list.push(part);
+                    }
+                    LinkExpr {
+                        this: Box::new(this),
+                        list,
+                    }
                 },
             ),
             space0,
         )(input)
     }
 
+    // right-associative: `2 ** 3 ** 2` => `2 ** (3 ** 2)`
+    fn power(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, base) = Self::factor(input)?;
+        let (input, exp) = opt(preceded(delimited(space0, tag("**"), space0), Self::power))(input)?;
+        match exp {
+            Some(exp) => Ok((input, CalcExpr::Pow(Box::new(base), Box::new(exp)))),
+            None => Ok((input, base)),
+        }
+    }
+
     fn term(input: &str) -> IResult<&str, CalcExpr> {
-        let (input, init) = Self::factor(input)?;
+        let (input, init) = Self::power(input)?;
         fold_many0(
             pair(
                 delimited(space0, alt((char('*'), char('/'), char('%'))), space0),
-                Self::factor,
+                Self::power,
             ),
             move || init.clone(),
             |acc, (op, val)| match op {
@@ -343,8 +698,72 @@ impl CalculateParser {
         )(input)
     }
 
-    fn comparison(input: &str) -> IResult<&str, CalcExpr> {
+    fn shift(input: &str) -> IResult<&str, CalcExpr> {
         let (input, init) = Self::add_sub(input)?;
+        fold_many0(
+            pair(
+                delimited(space0, alt((tag("<<"), tag(">>"))), space0),
+                Self::add_sub,
+            ),
+            move || init.clone(),
+            |acc, (op, val)| match op {
+                "<<" => CalcExpr::Shl(Box::new(acc), Box::new(val)),
+                ">>" => CalcExpr::Shr(Box::new(acc), Box::new(val)),
+                _ => unreachable!(),
+            },
+        )(input)
+    }
+
+    fn bit_and(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::shift(input)?;
+        fold_many0(
+            pair(
+                // `&&` is logical and.
+                delimited(space0, terminated(char('&'), not(char('&'))), space0),
+                Self::shift,
+            ),
+            move || init.clone(),
+            |acc, (_, val)| CalcExpr::BitAnd(Box::new(acc), Box::new(val)),
+        )(input)
+    }
+
+    fn bit_xor(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::bit_and(input)?;
+        fold_many0(
+            pair(delimited(space0, char('^'), space0), Self::bit_and),
+            move || init.clone(),
+            |acc, (_, val)| CalcExpr::BitXor(Box::new(acc), Box::new(val)),
+        )(input)
+    }
+
+    fn bit_or(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::bit_xor(input)?;
+        fold_many0(
+            pair(
+                // `||` is logical or, `|>` is pipeline.
+                delimited(space0, terminated(char('|'), not(one_of("|>"))), space0),
+                Self::bit_xor,
+            ),
+            move || init.clone(),
+            |acc, (_, val)| CalcExpr::BitOr(Box::new(acc), Box::new(val)),
+        )(input)
+    }
+
+    fn type_check(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::bit_or(input)?;
+        fold_many0(
+            delimited(
+                pair(keyword("is"), space1),
+                VariableParser::parse_var_name,
+                space0,
+            ),
+            move || init.clone(),
+            |acc, name| CalcExpr::Is(Box::new(acc), name),
+        )(input)
+    }
+
+    fn comparison(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::type_check(input)?;
         fold_many0(
             pair(
                 delimited(
@@ -359,7 +778,7 @@ impl CalculateParser {
                     )),
                     space0,
                 ),
-                Self::add_sub,
+                Self::type_check,
             ),
             move || init.clone(),
             |acc, (op, val)| match op {
@@ -398,36 +817,78 @@ impl CalculateParser {
         )(input)
     }
 
+    fn coalesce(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::logical_or(input)?;
+        fold_many0(
+            pair(
+                delimited(space0, tag("??"), space0),
+                Self::logical_or,
+            ),
+            move || init.clone(),
+            |acc, (_, val)| CalcExpr::Coalesce(Box::new(acc), Box::new(val)),
+        )(input)
+    }
+
+    fn pipe(input: &str) -> IResult<&str, CalcExpr> {
+        let (input, init) = Self::coalesce(input)?;
+        fold_many0(
+            pair(
+                delimited(space0, tag("|>"), space0),
+                terminated(
+                    alt((
+                        FunctionParser::call,
+                        map(FunctionParser::name, |name| FunctionCall {
+                            name,
+                            arguments: vec![],
+                        }),
+                    )),
+                    space0,
+                ),
+            ),
+            move || init.clone(),
+            |acc, (_, call)| CalcExpr::Pipe(Box::new(acc), call),
+        )(input)
+    }
+
     fn expr(input: &str) -> IResult<&str, CalcExpr> {
-        Self::logical_or(input)
+        Self::pipe(input)
     }
 }
 
 struct FunctionParser;
 impl FunctionParser {
+    fn name(message: &str) -> IResult<&str, FunctionName> {
+        context(
+            "function name",
+            map(
+                separated_list1(tag("::"), VariableParser::parse_var_name),
+                |v| {
+                    if v.len() > 1 {
+                        FunctionName::Namespace(v.into_iter().map(Symbol::from).collect())
+                    } else {
+                        FunctionName::Single(Symbol::from(&v[0]))
+                    }
+                },
+            ),
+        )(message)
+    }
+
+    // arguments may span lines and end with a `,`: `f(\n    fn (x) { ... },\n    1,\n)`
+    fn arguments(message: &str) -> IResult<&str, Vec<AstValue>> {
+        delimited(
+            trivia,
+            separated_list0(tag(","), delimited(trivia, TypeParser::parse, trivia)),
+            pair(opt(tag(",")), trivia),
+        )(message)
+    }
+
     fn call(message: &str) -> IResult<&str, FunctionCall> {
         context(
             "function call",
             map(
                 tuple((
-                    terminated(
-                        map(
-                            separated_list1(tag("::"), VariableParser::parse_var_name),
-                            |v| {
-                                if v.len() > 1 {
-                                    FunctionName::Namespace(v)
-                                } else {
-                                    FunctionName::Single(v.get(0).unwrap().to_string())
-                                }
-                            }
-                        ),
-                        tag("(")
-                    ),
-                    delimited(
-                        space0,
-                        separated_list0(tag(","), delimited(space0, TypeParser::parse, space0)),
-                        space0,
-                    ),
+                    terminated(FunctionParser::name, tag("(")),
+                    Self::arguments,
                     tag(")"),
                 )),
                 |(name, arguments, _)| FunctionCall { name, arguments },
@@ -444,16 +905,12 @@ impl FunctionParser {
                         map(
                             VariableParser::parse_var_name,
                             |v| {
-                                FunctionName::Single(v)
+                                FunctionName::Single(Symbol::from(v))
                             }
                         ),
                         tag("(")
                     ),
-                    delimited(
-                        space0,
-                        separated_list0(tag(","), delimited(space0, TypeParser::parse, space0)),
-                        space0,
-                    ),
+                    Self::arguments,
                     tag(")"),
                 )),
                 |(name, arguments, _)| FunctionCall { name, arguments },
@@ -466,30 +923,76 @@ impl FunctionParser {
             "function define",
             map(
                 tuple((
-                    pair(tag("fn"), space1),
+                    tuple((
+                        opt(doc_comments),
+                        many0(terminated(preceded(tag("@"), Self::name), multispace1)),
+                        pair(keyword("fn"), space0),
+                    )),
                     opt(terminated(VariableParser::parse_var_name, space0)),
                     delimited(
                         tag("("),
-                        alt((
-                            map(preceded(tag("@"), VariableParser::parse_var_name), |name| {
-                                ParamsType::Variable(name)
-                            }),
-                            map(
-                                separated_list0(
-                                    tag(","),
-                                    delimited(space0, VariableParser::parse_var_name, space0),
-                                ),
-                                |v| ParamsType::List(v),
-                            ),
+                        VariableParser::parse_signature,
+                        pair(tag(")"), space0),
+                    ),
+                    terminated(
+                        opt(terminated(
+                            preceded(pair(tag("->"), space0), VariableParser::parse_var_name),
+                            space0,
                         )),
-                        delimited(tag(")"), space0, tag("{")),
+                        tag("{"),
                     ),
-                    delimited(multispace0, parse_rsx, pair(multispace0, tag("}"))),
+                    delimited(multispace0, parse_block, pair(multispace0, tag("}"))),
                 )),
-                |(_, name, params, inner)| FunctionDefine {
-                    name,
+                |((docs, decorators, _), name, (params, param_types), return_type, inner)| {
+                    FunctionDefine {
+                        name,
+                        docs,
+                        decorators,
+                        params,
+                        param_types,
+                        return_type,
+                        inner,
+                        captures: vec![],
+                    }
+                },
+            ),
+        )(message)
+    }
+
+    // define with `///` doc comments or `@` decorators, it must be tried before line comment.
+    fn annotated(message: &str) -> IResult<&str, FunctionDefine> {
+        preceded(
+            peek(alt((nom::bytes::complete::tag("///"), tag("@")))),
+            Self::define,
+        )(message)
+    }
+
+    // `|a, b| a + b` => `fn (a, b) { return a + b; }`
+    fn lambda(message: &str) -> IResult<&str, FunctionDefine> {
+        context(
+            "lambda",
+            map(
+                pair(
+                    delimited(
+                        tag("|"),
+                        VariableParser::parse_signature,
+                        pair(tag("|"), space0),
+                    ),
+                    spanned(CalculateParser::expr),
+                ),
+                |((params, param_types), (expr, span))| FunctionDefine {
+                    name: None,
+                    docs: None,
+                    decorators: vec![],
                     params,
-                    inner,
+                    param_types,
+                    return_type: None,
+                    inner: vec![Statement {
+                        kind: DioAstStatement::ReturnValue(expr),
+                        span,
+                        comments: vec![],
+                    }],
+                    captures: vec![],
                 },
             ),
         )(message)
@@ -500,19 +1003,19 @@ struct StatementParser;
 impl StatementParser {
     fn parse_if(message: &str) -> IResult<&str, ConditionalStatement> {
         context(
-            "if statment",
+            "if statement",
             map(
                 tuple((
                     pair(tag("if"), space1),
-                    terminated(CalculateParser::expr, pair(space0, tag("{"))),
-                    delimited(multispace0, parse_rsx, pair(multispace0, tag("}"))),
+                    terminated(block_head(CalculateParser::expr), pair(space0, tag("{"))),
+                    delimited(multispace0, parse_block, pair(multispace0, tag("}"))),
                     opt(delimited(
                         delimited(
                             space0,
                             tag("else"),
                             delimited(space0, tag("{"), multispace0),
                         ),
-                        parse_rsx,
+                        parse_block,
                         pair(multispace0, tag("}")),
                     )),
                 )),
@@ -530,27 +1033,132 @@ impl StatementParser {
             map(
                 tuple((
                     pair(tag("for"), space1),
-                    pair(TypeParser::variable, pair(space1, tag("in"))),
-                    delimited(space1, TypeParser::parse, pair(space0, tag("{"))),
+                    terminated(
+                        alt((
+                            map(
+                                delimited(
+                                    pair(char('('), multispace0),
+                                    separated_pair(
+                                        TypeParser::variable,
+                                        delimited(multispace0, char(','), multispace0),
+                                        TypeParser::variable,
+                                    ),
+                                    pair(multispace0, char(')')),
+                                ),
+                                |(index, var)| (Some(index), var),
+                            ),
+                            map(TypeParser::variable, |var| (None, var)),
+                        )),
+                        pair(space1, tag("in")),
+                    ),
+                    delimited(space1, block_head(TypeParser::parse), pair(space0, tag("{"))),
                     delimited(multispace0, parse_rsx, pair(multispace0, tag("}"))),
                 )),
-                |(_, (var_name, _), iter, inner)| LoopStatement {
+                |(_, (index, var_name), iter, inner)| LoopStatement {
                     execute_type: crate::ast::LoopExecuteType::Iter {
                         iter,
                         var: var_name,
+                        index,
                     },
                     inner,
                 },
             ),
         )(message)
     }
+    fn parse_object(message: &str) -> IResult<&str, ObjectDefine> {
+        context(
+            "object define",
+            map(
+                tuple((
+                    pair(tag("object"), space1),
+                    terminated(VariableParser::parse_var_name, pair(space0, tag("{"))),
+                    many0(delimited(
+                        multispace0,
+                        alt((
+                            map(FunctionParser::annotated, ObjectItem::Method),
+                            map(comment, |_| ObjectItem::Comment),
+                            map(VariableParser::parse, ObjectItem::Field),
+                            map(FunctionParser::define, ObjectItem::Method),
+                        )),
+                        multispace0,
+                    )),
+                    tag("}"),
+                )),
+                |(_, name, items, _)| {
+                    let mut fields = vec![];
+                    let mut methods = vec![];
+                    for item in items {
+                        match item {
+                            ObjectItem::Field(v) => fields.push(v),
+                            ObjectItem::Method(v) if v.name.is_some() => methods.push(v),
+                            _ => {}
+                        }
+                    }
+                    ObjectDefine {
+                        name,
+                        fields,
+                        methods,
+                    }
+                },
+            ),
+        )(message)
+    }
+    fn parse_enum(message: &str) -> IResult<&str, EnumDefine> {
+        context(
+            "enum define",
+            map(
+                tuple((
+                    pair(tag("enum"), space1),
+                    terminated(VariableParser::parse_var_name, pair(space0, tag("{"))),
+                    separated_list0(
+                        tag(","),
+                        delimited(multispace0, VariableParser::parse_var_name, multispace0),
+                    ),
+                    delimited(opt(tag(",")), multispace0, tag("}")),
+                )),
+                |(_, name, variants, _)| EnumDefine { name, variants },
+            ),
+        )(message)
+    }
+    fn parse_assert(message: &str) -> IResult<&str, AssertStatement> {
+        context(
+            "assert statement",
+            map(
+                tuple((
+                    pair(tag("assert"), space1),
+                    consumed(CalculateParser::expr),
+                    opt(preceded(
+                        delimited(space0, tag(","), space0),
+                        CalculateParser::expr,
+                    )),
+                    pair(space0, tag(";")),
+                )),
+                |(_, (source, expr), message, _)| AssertStatement {
+                    expr,
+                    source: source.trim().to_string(),
+                    message,
+                },
+            ),
+        )(message)
+    }
+    fn parse_break(message: &str) -> IResult<&str, Option<CalcExpr>> {
+        context(
+            "break statement",
+            delimited(
+                keyword("break"),
+                opt(preceded(space1, CalculateParser::expr)),
+                pair(space0, tag(";")),
+            ),
+        )(message)
+    }
+
     fn parse_while(message: &str) -> IResult<&str, LoopStatement> {
         context(
             "while statement",
             map(
                 tuple((
                     pair(tag("while"), space1),
-                    terminated(CalculateParser::expr, pair(space0, tag("{"))),
+                    terminated(block_head(CalculateParser::expr), pair(space0, tag("{"))),
                     delimited(multispace0, parse_rsx, pair(multispace0, tag("}"))),
                 )),
                 |(_, expr, inner)| LoopStatement {
@@ -579,10 +1187,66 @@ impl ModuleParser {
             map(
                 delimited(
                     pair(tag("use"), space1),
-                    separated_list1(tag("::"), Self::parse_module_name),
+                    tuple((
+                        separated_list1(tag("::"), Self::parse_module_name),
+                        opt(Self::parse_requirements),
+                        opt(preceded(
+                            tuple((space1, tag("as"), space1)),
+                            VariableParser::parse_var_name,
+                        )),
+                    )),
+                    pair(space0, tag(";")),
+                ),
+                |(list, requirements, alias)| UseStatement {
+                    path: list.into_iter().map(Symbol::from).collect(),
+                    requirements: requirements.unwrap_or_default(),
+                    alias,
+                },
+            ),
+        )(message)
+    }
+
+    // `(>=1.2, <2)` after module path.
+    fn parse_requirements(message: &str) -> IResult<&str, Vec<VersionRequirement>> {
+        let version = map_opt(recognize(separated_list1(char('.'), digit1)), Version::parse);
+        let op = alt((
+            value(VersionOp::GreaterEq, tag(">=")),
+            value(VersionOp::LessEq, tag("<=")),
+            value(VersionOp::Greater, tag(">")),
+            value(VersionOp::Less, tag("<")),
+            value(VersionOp::Exact, tag("=")),
+            value(VersionOp::Compatible, tag("^")),
+        ));
+        let requirement = map(
+            pair(opt(terminated(op, space0)), version),
+            |(op, version)| VersionRequirement {
+                op: op.unwrap_or(VersionOp::Compatible),
+                version,
+            },
+        );
+        context(
+            "version requirement",
+            delimited(
+                pair(space0, char('(')),
+                separated_list1(
+                    char(','),
+                    delimited(multispace0, requirement, multispace0),
+                ),
+                pair(opt(char(',')), pair(multispace0, char(')'))),
+            ),
+        )(message)
+    }
+
+    fn parse_file_use(message: &str) -> IResult<&str, String> {
+        context(
+            "use file statement",
+            map(
+                delimited(
+                    pair(tag("use"), space1),
+                    TypeParser::string,
                     pair(space0, tag(";"))
                 ),
-                |list| UseStatement(list.iter().map(|v| v.to_string()).collect())
+                |path| path.to_string()
             )
         )(message)
     }
@@ -595,6 +1259,14 @@ impl ElementParser {
         context("element name", alphanumeric1)(message)
     }
 
+    // `.card#main` after element name, `.` adds a class and `#` sets the id.
+    fn parse_shorthand(message: &str) -> IResult<&str, Vec<(char, &str)>> {
+        many0(pair(
+            one_of(".#"),
+            take_while1(|c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')),
+        ))(message)
+    }
+
     fn attr_name_style(c: char) -> bool {
         matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-')
     }
@@ -603,190 +1275,446 @@ impl ElementParser {
         context("element name", take_while1(Self::attr_name_style))(message)
     }
 
-fn parse(message: &str) -> IResult<&str, AstElement> {
-    context(
-        "element",
+    fn parse_attribute(message: &str) -> IResult<&str, AttributeType> {
         map(
-            pair(
-                terminated(ElementParser::parse_element_name, multispace0),
+            separated_pair(
+                ElementParser::parse_attr_name,
+                // `::` belongs to a namespaced call, not an attribute
                 delimited(
-                    tag("{"),
-                    map(
-                        pair(
-                            many0(
-                                alt((
-                                    terminated(
-                                        alt((
-                                            map(
-                                                separated_pair(
-                                                    delimited(
-                                                        multispace0,
-                                                        ElementParser::parse_attr_name,
-                                                        multispace0,
-                                                    ),
-                                                    tag(":"),
-                                                    delimited(multispace0, TypeParser::parse, multispace0),
-                                                ),
-                                                |v| AttributeType::Attribute((v.0.to_string(), v.1)),
-                                            ),
-                                            map(
-                                                delimited(multispace0, CalculateParser::expr, multispace0),
-                                                |v| AttributeType::InlineExpr(v),
-                                            ),
-                                            map(
-                                                delimited(multispace0, TypeParser::string, multispace0),
-                                                |v| AttributeType::Content(v.to_string()),
-                                            ),
-                                        )),
-                                        tag(","),
-                                    ),
-                                    alt((
-                                        map(
-                                            delimited(multispace0, ElementParser::parse, multispace0),
-                                            AttributeType::Element,
-                                        ),
-                                        map(
-                                            delimited(multispace0, StatementParser::parse_if, multispace0),
-                                            |v| AttributeType::Condition(v),
-                                        ),
-                                        map(
-                                            delimited(multispace0, StatementParser::parse_for, multispace0),
-                                            |v| AttributeType::Loop(v),
-                                        ),
-                                        map(
-                                            delimited(multispace0, StatementParser::parse_while, multispace0),
-                                            |v| AttributeType::Loop(v),
-                                        ),
-                                    )),
-                                ))
-                            ),
-                            opt(
-                                alt((
-                                    map(
-                                        separated_pair(
-                                            delimited(multispace0, ElementParser::parse_attr_name, multispace0),
-                                            tag(":"),
-                                            delimited(multispace0, TypeParser::parse, multispace0),
-                                        ),
-                                        |v| AttributeType::Attribute((v.0.to_string(), v.1)),
-                                    ),
-                                    map(
-                                        delimited(multispace0, CalculateParser::expr, multispace0),
-                                        |v| AttributeType::InlineExpr(v),
-                                    ),
-                                    map(
-                                        delimited(multispace0, TypeParser::string, multispace0),
-                                        |v| AttributeType::Content(v.to_string()),
-                                    ),
-                                    map(
-                                        delimited(multispace0, ElementParser::parse, multispace0),
-                                        AttributeType::Element,
-                                    ),
-                                    map(
-                                        delimited(multispace0, StatementParser::parse_if, multispace0),
-                                        |v| AttributeType::Condition(v),
-                                    ),
-                                    map(
-                                        delimited(multispace0, StatementParser::parse_for, multispace0),
-                                        |v| AttributeType::Loop(v),
-                                    ),
-                                    map(
-                                        delimited(multispace0, StatementParser::parse_while, multispace0),
-                                        |v| AttributeType::Loop(v),
-                                    ),
-                                ))
-                            )
-                        ),
-                        |(mut attrs, last_attr)| {
-                            if let Some(attr) = last_attr {
-                                attrs.push(attr);
-                            }
-                            attrs
-                        }
+                    multispace0,
+                    terminated(char(':'), not(char(':'))),
+                    multispace0,
+                ),
+                TypeParser::parse,
+            ),
+            |(k, v)| AttributeType::Attribute((k.to_string(), v)),
+        )(message)
+    }
+
+    // items which own a `{ ... }` block, they don't need a `,` separator.
+    fn parse_block_item(message: &str) -> IResult<&str, AttributeType> {
+        alt((
+            map(
+                preceded(
+                    pair(keyword("do"), multispace0),
+                    delimited(
+                        pair(char('{'), multispace0),
+                        parse_block,
+                        pair(multispace0, char('}')),
                     ),
-                    // 解析闭合大括号
-                    tag("}"),
                 ),
+                AttributeType::Block,
             ),
-            |(name, attrs)| {
-                let mut attr: HashMap<String, AstValue> = HashMap::new();
-                let mut content = vec![];
-                for a in attrs {
-                    match a {
-                        AttributeType::Attribute((key, value)) => {
-                            attr.insert(key, value);
-                        }
-                        AttributeType::Content(c) => {
-                            content.push(AstElementContentType::Content(c));
-                        }
-                        AttributeType::Element(e) => {
-                            content.push(AstElementContentType::Children(e));
-                        }
-                        AttributeType::InlineExpr(s) => {
-                            content.push(AstElementContentType::InlineExpr(s));
-                        }
-                        AttributeType::Condition(c) => {
-                            content.push(AstElementContentType::Condition(c));
+            map(StatementParser::parse_if, AttributeType::Condition),
+            map(StatementParser::parse_for, AttributeType::Loop),
+            map(StatementParser::parse_while, AttributeType::Loop),
+            map(ElementParser::parse, AttributeType::Element),
+        ))(message)
+    }
+
+    fn parse_value_item(message: &str) -> IResult<&str, AttributeType> {
+        alt((
+            ElementParser::parse_attribute,
+            map(CalculateParser::expr, AttributeType::InlineExpr),
+            map(TypeParser::string, |v| {
+                AttributeType::Content(v.to_string())
+            }),
+        ))(message)
+    }
+
+    // every item is parsed exactly once: block items may be followed by a `,`,
+    // value items must be followed by a `,` unless they close the element.
+    fn parse_item(message: &str) -> IResult<&str, AttributeType> {
+        delimited(
+            multispace0,
+            alt((
+                map(comment, AttributeType::Comment),
+                terminated(
+                    ElementParser::parse_block_item,
+                    opt(pair(multispace0, char(','))),
+                ),
+                terminated(
+                    ElementParser::parse_value_item,
+                    alt((
+                        value((), pair(multispace0, char(','))),
+                        value((), peek(pair(multispace0, char('}')))),
+                        // comment after the last item: `"text" // note`
+                        value((), peek(tuple((multispace0, comment, multispace0, char('}'))))),
+                    )),
+                ),
+            )),
+            multispace0,
+        )(message)
+    }
+
+    fn parse(message: &str) -> IResult<&str, AstElement> {
+        if NO_ELEMENT.with(|v| v.get()) == Some(message.len()) {
+            return Err(nom::Err::Error(nom::error::ParseError::from_error_kind(
+                message,
+                ErrorKind::Verify,
+            )));
+        }
+        let (rest, element) = Self::parse_element(message)?;
+        HEAD_ELEMENTS.with(|v| {
+            if let Some(list) = v.borrow_mut().as_mut() {
+                list.push((message.len(), rest.len()));
+            }
+        });
+        Ok((rest, element))
+    }
+
+    fn parse_element(message: &str) -> IResult<&str, AstElement> {
+        context(
+            "element",
+            map(
+                spanned(pair(
+                    terminated(
+                        pair(
+                            ElementParser::parse_element_name,
+                            ElementParser::parse_shorthand,
+                        ),
+                        multispace0,
+                    ),
+                    delimited(
+                        pair(char('{'), multispace0),
+                        many0(ElementParser::parse_item),
+                        char('}'),
+                    ),
+                )),
+                |(((name, shorthand), attrs), span)| {
+                    let mut attr: HashMap<String, AstValue> = HashMap::new();
+                    let mut duplicates = vec![];
+                    let mut content = vec![];
+                    let classes: Vec<String> = shorthand
+                        .iter()
+                        .filter(|(mark, _)| *mark == '.')
+                        .map(|(_, v)| v.to_string())
+                        .collect();
+                    for (_, id) in shorthand.iter().filter(|(mark, _)| *mark == '#') {
+                        if attr.contains_key("id") && !duplicates.contains(&"id".to_string()) {
+                            duplicates.push("id".to_string());
                         }
-                        AttributeType::Loop(l) => {
-                            content.push(AstElementContentType::Loop(l));
+                        attr.insert("id".to_string(), AstValue::String(id.to_string()));
+                    }
+                    for a in attrs {
+                        match a {
+                            AttributeType::Attribute((key, value)) => {
+                                if attr.contains_key(&key) && !duplicates.contains(&key) {
+                                    duplicates.push(key.clone());
+                                }
+                                attr.insert(key, value);
+                            }
+                            AttributeType::Content(c) => {
+                                content.push(AstElementContentType::Content(c));
+                            }
+                            AttributeType::Element(e) => {
+                                content.push(AstElementContentType::Children(e));
+                            }
+                            AttributeType::InlineExpr(s) => {
+                                content.push(AstElementContentType::InlineExpr(s));
+                            }
+                            AttributeType::Condition(c) => {
+                                content.push(AstElementContentType::Condition(c));
+                            }
+                            AttributeType::Loop(l) => {
+                                content.push(AstElementContentType::Loop(l));
+                            }
+                            AttributeType::Block(b) => {
+                                content.push(AstElementContentType::Block(b));
+                            }
+                            AttributeType::Comment(c) => {
+                                content.push(AstElementContentType::Comment(c));
+                            }
                         }
                     }
-                }
-                AstElement {
-                    name: name.to_string(),
-                    attributes: attr,
-                    content,
-                }
-            },
-        ),
-    )(message)
-}}
+                    AstElement {
+                        name: name.to_string(),
+                        attributes: attr,
+                        classes,
+                        duplicate_attributes: duplicates,
+                        content,
+                        span,
+                    }
+                },
+            ),
+        )(message)
+    }
+}
+
+thread_local! {
+    // positions are lengths of the remaining input.
+    // start of the element which is parsed as `name` and block instead in `if`/`for`/`while`
+    // heads: `for v in items { ... }`.
+    static NO_ELEMENT: Cell<Option<usize>> = const { Cell::new(None) };
+    // (start, end) of elements parsed in the current head.
+    static HEAD_ELEMENTS: RefCell<Option<Vec<(usize, usize)>>> = const { RefCell::new(None) };
+    // comments consumed by `trivia` in the statement being parsed, spans are the input left
+    // like `spanned`.
+    static COMMENTS: RefCell<Option<Vec<Comment>>> = const { RefCell::new(None) };
+    // deepest failure while `deepest_failure` runs.
+    static DEEPEST: RefCell<Deepest> = const { RefCell::new((0, ErrorKind::Fail, vec![], vec![])) };
+}
+
+// head of `if`/`for`/`while` which must be followed by the block. when it is not, the element
+// at the end of head took the block: `items { ... }`, so head is parsed again without it.
+// elements inside of the head are kept: `for v in [div { "a" }] { ... }`.
+fn block_head<'a, O>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    move |message| {
+        let outer = HEAD_ELEMENTS.with(|v| v.replace(Some(vec![])));
+        let result = parser(message);
+        let elements = HEAD_ELEMENTS.with(|v| v.replace(outer)).unwrap_or_default();
+        let end = match &result {
+            Ok((rest, _)) if !rest.trim_start_matches([' ', '\t']).starts_with('{') => {
+                message.len() - rest.len()
+            }
+            _ => return result,
+        };
+        // only spaces may follow the element at the end.
+        let last = elements.iter().find(|(_, v)| {
+            let at = message.len() - v;
+            at <= end && message[at..end].trim().is_empty()
+        });
+        let start = match last {
+            Some((start, _)) => *start,
+            None => return result,
+        };
+        let prev = NO_ELEMENT.with(|v| v.replace(Some(start)));
+        let result = parser(message);
+        NO_ELEMENT.with(|v| v.set(prev));
+        result
+    }
+}
+
+// match a keyword only when it is not the prefix of a longer identifier.
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(
+        tag(word),
+        not(satisfy(|c: char| c.is_alphanumeric() || c == '_')),
+    )
+}
+
+// whitespace and `//` comments between items of literals, arguments, parameters and method
+// chains which span lines. these comments are kept in `Statement::comments`, comments in blocks
+// and element bodies are nodes of their own.
+fn trivia(message: &str) -> IResult<&str, ()> {
+    value((), many0_count(alt((multispace1, inline_comment))))(message)
+}
+
+// comment consumed by `trivia`, it is recorded for the statement being parsed.
+fn inline_comment(message: &str) -> IResult<&str, &str> {
+    let (rest, text) = comment(message)?;
+    COMMENTS.with(|v| {
+        if let Some(comments) = v.borrow_mut().as_mut() {
+            // alternatives which failed later may have recorded it already.
+            if !comments.iter().any(|c| c.span.start == message.len()) {
+                comments.push(Comment {
+                    text,
+                    span: Span {
+                        start: message.len(),
+                        end: rest.len(),
+                    },
+                });
+            }
+        }
+    });
+    Ok((rest, &message[..message.len() - rest.len()]))
+}
 
 fn comment(message: &str) -> IResult<&str, String> {
     context(
-        "Comment",
-        map(preceded(tag("//"), take_until("\n")), |comment: &str| {
-            comment.trim().to_string()
-        }),
+        "comment",
+        // comments may follow anything, they are not listed as expected.
+        map(
+            preceded(nom::bytes::complete::tag("//"), take_until("\n")),
+            |comment: &str| comment.trim().to_string(),
+        ),
     )(message)
 }
 
-pub(crate) fn parse_rsx(message: &str) -> IResult<&str, Vec<DioAstStatement>> {
-    context(
-        "AST Full",
-        many0(delimited(
-            multispace0,
-            alt((
-                map(comment, |v| DioAstStatement::LineComment(v)),
-                map(VariableParser::parse, |v| {
-                    DioAstStatement::VariableAss(v)
-                }),
-                map(
-                    delimited(tag("return "), CalculateParser::expr, tag(";")),
-                    |v| DioAstStatement::ReturnValue(v),
-                ),
-                map(
-                    terminated(FunctionParser::call, pair(space0, tag(";"))),
-                    |v| DioAstStatement::FunctionCall(v),
-                ),
-                map(StatementParser::parse_if, |v| {
-                    DioAstStatement::IfStatement(v)
-                }),
-                map(StatementParser::parse_for, |v| {
-                    DioAstStatement::LoopStatement(v)
-                }),
-                map(StatementParser::parse_while, |v| {
-                    DioAstStatement::LoopStatement(v)
-                }),
-                map(FunctionParser::define, |v| {
-                    DioAstStatement::FunctionDefine(v)
-                }),
-                map(ModuleParser::parse_use, |v| {
-                    DioAstStatement::ModuleUse(v)
-                }),
-            )),
+// consecutive `/// ...` lines, joined by line break.
+fn doc_comments(message: &str) -> IResult<&str, String> {
+    map(
+        many1(terminated(
+            preceded(nom::bytes::complete::tag("///"), take_until("\n")),
             multispace0,
         )),
+        |lines: Vec<&str>| {
+            lines
+                .iter()
+                .map(|v| v.strip_prefix(' ').unwrap_or(v).trim_end())
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+    )(message)
+}
+
+// parsed construct and the unparsed rest of input, errors are located in the input.
+pub type ParseResult<'a, T> = Result<(&'a str, T), ParseError>;
+
+// low-level parsers for single constructs, they return the unparsed rest of input.
+// use the wrappers in `ast` module to parse a complete fragment.
+pub fn parse_expr(message: &str) -> ParseResult<'_, CalcExpr> {
+    fragment(message, expr_node)
+}
+
+pub fn parse_function_define(message: &str) -> ParseResult<'_, FunctionDefine> {
+    fragment(message, function_node)
+}
+
+pub fn parse_element_node(message: &str) -> ParseResult<'_, AstElement> {
+    fragment(message, element_node)
+}
+
+pub(crate) fn expr_node(message: &str) -> IResult<&str, CalcExpr> {
+    context("expression", CalculateParser::expr)(message)
+}
+
+pub(crate) fn function_node(message: &str) -> IResult<&str, FunctionDefine> {
+    FunctionParser::define(message)
+}
+
+pub(crate) fn element_node(message: &str) -> IResult<&str, AstElement> {
+    ElementParser::parse(message)
+}
+
+fn fragment<'a, T>(
+    message: &'a str,
+    parser: impl Fn(&'a str) -> IResult<&'a str, T>,
+) -> ParseResult<'a, T> {
+    match parser(message).finish() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(parse_failure(message, message, parser)),
+    }
+}
+
+// statements of a function, if/else or `do` block, a final expression without `;` is
+// the value of the block, same as `return expr;`.
+fn parse_block(message: &str) -> IResult<&str, Vec<Statement>> {
+    map(
+        pair(parse_rsx, opt(spanned(CalculateParser::expr))),
+        |(mut stats, tail)| {
+            if let Some((expr, span)) = tail {
+                stats.push(Statement {
+                    kind: DioAstStatement::ReturnValue(expr),
+                    span,
+                    comments: vec![],
+                });
+            }
+            stats
+        },
     )(message)
 }
+
+pub(crate) fn parse_rsx(message: &str) -> IResult<&str, Vec<Statement>> {
+    context(
+        "statements",
+        many0(delimited(multispace0, parse_statement, multispace0)),
+    )(message)
+}
+
+// parse one top-level statement, leading whitespace is not skipped.
+// comments consumed by `trivia` are kept in the innermost statement.
+pub(crate) fn parse_statement(message: &str) -> IResult<&str, Statement> {
+    let outer = COMMENTS.with(|v| v.replace(Some(vec![])));
+    let result = spanned(statement_kind)(message);
+    let comments = COMMENTS.with(|v| v.replace(outer)).unwrap_or_default();
+    let (rest, (kind, span)) = result?;
+    Ok((
+        rest,
+        Statement {
+            kind,
+            span,
+            comments,
+        },
+    ))
+}
+
+// span is recorded as length of input left before and after the parser, because the whole
+// source is unknown here. resolver turns it into offsets once the source end is known.
+fn spanned<'a, T>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, T>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (T, Span)> {
+    move |message: &'a str| {
+        let (rest, value) = parser(message)?;
+        let span = Span {
+            start: message.len(),
+            end: rest.len(),
+        };
+        Ok((rest, (value, span)))
+    }
+}
+
+fn statement_kind(message: &str) -> IResult<&str, DioAstStatement> {
+    alt((
+        map(FunctionParser::annotated, DioAstStatement::FunctionDefine),
+        map(comment, DioAstStatement::LineComment),
+        map(StatementParser::parse_break, DioAstStatement::Break),
+        map(
+            delimited(
+                pair(keyword("defer"), space1),
+                CalculateParser::expr,
+                pair(space0, tag(";")),
+            ),
+            DioAstStatement::Defer,
+        ),
+        map(StatementParser::parse_assert, |v| {
+            DioAstStatement::Assert(v)
+        }),
+        map(StatementParser::parse_enum, |v| {
+            DioAstStatement::EnumDefine(v)
+        }),
+        map(StatementParser::parse_object, |v| {
+            DioAstStatement::ObjectDefine(v)
+        }),
+        map(VariableParser::parse, DioAstStatement::VariableAss),
+        map(VariableParser::parse_tuple, DioAstStatement::TupleAss),
+        map(
+            delimited(
+                tag("return "),
+                pair(
+                    CalculateParser::expr,
+                    opt(preceded(char(','), CalculateParser::expr)),
+                ),
+                tag(";"),
+            ),
+            |(v, second)| match second {
+                Some(second) => DioAstStatement::ReturnValue(CalcExpr::Tuple(
+                    Box::new(v),
+                    Box::new(second),
+                )),
+                None => DioAstStatement::ReturnValue(v),
+            },
+        ),
+        map(
+            terminated(FunctionParser::call, pair(space0, tag(";"))),
+            DioAstStatement::FunctionCall,
+        ),
+        map(
+            terminated(
+                verify(CalculateParser::link, |v: &LinkExpr| {
+                    matches!(v.list.last(), Some(LinkExprPart::FunctionCall(_)))
+                }),
+                pair(space0, tag(";")),
+            ),
+            |v| DioAstStatement::MethodCall(CalcExpr::LinkExpr(v)),
+        ),
+        map(StatementParser::parse_if, |v| {
+            DioAstStatement::IfStatement(v)
+        }),
+        map(StatementParser::parse_for, |v| {
+            DioAstStatement::LoopStatement(v)
+        }),
+        map(StatementParser::parse_while, |v| {
+            DioAstStatement::LoopStatement(v)
+        }),
+        map(FunctionParser::define, |v| {
+            DioAstStatement::FunctionDefine(v)
+        }),
+        map(ModuleParser::parse_use, DioAstStatement::ModuleUse),
+        map(ModuleParser::parse_file_use, |v| {
+            DioAstStatement::FileUse(v)
+        }),
+    ))(message)
+}
@@ -0,0 +1,377 @@
+use crate::{
+    ast::{
+        AssignScope, ConditionalStatement, DioAstStatement, FunctionCall, FunctionDefine, FunctionName,
+        LoopExecuteType, LoopStatement, Span, Statement,
+    },
+    element::{AstElement, AstElementContentType},
+    parser::{CalcExpr, LinkExprPart},
+    symbol::Symbol,
+    types::{AstValue, LocalVariable},
+};
+
+// rewrite references to parameters and `let` variables of functions into (depth, slot) pairs,
+// so runtime can load them without scope name lookup. runtime pushes a slot for every `let`
+// inside a function, in the order they are declared in each block. loop variables, top-level
+// variables and captured variables keep dynamic lookup by name.
+// it also collects free variables of functions, runtime captures them as closure.
+//
+// statement and element spans are turned into offsets here, `end` is the offset where the
+// parsed code ends.
+pub(crate) struct Resolver {
+    end: usize,
+    // resolving a function, top-level code is not resolved.
+    function: bool,
+    // block scopes entered from the function scope.
+    depth: usize,
+    // names bound in current block and its parents, latest last: parameters and `let`
+    // variables with (depth of block, slot), other names without.
+    bindings: Vec<(Symbol, Option<(usize, usize)>)>,
+    // next slot of every open block, the function scope first.
+    slots: Vec<usize>,
+    // names used by the function but not bound in it.
+    free: Vec<Symbol>,
+}
+
+impl Resolver {
+    pub(crate) fn resolve_statements(stats: &mut [Statement], end: usize) {
+        Resolver {
+            end,
+            function: false,
+            depth: 0,
+            bindings: vec![],
+            slots: vec![0],
+            free: vec![],
+        }
+        .statements(stats);
+    }
+
+    pub(crate) fn resolve_expr(expr: &mut CalcExpr, end: usize) {
+        Resolver {
+            end,
+            function: false,
+            depth: 0,
+            bindings: vec![],
+            slots: vec![0],
+            free: vec![],
+        }
+        .expr(expr);
+    }
+
+    pub(crate) fn resolve_element(element: &mut AstElement, end: usize) {
+        Resolver {
+            end,
+            function: false,
+            depth: 0,
+            bindings: vec![],
+            slots: vec![0],
+            free: vec![],
+        }
+        .element(element);
+    }
+
+    pub(crate) fn resolve_function(func: &mut FunctionDefine, end: usize) {
+        let params = func.params.names();
+        let mut resolver = Resolver {
+            end,
+            function: true,
+            depth: 0,
+            slots: vec![params.len()],
+            bindings: params
+                .into_iter()
+                .enumerate()
+                .map(|(slot, name)| (name, Some((0, slot))))
+                .collect(),
+            free: vec![],
+        };
+        resolver.statements(&mut func.inner);
+        func.captures = resolver.free;
+    }
+
+    // nested function captures its free variables from this function, so they are used here.
+    fn function(&mut self, func: &mut FunctionDefine) {
+        Self::resolve_function(func, self.end);
+        for name in func.captures.iter() {
+            self.reference(name);
+        }
+    }
+
+    fn block(&mut self, stats: &mut [Statement]) {
+        let bindings = self.bindings.len();
+        self.depth += 1;
+        self.slots.push(0);
+        self.statements(stats);
+        self.slots.pop();
+        self.depth -= 1;
+        self.bindings.truncate(bindings);
+    }
+
+    // name bound without slot, it hides outer bindings.
+    fn shadow(&mut self, name: &Symbol) {
+        self.bindings.push((name.clone(), None));
+    }
+
+    // `let` variable, it takes the next slot of current block.
+    fn declare(&mut self, name: &Symbol) {
+        let slot = self.slots[self.depth];
+        self.slots[self.depth] += 1;
+        self.bindings.push((name.clone(), Some((self.depth, slot))));
+    }
+
+    fn reference(&mut self, name: &Symbol) {
+        if self.function {
+            let bound = self.bindings.iter().any(|(v, _)| v == name);
+            if !bound && !self.free.contains(name) {
+                self.free.push(name.clone());
+            }
+        }
+    }
+
+    fn capture(&mut self, name: &Symbol) {
+        if self.function && !self.free.contains(name) {
+            self.free.push(name.clone());
+        }
+    }
+
+    fn span(&self, span: Span) -> Span {
+        Span {
+            start: self.end.saturating_sub(span.start),
+            end: self.end.saturating_sub(span.end),
+        }
+    }
+
+    fn statements(&mut self, stats: &mut [Statement]) {
+        for stat in stats {
+            stat.span = self.span(stat.span);
+            for comment in stat.comments.iter_mut() {
+                comment.span = self.span(comment.span);
+            }
+            self.statement(&mut stat.kind);
+        }
+    }
+
+    fn statement(&mut self, stat: &mut DioAstStatement) {
+        match stat {
+            DioAstStatement::VariableAss(var) => {
+                for index in var.index.iter_mut() {
+                    self.expr(index);
+                }
+                self.expr(&mut var.expr);
+                match var.scope {
+                    _ if var.new && var.index.is_empty() => self.declare(&var.name),
+                    _ if var.new => self.shadow(&var.name),
+                    None => self.reference(&var.name),
+                    // captured from where the function is defined, even when it is shadowed.
+                    Some(AssignScope::Outer) => self.capture(&var.name),
+                    Some(AssignScope::Global) => {}
+                }
+            }
+            DioAstStatement::TupleAss(var) => {
+                self.expr(&mut var.expr);
+                if var.new {
+                    self.declare(&var.names.0);
+                    self.declare(&var.names.1);
+                } else {
+                    self.reference(&var.names.0);
+                    self.reference(&var.names.1);
+                }
+            }
+            DioAstStatement::ReturnValue(expr)
+            | DioAstStatement::Defer(expr)
+            | DioAstStatement::MethodCall(expr) => self.expr(expr),
+            DioAstStatement::Break(value) => {
+                if let Some(expr) = value {
+                    self.expr(expr);
+                }
+            }
+            DioAstStatement::IfStatement(cond) => self.condition(cond),
+            DioAstStatement::LoopStatement(data) => self.loop_statement(data),
+            DioAstStatement::FunctionCall(call) => self.call(call),
+            DioAstStatement::FunctionDefine(define) => {
+                for decorator in define.decorators.iter() {
+                    if let FunctionName::Single(name) = decorator {
+                        self.reference(name);
+                    }
+                }
+                self.function(define);
+                if let Some(name) = &define.name {
+                    self.shadow(&Symbol::new(name));
+                }
+            }
+            DioAstStatement::Assert(assert) => {
+                self.expr(&mut assert.expr);
+                if let Some(message) = &mut assert.message {
+                    self.expr(message);
+                }
+            }
+            DioAstStatement::ObjectDefine(define) => {
+                // fields are evaluated when object constructing, not in this function.
+                for field in define.fields.iter_mut() {
+                    Self::resolve_expr(&mut field.expr, self.end);
+                }
+                for method in define.methods.iter_mut() {
+                    Self::resolve_function(method, self.end);
+                }
+            }
+            DioAstStatement::LineComment(_)
+            | DioAstStatement::EnumDefine(_)
+            | DioAstStatement::ModuleUse(_)
+            | DioAstStatement::FileUse(_) => {}
+        }
+    }
+
+    fn condition(&mut self, cond: &mut ConditionalStatement) {
+        self.expr(&mut cond.condition);
+        self.block(&mut cond.inner);
+        if let Some(otherwise) = &mut cond.otherwise {
+            self.block(otherwise);
+        }
+    }
+
+    fn loop_statement(&mut self, data: &mut LoopStatement) {
+        match &mut data.execute_type {
+            LoopExecuteType::Conditional(cond) => self.expr(cond),
+            LoopExecuteType::Iter { iter, .. } => self.value(iter),
+        }
+        let bindings = self.bindings.len();
+        if let LoopExecuteType::Iter { var, index, .. } = &data.execute_type {
+            self.shadow(var);
+            if let Some(index) = index {
+                self.shadow(index);
+            }
+        }
+        self.block(&mut data.inner);
+        self.bindings.truncate(bindings);
+    }
+
+    fn call(&mut self, call: &mut FunctionCall) {
+        if let FunctionName::Single(name) = &call.name {
+            self.reference(name);
+        }
+        for arg in call.arguments.iter_mut() {
+            self.value(arg);
+        }
+    }
+
+    fn expr(&mut self, expr: &mut CalcExpr) {
+        match expr {
+            CalcExpr::Value(v) => self.value(v),
+            CalcExpr::LinkExpr(link) => {
+                self.expr(&mut link.this);
+                for part in link.list.iter_mut() {
+                    if let LinkExprPart::FunctionCall(call) = part {
+                        self.call(call);
+                    }
+                }
+            }
+            CalcExpr::Pipe(l, call) => {
+                self.expr(l);
+                self.call(call);
+            }
+            CalcExpr::TypeOf(v) | CalcExpr::Is(v, _) => self.expr(v),
+            CalcExpr::Call(f, arguments) => {
+                self.expr(f);
+                for arg in arguments.iter_mut() {
+                    self.value(arg);
+                }
+            }
+            CalcExpr::Add(l, r)
+            | CalcExpr::Sub(l, r)
+            | CalcExpr::Mul(l, r)
+            | CalcExpr::Div(l, r)
+            | CalcExpr::Mod(l, r)
+            | CalcExpr::Pow(l, r)
+            | CalcExpr::BitAnd(l, r)
+            | CalcExpr::BitOr(l, r)
+            | CalcExpr::BitXor(l, r)
+            | CalcExpr::Shl(l, r)
+            | CalcExpr::Shr(l, r)
+            | CalcExpr::Eq(l, r)
+            | CalcExpr::Ne(l, r)
+            | CalcExpr::Gt(l, r)
+            | CalcExpr::Lt(l, r)
+            | CalcExpr::Ge(l, r)
+            | CalcExpr::Le(l, r)
+            | CalcExpr::And(l, r)
+            | CalcExpr::Or(l, r)
+            | CalcExpr::Coalesce(l, r)
+            | CalcExpr::Tuple(l, r) => {
+                self.expr(l);
+                self.expr(r);
+            }
+        }
+    }
+
+    fn value(&mut self, value: &mut AstValue) {
+        match value {
+            AstValue::Variable(name) => {
+                let binding = self
+                    .bindings
+                    .iter()
+                    .rev()
+                    .find(|(v, _)| v == name)
+                    .and_then(|(_, slot)| *slot)
+                    .filter(|_| self.function);
+                if let Some((depth, slot)) = binding {
+                    *value = AstValue::Local(LocalVariable {
+                        name: name.clone(),
+                        depth: self.depth - depth,
+                        slot,
+                    });
+                } else {
+                    self.reference(name);
+                }
+            }
+            AstValue::VariableIndex((name, index)) => {
+                self.reference(name);
+                self.expr(index);
+            }
+            AstValue::VariableSlice((name, slice)) => {
+                self.reference(name);
+                let parts = [&mut slice.start, &mut slice.end, &mut slice.step];
+                for v in parts.into_iter().flatten() {
+                    self.expr(v);
+                }
+            }
+            AstValue::List(list) => {
+                for v in list.iter_mut() {
+                    self.value(v);
+                }
+            }
+            AstValue::Dict(dict) => {
+                for v in dict.values_mut() {
+                    self.value(v);
+                }
+            }
+            AstValue::Tuple((a, b)) => {
+                self.value(a);
+                self.value(b);
+            }
+            AstValue::Element(element) => self.element(element),
+            AstValue::FunctionCaller(call) => self.call(call),
+            AstValue::FunctionDefine(define) => self.function(define),
+            AstValue::Loop(data) => self.loop_statement(data),
+            AstValue::None
+            | AstValue::String(_)
+            | AstValue::Number(_)
+            | AstValue::Boolean(_)
+            | AstValue::Local(_) => {}
+        }
+    }
+
+    fn element(&mut self, element: &mut AstElement) {
+        element.span = self.span(element.span);
+        for v in element.attributes.values_mut() {
+            self.value(v);
+        }
+        for content in element.content.iter_mut() {
+            match content {
+                AstElementContentType::Children(e) => self.element(e),
+                AstElementContentType::Condition(cond) => self.condition(cond),
+                AstElementContentType::Loop(data) => self.loop_statement(data),
+                AstElementContentType::InlineExpr(expr) => self.expr(expr),
+                AstElementContentType::Block(stats) => self.block(stats),
+                AstElementContentType::Content(_) | AstElementContentType::Comment(_) => {}
+            }
+        }
+    }
+}
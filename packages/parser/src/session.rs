@@ -0,0 +1,158 @@
+use std::ops::Range;
+
+use crate::{
+    ast::{unmatched_statement, DioscriptAst, Statement},
+    error::ParseError,
+    keyword::{check_statements, check_types, object_names},
+    parser::parse_statement,
+    resolver::Resolver,
+};
+
+// top-level statement with its byte range in source.
+pub type SpannedStatement = (Range<usize>, Statement);
+
+// keep parsed top-level statements with their byte ranges, so editors can re-parse
+// only the statements touched by an edit instead of the whole document.
+#[derive(Debug, Clone, Default)]
+pub struct ParseSession {
+    source: String,
+    stats: Vec<SpannedStatement>,
+    // offset of the first content which can not be parsed.
+    error: Option<usize>,
+}
+
+impl ParseSession {
+    pub fn new(source: &str) -> Self {
+        let mut session = Self {
+            source: source.to_string(),
+            ..Default::default()
+        };
+        let (stats, error, _) = session.parse_from(0, None);
+        session.stats = stats;
+        session.error = error;
+        session
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn statements(&self) -> &[SpannedStatement] {
+        &self.stats
+    }
+
+    pub fn error_offset(&self) -> Option<usize> {
+        self.error
+    }
+
+    // replace `range` (byte offsets of current source) by `text` and re-parse affected
+    // statements, returns indexes of statements which were parsed again.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Range<usize> {
+        self.source.replace_range(range.clone(), text);
+        let edit_end = range.start + text.len();
+        let shift = |v: usize| v + text.len() - range.len();
+
+        // the statement before the edit is parsed again too, an edit right after it
+        // may extend it (e.g. appending `else` to `if`).
+        let before = self
+            .stats
+            .iter()
+            .take_while(|(r, _)| r.end < range.start)
+            .count();
+        let mut first = before.saturating_sub(1);
+        // doc comments before it may become docs of a function define.
+        while first > 0 && self.source[self.stats[first - 1].0.clone()].starts_with("///") {
+            first -= 1;
+        }
+        // unchanged statements after the edit, parsing can stop once it reaches one of them.
+        let reuse = self
+            .stats
+            .iter()
+            .position(|(r, _)| r.start >= range.end)
+            .unwrap_or(self.stats.len());
+        let resync: Vec<usize> = self.stats[reuse..]
+            .iter()
+            .map(|(r, _)| shift(r.start))
+            .collect();
+
+        let start = if before > 0 {
+            self.stats[first].0.start
+        } else {
+            0
+        };
+        let (parsed, error, resumed) = self.parse_from(start, Some((edit_end, &resync)));
+        let count = parsed.len();
+
+        let mut tail = match resumed {
+            Some(index) => {
+                self.error = self.error.map(shift);
+                self.stats.split_off(reuse + index)
+            }
+            None => {
+                self.error = error;
+                vec![]
+            }
+        };
+        for (r, _) in tail.iter_mut() {
+            *r = shift(r.start)..shift(r.end);
+        }
+        self.stats.truncate(first);
+        self.stats.extend(parsed);
+        self.stats.extend(tail);
+        first..first + count
+    }
+
+    // build the ast from current statements, fails if part of source can not be parsed.
+    pub fn ast(&self) -> Result<DioscriptAst, ParseError> {
+        if let Some(offset) = self.error {
+            let rest = &self.source[offset..];
+            return Err(unmatched_statement(&self.source, rest, 0, (1, 1)));
+        }
+        let mut stats: Vec<Statement> = self.stats.iter().map(|(_, s)| s.clone()).collect();
+        let objects = object_names(&stats);
+        for (stat, (range, _)) in stats.iter_mut().zip(self.stats.iter()) {
+            // span is still the length of source left when statement was parsed, the source
+            // ended at this offset then. edits before the statement shift both of them.
+            let end = range.start + stat.span.start;
+            let stat_slice = std::slice::from_ref(stat);
+            check_statements(stat_slice)
+                .and_then(|_| check_types(stat_slice, &objects))
+                .map_err(|e| e.locate(&self.source[..end], 0, (1, 1)))?;
+            Resolver::resolve_statements(std::slice::from_mut(stat), end);
+        }
+        Ok(DioscriptAst { stats })
+    }
+
+    // parse statements from `start`; with `resync`, stop once past `edit_end` and at the
+    // start of an unchanged statement, returning its index in `resync`.
+    fn parse_from(
+        &self,
+        start: usize,
+        resync: Option<(usize, &[usize])>,
+    ) -> (Vec<SpannedStatement>, Option<usize>, Option<usize>) {
+        let mut stats = vec![];
+        let mut pos = start;
+        loop {
+            let rest = self.source[pos..].trim_start();
+            pos = self.source.len() - rest.len();
+            if let Some((edit_end, starts)) = resync {
+                if pos >= edit_end {
+                    if let Ok(index) = starts.binary_search(&pos) {
+                        return (stats, None, Some(index));
+                    }
+                }
+            }
+            if rest.is_empty() {
+                return (stats, None, None);
+            }
+            match parse_statement(rest) {
+                Ok((next, stat)) => {
+                    let end = self.source.len() - next.len();
+                    stats.push((pos..end, stat));
+                    pos = end;
+                }
+                Err(_) => return (stats, Some(pos), None),
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashSet, io::Read};
+
+use nom::Finish;
+
+use crate::{
+    ast::{unmatched_statement, DioAstStatement, DioscriptAst, Statement},
+    error::{advance_position, ParseError},
+    keyword::{check_statements, check_types, object_names},
+    parser::parse_statement,
+    resolver::Resolver,
+};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+// feed source code chunk by chunk, complete statements are parsed as soon as they arrive
+// and dropped from the buffer, only the unfinished tail is kept in memory.
+//
+// the last parsed statement stays pending until another statement follows it, because a
+// later chunk may still extend it (e.g. an `else` block after `if`).
+#[derive(Debug)]
+pub struct StreamParser {
+    buffer: String,
+    // statements with the offset where the buffer ended when they were parsed, the resolver
+    // needs it to locate them in whole source.
+    stats: Vec<(usize, Statement)>,
+    // bytes dropped from the buffer and line, column after them, used to report error
+    // position in whole source.
+    consumed: usize,
+    position: (usize, usize),
+    // first error of checking committed statements, `finish` returns it.
+    error: Option<ParseError>,
+    // annotations which name neither a builtin type nor an object defined so far, with their
+    // error. an object defined later makes them valid, so they are checked at `finish`.
+    unknown_types: Vec<(String, ParseError)>,
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        Self {
+            buffer: String::new(),
+            stats: vec![],
+            consumed: 0,
+            position: (1, 1),
+            error: None,
+            unknown_types: vec![],
+        }
+    }
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // append a chunk and parse every statement which is known to be complete,
+    // returns the number of newly committed statements.
+    pub fn feed(&mut self, chunk: &str) -> usize {
+        self.buffer.push_str(chunk);
+        // every statement ends with `;`, `}` or a line break (comments).
+        if !chunk.contains([';', '}', '\n']) {
+            return 0;
+        }
+        let before = self.stats.len();
+        let mut start = self.buffer.trim_start();
+        let mut rest = start;
+        let mut last = None;
+        let end = self.consumed + self.buffer.len();
+        while let Ok((next, stat)) = parse_statement(rest) {
+            // doc comments may belong to a function define which has not arrived yet.
+            if rest.starts_with("///") && matches!(stat.kind, DioAstStatement::LineComment(_)) {
+                break;
+            }
+            // the previous statement is followed by another one, so it is complete.
+            if let Some(prev) = last.replace(stat) {
+                self.stats.push((end, prev));
+                start = rest;
+            }
+            rest = next.trim_start();
+        }
+        let consumed = self.buffer.len() - start.len();
+        self.check(before);
+        self.position = advance_position(self.position, &self.buffer[..consumed]);
+        self.buffer.drain(..consumed);
+        self.consumed += consumed;
+        self.stats.len() - before
+    }
+
+    // code which has not been committed as a statement yet.
+    pub fn pending(&self) -> &str {
+        &self.buffer
+    }
+
+    // check whether the pending code forms complete statements, useful for REPL to decide
+    // if it should wait for more lines.
+    pub fn is_complete(&self) -> bool {
+        let mut rest = self.buffer.trim_start();
+        while !rest.is_empty() {
+            match parse_statement(rest) {
+                Ok((next, _)) => rest = next.trim_start(),
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+
+    // parse everything left in the buffer and build the ast.
+    pub fn finish(mut self) -> Result<DioscriptAst, ParseError> {
+        let before = self.stats.len();
+        let mut rest = self.buffer.trim_start();
+        let end = self.consumed + self.buffer.len();
+        while !rest.is_empty() {
+            match parse_statement(rest).finish() {
+                Ok((next, stat)) => {
+                    self.stats.push((end, stat));
+                    rest = next.trim_start();
+                }
+                Err(_) => {
+                    if let Some(e) = self.error.take() {
+                        return Err(e);
+                    }
+                    let error =
+                        unmatched_statement(&self.buffer, rest, self.consumed, self.position);
+                    return Err(error);
+                }
+            }
+        }
+        self.check(before);
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let (ends, mut stats): (Vec<usize>, Vec<Statement>) = self.stats.into_iter().unzip();
+        let objects = object_names(&stats);
+        if let Some((_, e)) = self
+            .unknown_types
+            .into_iter()
+            .find(|(name, _)| !objects.contains(name))
+        {
+            return Err(e);
+        }
+        // top-level statements do not share resolver state, so they are resolved one by one.
+        for (stat, end) in stats.iter_mut().zip(ends) {
+            Resolver::resolve_statements(std::slice::from_mut(stat), end);
+        }
+        Ok(DioscriptAst { stats })
+    }
+
+    // check statements committed from index `from`, before their code leaves the buffer.
+    fn check(&mut self, from: usize) {
+        for (_, stat) in self.stats[from..].iter() {
+            if self.error.is_some() {
+                return;
+            }
+            if let Err(e) = check_statements(std::slice::from_ref(stat)) {
+                self.error = Some(e.locate(&self.buffer, self.consumed, self.position));
+            }
+            let mut names = HashSet::new();
+            while let Err(e) = check_types(std::slice::from_ref(stat), &names) {
+                let name = match &e {
+                    ParseError::UnknownType { name, .. } => name.clone(),
+                    _ => break,
+                };
+                names.insert(name.clone());
+                let e = e.locate(&self.buffer, self.consumed, self.position);
+                self.unknown_types.push((name, e));
+            }
+        }
+    }
+}
+
+// read source code from reader in chunks, see `StreamParser`.
+pub(crate) fn parse_reader(mut reader: impl Read) -> Result<DioscriptAst, ParseError> {
+    let mut parser = StreamParser::new();
+    let mut chunk = [0u8; CHUNK_SIZE];
+    // bytes of an utf-8 char which was split by the chunk boundary.
+    let mut partial: Vec<u8> = vec![];
+    loop {
+        let size = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        partial.extend_from_slice(&chunk[..size]);
+        let valid = match std::str::from_utf8(&partial) {
+            Ok(_) => partial.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e).into());
+            }
+        };
+        // `valid` always ends at a char boundary.
+        parser.feed(std::str::from_utf8(&partial[..valid]).unwrap_or_default());
+        partial.drain(..valid);
+    }
+    if !partial.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+        .into());
+    }
+    parser.finish()
+}
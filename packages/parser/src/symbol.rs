@@ -0,0 +1,163 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+// names interned by the process, shared by all threads so the ast can be parsed on one thread
+// and executed on another.
+static SYMBOLS: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+// names which are not used by any symbol anymore are dropped when the set grows past `limit`,
+// so long running hosts do not keep every name they have ever seen.
+struct Interner {
+    names: HashSet<Arc<str>>,
+    limit: usize,
+}
+
+const MIN_LIMIT: usize = 1024;
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(v) = self.names.get(name) {
+            return v.clone();
+        }
+        if self.names.len() >= self.limit {
+            // a count of one is the set itself: symbols are only cloned from other symbols or
+            // from the set under this lock, so the name cannot come back while it is removed.
+            self.names.retain(|v| Arc::strong_count(v) > 1);
+            self.limit = (self.names.len() * 2).max(MIN_LIMIT);
+        }
+        let v: Arc<str> = Arc::from(name);
+        self.names.insert(v.clone());
+        v
+    }
+}
+
+// interned identifier: equal names share one allocation, so symbols are cloned without
+// allocation, and compared and hashed by address instead of text. symbols are `Send` and
+// `Sync`, interning takes a global lock.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    pub fn new(name: &str) -> Self {
+        let symbols = SYMBOLS.get_or_init(|| {
+            Mutex::new(Interner {
+                names: HashSet::new(),
+                limit: MIN_LIMIT,
+            })
+        });
+        // the set is still consistent when another thread panicked while holding the lock.
+        let mut symbols = symbols.lock().unwrap_or_else(|e| e.into_inner());
+        Symbol(symbols.intern(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const u8).hash(state);
+    }
+}
+
+// sorted by text, addresses depend on the order names are interned.
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value: &str) -> Self {
+        Symbol::new(value)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(value: String) -> Self {
+        Symbol::new(&value)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(value: &String) -> Self {
+        Symbol::new(value)
+    }
+}
+
+impl From<Cow<'_, str>> for Symbol {
+    fn from(value: Cow<'_, str>) -> Self {
+        Symbol::new(&value)
+    }
+}
+
+// names joined by separator, e.g. `std::fmt` of a module path.
+pub fn join(symbols: &[Symbol], separator: &str) -> String {
+    let names: Vec<&str> = symbols.iter().map(|v| v.as_str()).collect();
+    names.join(separator)
+}
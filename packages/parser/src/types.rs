@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
 use crate::{
-    ast::{FunctionCall, FunctionDefine},
+    ast::{FunctionCall, FunctionDefine, LoopStatement},
     element::AstElement,
+    parser::CalcExpr,
+    symbol::Symbol,
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,10 +17,15 @@ pub enum AstValue {
     Dict(HashMap<String, AstValue>),
     Tuple((Box<AstValue>, Box<AstValue>)),
     Element(AstElement),
-    Variable(String),
-    VariableIndex((String, Box<AstValue>)),
+    Variable(Symbol),
+    VariableIndex((Symbol, Box<CalcExpr>)),
+    VariableSlice((Symbol, SliceIndex)),
+    // function parameter or `let` variable resolved by `Resolver`.
+    Local(LocalVariable),
     FunctionCaller(FunctionCall),
     FunctionDefine(FunctionDefine),
+    // `for`/`while` loop used as expression, result is value of `break`.
+    Loop(Box<LoopStatement>),
 }
 
 impl AstValue {
@@ -34,8 +41,11 @@ impl AstValue {
             AstValue::Element(_) => "element",
             AstValue::Variable(_) => "variable",
             AstValue::VariableIndex(_) => "variable[index]",
+            AstValue::VariableSlice(_) => "variable[slice]",
+            AstValue::Local(_) => "variable",
             AstValue::FunctionCaller(_) => "call[func]",
             AstValue::FunctionDefine(_) => "def[func]",
+            AstValue::Loop(_) => "loop",
         }
         .to_string()
     }
@@ -105,10 +115,26 @@ impl AstValue {
     }
 
     pub fn as_variable(&self) -> Option<String> {
-        if let Self::Variable(s) = self {
-            Some(s.to_string())
-        } else {
-            None
+        match self {
+            Self::Variable(s) => Some(s.to_string()),
+            Self::Local(v) => Some(v.name.to_string()),
+            _ => None,
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceIndex {
+    pub start: Option<Box<CalcExpr>>,
+    pub end: Option<Box<CalcExpr>>,
+    pub step: Option<Box<CalcExpr>>,
+}
+
+// `depth` is the number of block scopes between the reference and the scope declaring the
+// variable, `slot` is its position in the slots of that scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVariable {
+    pub name: Symbol,
+    pub depth: usize,
+    pub slot: usize,
+}
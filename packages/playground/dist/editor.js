@@ -5,5 +5,11 @@ window.editor = monaco.editor.create(document.querySelector("#monaco"), {
   fontSize: 13,
 });
 
-
-
+// syntax errors of script, see `dsx::markers`.
+window.setMarkers = (markers) => {
+  monaco.editor.setModelMarkers(
+    window.editor.getModel(),
+    "dioscript",
+    markers.map((marker) => ({ ...marker, severity: monaco.MarkerSeverity.Error })),
+  );
+};
@@ -1,11 +1,28 @@
 use dioxus::prelude::*;
-use dsx::{AstView, View};
+use dsx::{AstView, ExecutionLimits, InterruptHandle, SourceView, View, MAX_FUEL};
 use indoc::indoc;
 
 fn main() {
     dioxus::launch(App);
 }
 
+// script runs on the page thread, so a run is bounded by fuel, and values must not take all
+// memory of the tab. clock is not available in browser wasm, so timeout is not used.
+const LIMITS: ExecutionLimits = ExecutionLimits {
+    fuel: Some(1_000_000),
+    timeout: None,
+    max_data: Some(1_000_000),
+    max_size: Some(10_000_000),
+    max_depth: Some(ExecutionLimits::DEFAULT_MAX_DEPTH),
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputTab {
+    Result,
+    Ast,
+    Html,
+}
+
 #[allow(non_snake_case)]
 pub fn App() -> Element {
 
@@ -37,7 +54,40 @@ pub fn App() -> Element {
         }
     });
    
-    let mut display_result = use_signal(|| true);
+    let mut tab = use_signal(|| OutputTab::Result);
+    // script runs on the page thread between events, so a stopped handle ends it, and it keeps
+    // later edits from running until `Run` is clicked, e.g. while an endless loop is being fixed.
+    let mut interrupt = use_signal(InterruptHandle::new);
+    // retry of a script which used up its fuel gets more, up to `MAX_FUEL`, edits start with
+    // the default again.
+    let mut limits = use_signal(|| LIMITS);
+    use_effect(move || {
+        editor_content.read();
+        limits.set(LIMITS);
+    });
+    // syntax errors and the statement which failed at runtime are underlined in the editor. it
+    // runs at once, so it keeps the default fuel of retries.
+    use_effect(move || {
+        let code = editor_content.read();
+        let markers: Vec<serde_json::Value> = dsx::markers(&code, Some(LIMITS))
+            .into_iter()
+            .map(|marker| {
+                serde_json::json!({
+                    "message": marker.message,
+                    "startLineNumber": marker.start_line,
+                    "startColumn": marker.start_column,
+                    "endLineNumber": marker.end_line,
+                    "endColumn": marker.end_column,
+                })
+            })
+            .collect();
+        let js = eval("let markers = await dioxus.recv(); window.setMarkers?.(markers);");
+        let _ = js.send(markers.into());
+    });
+    let retry = move |_: ()| {
+        let fuel = limits.read().fuel.map(|v| (v * 10).min(MAX_FUEL));
+        limits.set(ExecutionLimits { fuel, ..LIMITS });
+    };
 
     rsx! {
         script {
@@ -55,14 +105,38 @@ pub fn App() -> Element {
                     class: "basis-1/2",
                     button { 
                         class: "bg-cyan-500 hover:bg-cyan-700 text-white font-semibold text-sm py-2 px-3 rounded",
-                        onclick: move |_| { display_result.set(true); },
+                        onclick: move |_| { tab.set(OutputTab::Result); },
                         "Result"
                     }
                     button {
                         class: "bg-emerald-500 hover:bg-emerald-700 text-white font-semibold text-sm ml-2 py-2 px-3 rounded",
-                        onclick: move |_| { display_result.set(false); },
+                        onclick: move |_| { tab.set(OutputTab::Ast); },
                         "AST Tree"   
                     }
+                    button {
+                        class: "bg-amber-500 hover:bg-amber-700 text-white font-semibold text-sm ml-2 py-2 px-3 rounded",
+                        onclick: move |_| { tab.set(OutputTab::Html); },
+                        "HTML"
+                    }
+                    if interrupt.read().is_interrupted() {
+                        button {
+                            class: "bg-indigo-500 hover:bg-indigo-700 text-white font-semibold text-sm ml-2 py-2 px-3 rounded",
+                            onclick: move |_| { interrupt.set(InterruptHandle::new()); },
+                            "Run"
+                        }
+                    } else {
+                        button {
+                            class: "bg-rose-500 hover:bg-rose-700 text-white font-semibold text-sm ml-2 py-2 px-3 rounded",
+                            onclick: move |_| {
+                                interrupt.read().interrupt();
+                                // a new handle changes view props, so it is rendered again.
+                                let handle = InterruptHandle::new();
+                                handle.interrupt();
+                                interrupt.set(handle);
+                            },
+                            "Stop execution"
+                        }
+                    }
                 }
             }
             div {
@@ -80,12 +154,28 @@ pub fn App() -> Element {
                         class: "w-full h-[700px] border border-gray-400",
                         div {
                             class: "mt-1 px-4 py-4",    
-                            if *display_result.read() {
-                                View {
-                                  code: editor_content.to_string(),  
+                            {
+                                match *tab.read() {
+                                    OutputTab::Result => rsx! {
+                                        View {
+                                            code: editor_content.to_string(),
+                                            interrupt: interrupt.read().clone(),
+                                            limits: *limits.read(),
+                                            markers: true,
+                                            on_retry: retry,
+                                        }
+                                    },
+                                    OutputTab::Ast => rsx! {
+                                        AstView { code: editor_content.to_string() }
+                                    },
+                                    OutputTab::Html => rsx! {
+                                        SourceView {
+                                            code: editor_content.to_string(),
+                                            interrupt: interrupt.read().clone(),
+                                            limits: *limits.read(),
+                                        }
+                                    },
                                 }
-                            } else {
-                                AstView { code: editor_content.to_string() }
                             }
                         }
                     }
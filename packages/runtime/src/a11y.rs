@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use dioscript_parser::ast::Span;
+
+use crate::types::{Element, ElementContentType, Value};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum A11yRule {
+    #[error("image without `alt` text.")]
+    MissingAlt,
+
+    #[error("button without text or label.")]
+    EmptyButton,
+
+    #[error("id `{id}` is used by more than one element.")]
+    DuplicateId { id: String },
+
+    #[error("heading `h{level}` skips level after `h{previous}`.")]
+    HeadingSkip { level: usize, previous: usize },
+}
+
+// accessibility problem of a rendered element found by `check`.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("<{element}>: {rule}")]
+pub struct A11yIssue {
+    pub element: String,
+    pub rule: A11yRule,
+    // source range of the script element, `None` for elements built by host.
+    pub span: Option<Span>,
+}
+
+// check elements of rendered result in document order: elements of lists one after another,
+// entries of dicts (template slots) in key order.
+pub fn check(value: &Value) -> Vec<A11yIssue> {
+    let mut checker = Checker::default();
+    checker.value(value);
+    checker.issues
+}
+
+#[derive(Default)]
+struct Checker {
+    issues: Vec<A11yIssue>,
+    ids: HashSet<String>,
+    // level of the last heading.
+    heading: Option<usize>,
+}
+
+impl Checker {
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Element(e) => self.element(e),
+            Value::List(list) => {
+                for v in list.iter() {
+                    self.value(v);
+                }
+            }
+            Value::Dict(dict) => {
+                let mut keys: Vec<&String> = dict.keys().collect();
+                keys.sort();
+                for key in keys {
+                    self.value(&dict[key]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn element(&mut self, e: &Element) {
+        let name = e.name.to_lowercase();
+        match name.as_str() {
+            // `alt: ""` marks a decorative image.
+            "img" if attribute(e, "alt").is_none() => self.report(e, A11yRule::MissingAlt),
+            "button" if !has_label(e) => self.report(e, A11yRule::EmptyButton),
+            _ => {}
+        }
+        if let Some(level) = heading_level(&name) {
+            if let Some(previous) = self.heading {
+                if level > previous + 1 {
+                    self.report(e, A11yRule::HeadingSkip { level, previous });
+                }
+            }
+            self.heading = Some(level);
+        }
+        if let Some(id) = attribute(e, "id") {
+            if !self.ids.insert(id.clone()) {
+                self.report(e, A11yRule::DuplicateId { id });
+            }
+        }
+        for sub in e.content.iter() {
+            if let ElementContentType::Children(child) = sub {
+                self.element(child);
+            }
+        }
+    }
+
+    fn report(&mut self, e: &Element, rule: A11yRule) {
+        self.issues.push(A11yIssue {
+            element: e.name.clone(),
+            rule,
+            span: e.span,
+        });
+    }
+}
+
+// value of attribute as it is rendered, `None` if it is not rendered.
+fn attribute(e: &Element, name: &str) -> Option<String> {
+    match e.attributes.get(name)? {
+        Value::String(v) => Some(v.to_string()),
+        Value::Number(v) => Some(v.to_string()),
+        Value::Boolean(true) => Some(String::new()),
+        _ => None,
+    }
+}
+
+fn has_label(e: &Element) -> bool {
+    let labeled = ["aria-label", "aria-labelledby", "title"]
+        .iter()
+        .filter_map(|name| attribute(e, name))
+        .any(|v| !v.trim().is_empty());
+    labeled || has_text(e)
+}
+
+// text content, or an image with `alt` text inside it.
+fn has_text(e: &Element) -> bool {
+    if e.name.eq_ignore_ascii_case("img") {
+        return attribute(e, "alt")
+            .map(|v| !v.trim().is_empty())
+            .unwrap_or(false);
+    }
+    e.content.iter().any(|sub| match sub {
+        ElementContentType::Content(v) => !v.trim().is_empty(),
+        ElementContentType::Children(child) => has_text(child),
+    })
+}
+
+fn heading_level(name: &str) -> Option<usize> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
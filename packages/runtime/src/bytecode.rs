@@ -0,0 +1,669 @@
+use std::{collections::HashMap, rc::Rc};
+
+use dioscript_parser::{
+    ast::{
+        CalculateMark, DioAstStatement, FunctionCall, FunctionDefine, FunctionName,
+        LoopExecuteType, Span, Statement,
+    },
+    parser::CalcExpr,
+    symbol::Symbol,
+    types::{AstValue, LocalVariable},
+};
+
+use crate::{error::RuntimeError, iter_items, type_matches, types::Value, Runtime};
+
+// how script and function bodies are executed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Engine {
+    // walk the syntax tree of every statement.
+    #[default]
+    Interpreter,
+    // lower bodies to bytecode once and run them on a stack machine, statements and
+    // expressions without bytecode form are handed to the interpreter.
+    Bytecode,
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    // start of statement `spans[i]`: limits are checked and errors are located there.
+    Statement(usize),
+    // start of block, an empty block uses fuel too.
+    Check,
+    Const(usize),
+    Load(usize),
+    LoadLocal(usize),
+    Define(usize),
+    Assign(usize),
+    Pop,
+    Binary(CalculateMark),
+    TypeOf,
+    Is(usize),
+    Tuple,
+    List(usize),
+    // values of dict with keys `keys[i]`, in that order.
+    Dict(usize),
+    Index,
+    // function `calls[i]` with arguments on the stack.
+    Call(usize, usize),
+    // parts run by interpreter: expression, value and statement.
+    Eval(usize),
+    Value(usize),
+    Execute(usize),
+    // scope of block which belongs to statement `spans[i]`.
+    EnterScope(usize),
+    LeaveScope,
+    Jump(usize),
+    // `while` condition, loop stops when value is not truthy.
+    JumpIfFalse(usize),
+    // `if` condition, it must be boolean.
+    Branch(usize),
+    IterStart,
+    // bind next item of innermost `for` loop in a new scope, jump to `exit` after the last.
+    IterNext {
+        var: usize,
+        index: Option<usize>,
+        span: usize,
+        exit: usize,
+    },
+    IterEnd,
+    Return,
+    // `return` in nested block: `none` only leaves the block.
+    ReturnIfSome(usize),
+    // `break` outside of loop, caller reports it.
+    Break,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Chunk {
+    ops: Vec<Op>,
+    consts: Vec<Value>,
+    names: Vec<Symbol>,
+    locals: Vec<LocalVariable>,
+    calls: Vec<FunctionName>,
+    keys: Vec<Vec<String>>,
+    spans: Vec<Span>,
+    exprs: Vec<CalcExpr>,
+    values: Vec<AstValue>,
+    statements: Vec<DioAstStatement>,
+}
+
+struct Loop {
+    // open scopes outside of the loop.
+    depth: usize,
+    iter: bool,
+    breaks: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Compiler {
+    chunk: Chunk,
+    // statement spans of open scopes, innermost last.
+    scopes: Vec<usize>,
+    // `return` jumps of open blocks, they go to the end of block.
+    blocks: Vec<Vec<usize>>,
+    loops: Vec<Loop>,
+}
+
+pub(crate) fn compile(statements: &[Statement]) -> Chunk {
+    let mut compiler = Compiler::default();
+    compiler.emit(Op::Check);
+    for stat in statements {
+        compiler.statement(stat);
+    }
+    compiler.chunk
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.ops.push(op);
+        self.chunk.ops.len() - 1
+    }
+
+    // jump of op `at` goes to the next op.
+    fn patch(&mut self, at: usize) {
+        let target = self.chunk.ops.len();
+        match &mut self.chunk.ops[at] {
+            Op::Jump(v)
+            | Op::JumpIfFalse(v)
+            | Op::Branch(v)
+            | Op::ReturnIfSome(v)
+            | Op::IterNext { exit: v, .. } => *v = target,
+            _ => {}
+        }
+    }
+
+    fn constant(&mut self, value: Value) {
+        self.chunk.consts.push(value);
+        self.emit(Op::Const(self.chunk.consts.len() - 1));
+    }
+
+    fn name(&mut self, name: &Symbol) -> usize {
+        match self.chunk.names.iter().position(|v| v == name) {
+            Some(index) => index,
+            None => {
+                self.chunk.names.push(name.clone());
+                self.chunk.names.len() - 1
+            }
+        }
+    }
+
+    fn statement(&mut self, stat: &Statement) {
+        self.chunk.spans.push(stat.span);
+        let span = self.chunk.spans.len() - 1;
+        self.emit(Op::Statement(span));
+        match &stat.kind {
+            DioAstStatement::VariableAss(var)
+                if var.index.is_empty() && var.scope.is_none() && var.annotation.is_none() =>
+            {
+                self.expr(&var.expr);
+                let name = self.name(&var.name);
+                match var.new {
+                    true => self.emit(Op::Define(name)),
+                    false => self.emit(Op::Assign(name)),
+                };
+            }
+            DioAstStatement::ReturnValue(expr) => {
+                self.expr(expr);
+                if self.blocks.is_empty() {
+                    self.emit(Op::Return);
+                } else {
+                    let at = self.emit(Op::ReturnIfSome(0));
+                    self.blocks.last_mut().unwrap().push(at);
+                }
+            }
+            DioAstStatement::IfStatement(cond) => {
+                self.expr(&cond.condition);
+                let branch = self.emit(Op::Branch(0));
+                self.block(&cond.inner, span);
+                match &cond.otherwise {
+                    Some(otherwise) => {
+                        let jump = self.emit(Op::Jump(0));
+                        self.patch(branch);
+                        self.block(otherwise, span);
+                        self.patch(jump);
+                    }
+                    None => self.patch(branch),
+                }
+            }
+            DioAstStatement::LoopStatement(data) => match &data.execute_type {
+                LoopExecuteType::Conditional(cond) => {
+                    let start = self.chunk.ops.len();
+                    self.expr(cond);
+                    let exit = self.emit(Op::JumpIfFalse(0));
+                    self.start_loop(false);
+                    self.block(&data.inner, span);
+                    self.emit(Op::Jump(start));
+                    self.patch(exit);
+                    self.end_loop();
+                }
+                LoopExecuteType::Iter { iter, var, index } => {
+                    self.value(iter);
+                    self.emit(Op::IterStart);
+                    let var = self.name(var);
+                    let index = index.as_ref().map(|v| self.name(v));
+                    let next = self.emit(Op::IterNext {
+                        var,
+                        index,
+                        span,
+                        exit: 0,
+                    });
+                    self.start_loop(true);
+                    self.scoped(&data.inner, span);
+                    self.emit(Op::Jump(next));
+                    self.patch(next);
+                    self.end_loop();
+                }
+            },
+            DioAstStatement::Break(value) => {
+                match value {
+                    Some(expr) => self.expr(expr),
+                    None => self.constant(Value::None),
+                }
+                self.exit_loop();
+            }
+            DioAstStatement::FunctionCall(call) => {
+                self.call(call);
+                self.emit(Op::Pop);
+            }
+            DioAstStatement::MethodCall(expr) => {
+                self.expr(expr);
+                self.emit(Op::Pop);
+            }
+            DioAstStatement::LineComment(_) => {}
+            kind => {
+                self.chunk.statements.push(kind.clone());
+                self.emit(Op::Execute(self.chunk.statements.len() - 1));
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement], span: usize) {
+        self.emit(Op::EnterScope(span));
+        self.scoped(statements, span);
+    }
+
+    // statements of scope which is entered already.
+    fn scoped(&mut self, statements: &[Statement], span: usize) {
+        self.scopes.push(span);
+        self.blocks.push(vec![]);
+        self.emit(Op::Check);
+        for stat in statements {
+            self.statement(stat);
+        }
+        for at in self.blocks.pop().unwrap() {
+            self.patch(at);
+        }
+        self.scopes.pop();
+        self.emit(Op::LeaveScope);
+    }
+
+    fn start_loop(&mut self, iter: bool) {
+        self.loops.push(Loop {
+            depth: self.scopes.len(),
+            iter,
+            breaks: vec![],
+        });
+    }
+
+    fn end_loop(&mut self) {
+        if let Some(data) = self.loops.pop() {
+            for at in data.breaks {
+                self.patch(at);
+            }
+        }
+    }
+
+    // value of `break` is on the stack, it is only kept when there is no loop to leave.
+    fn exit_loop(&mut self) {
+        let depth = match self.loops.last() {
+            Some(data) => data.depth,
+            None => {
+                self.emit(Op::Break);
+                return;
+            }
+        };
+        self.emit(Op::Pop);
+        for _ in depth..self.scopes.len() {
+            self.emit(Op::LeaveScope);
+        }
+        if self.loops.last().map(|v| v.iter).unwrap_or(false) {
+            self.emit(Op::IterEnd);
+        }
+        let at = self.emit(Op::Jump(0));
+        self.loops.last_mut().unwrap().breaks.push(at);
+    }
+
+    fn call(&mut self, call: &FunctionCall) {
+        for arg in call.arguments.iter() {
+            self.value(arg);
+        }
+        self.chunk.calls.push(call.name.clone());
+        self.emit(Op::Call(self.chunk.calls.len() - 1, call.arguments.len()));
+    }
+
+    fn expr(&mut self, expr: &CalcExpr) {
+        let (l, r, mark) = match expr {
+            CalcExpr::Value(v) => return self.value(v),
+            CalcExpr::Add(l, r) => (l, r, CalculateMark::Plus),
+            CalcExpr::Sub(l, r) => (l, r, CalculateMark::Minus),
+            CalcExpr::Mul(l, r) => (l, r, CalculateMark::Multiply),
+            CalcExpr::Div(l, r) => (l, r, CalculateMark::Divide),
+            CalcExpr::Pow(l, r) => (l, r, CalculateMark::Power),
+            CalcExpr::BitAnd(l, r) => (l, r, CalculateMark::BitAnd),
+            CalcExpr::BitOr(l, r) => (l, r, CalculateMark::BitOr),
+            CalcExpr::BitXor(l, r) => (l, r, CalculateMark::BitXor),
+            CalcExpr::Shl(l, r) => (l, r, CalculateMark::Shl),
+            CalcExpr::Shr(l, r) => (l, r, CalculateMark::Shr),
+            CalcExpr::Eq(l, r) => (l, r, CalculateMark::Equal),
+            CalcExpr::Ne(l, r) => (l, r, CalculateMark::NotEqual),
+            CalcExpr::Gt(l, r) => (l, r, CalculateMark::Large),
+            CalcExpr::Lt(l, r) => (l, r, CalculateMark::Small),
+            CalcExpr::Ge(l, r) => (l, r, CalculateMark::LargeOrEqual),
+            CalcExpr::Le(l, r) => (l, r, CalculateMark::SmallOrEqual),
+            CalcExpr::And(l, r) => (l, r, CalculateMark::And),
+            CalcExpr::Or(l, r) => (l, r, CalculateMark::Or),
+            CalcExpr::TypeOf(v) => {
+                self.expr(v);
+                self.emit(Op::TypeOf);
+                return;
+            }
+            CalcExpr::Is(v, name) => {
+                self.expr(v);
+                let name = self.name(&Symbol::new(name));
+                self.emit(Op::Is(name));
+                return;
+            }
+            CalcExpr::Tuple(a, b) => {
+                self.expr(a);
+                self.expr(b);
+                self.emit(Op::Tuple);
+                return;
+            }
+            // link expressions, pipes, calls of values, `??` and `%`.
+            expr => {
+                self.chunk.exprs.push(expr.clone());
+                self.emit(Op::Eval(self.chunk.exprs.len() - 1));
+                return;
+            }
+        };
+        self.expr(l);
+        self.expr(r);
+        self.emit(Op::Binary(mark));
+    }
+
+    fn value(&mut self, value: &AstValue) {
+        match value {
+            AstValue::None => self.constant(Value::None),
+            AstValue::String(v) => self.constant(Value::String(v.as_str().into())),
+            AstValue::Number(v) => self.constant(Value::Number(*v)),
+            AstValue::Boolean(v) => self.constant(Value::Boolean(*v)),
+            AstValue::List(list) => {
+                for v in list.iter() {
+                    self.value(v);
+                }
+                self.emit(Op::List(list.len()));
+            }
+            AstValue::Dict(dict) => {
+                let mut keys = vec![];
+                for (k, v) in dict.iter() {
+                    keys.push(k.clone());
+                    self.value(v);
+                }
+                self.chunk.keys.push(keys);
+                self.emit(Op::Dict(self.chunk.keys.len() - 1));
+            }
+            AstValue::Tuple((a, b)) => {
+                self.value(a);
+                self.value(b);
+                self.emit(Op::Tuple);
+            }
+            AstValue::Variable(name) => {
+                let name = self.name(name);
+                self.emit(Op::Load(name));
+            }
+            AstValue::Local(local) => {
+                self.chunk.locals.push(local.clone());
+                self.emit(Op::LoadLocal(self.chunk.locals.len() - 1));
+            }
+            AstValue::VariableIndex((name, index)) => {
+                let name = self.name(name);
+                self.emit(Op::Load(name));
+                self.expr(index);
+                self.emit(Op::Index);
+            }
+            AstValue::FunctionCaller(call) => self.call(call),
+            // elements, loop expressions, slices and anonymous functions.
+            value => {
+                self.chunk.values.push(value.clone());
+                self.emit(Op::Value(self.chunk.values.len() - 1));
+            }
+        }
+    }
+}
+
+// operand stack and state of running chunk.
+#[derive(Default)]
+struct Frame {
+    stack: Vec<Value>,
+    // items left and index of next item in running `for` loops.
+    iters: Vec<(std::vec::IntoIter<Value>, usize)>,
+    // statement spans of scopes entered by chunk, they are left on `return` and errors.
+    scopes: Vec<usize>,
+    span: Option<Span>,
+}
+
+impl Frame {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("bytecode stack is empty.")
+    }
+
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+}
+
+impl Runtime {
+    // compiled body of script function, it is compiled at first call.
+    pub(crate) fn function_chunk(&mut self, define: &Rc<FunctionDefine>) -> Rc<Chunk> {
+        // weak reference keeps the allocation, so the address is not reused by another define
+        // while it is cached.
+        let key = Rc::as_ptr(define);
+        if let Some((_, chunk)) = self.chunks.get(&key) {
+            return chunk.clone();
+        }
+        // bodies of dropped closures are pruned whenever the cache size doubles.
+        if self.chunks.len() >= 64 && self.chunks.len().is_power_of_two() {
+            self.chunks
+                .retain(|_, (define, _)| define.strong_count() > 0);
+        }
+        let chunk = Rc::new(compile(&define.inner));
+        self.chunks
+            .insert(key, (Rc::downgrade(define), chunk.clone()));
+        chunk
+    }
+
+    pub(crate) fn run_chunk(&mut self, chunk: &Chunk) -> Result<Value, RuntimeError> {
+        let mut frame = Frame::default();
+        let outer = self.statement_span;
+        let result = self.run_ops(chunk, &mut frame);
+        let mut result = match (result, frame.span) {
+            (Err(e), Some(span)) => Err(e.at(span)),
+            (result, _) => result,
+        };
+        while !frame.scopes.is_empty() {
+            result = self.close_scope(chunk, &mut frame, result);
+        }
+        self.statement_span = outer;
+        result
+    }
+
+    // leave innermost scope of chunk, errors of deferred expressions are located at the
+    // statement which opened it.
+    fn close_scope<T>(
+        &mut self,
+        chunk: &Chunk,
+        frame: &mut Frame,
+        result: Result<T, RuntimeError>,
+    ) -> Result<T, RuntimeError> {
+        let span = frame.scopes.pop().map(|v| chunk.spans[v]);
+        let result = self.run_deferred(result);
+        self.leave_scope();
+        frame.span = span;
+        self.statement_span = span;
+        match (result, span) {
+            (Err(e), Some(span)) => Err(e.at(span)),
+            (result, _) => result,
+        }
+    }
+
+    // arms are kept free of `?` like `execute_calculate`: calls of script functions go through
+    // here. ops which never call back into scripts are run by `run_op`.
+    fn run_ops(&mut self, chunk: &Chunk, frame: &mut Frame) -> Result<Value, RuntimeError> {
+        let mut pc = 0;
+        while let Some(op) = chunk.ops.get(pc) {
+            // `return` inside a loop expression leaves the chunk.
+            if let Some(value) = self.return_value.take() {
+                return Ok(value);
+            }
+            pc += 1;
+            let step = match op {
+                Op::Binary(mark) => {
+                    let r = frame.pop();
+                    let l = frame.pop();
+                    self.calc_value(l, r, mark.clone())
+                        .map(|value| frame.stack.push(value))
+                }
+                Op::Call(name, count) => {
+                    let par = frame.pop_many(*count);
+                    self.execute_function_by_name(&chunk.calls[*name], par)
+                        .map(|value| frame.stack.push(value))
+                }
+                Op::Eval(expr) => self
+                    .execute_calculate(&chunk.exprs[*expr])
+                    .map(|value| frame.stack.push(value)),
+                Op::Value(value) => self
+                    .to_value(&chunk.values[*value])
+                    .map(|value| frame.stack.push(value)),
+                Op::Execute(stat) => {
+                    self.execute_statement(&chunk.statements[*stat], &mut Value::None, &mut false)
+                }
+                Op::Jump(target) => {
+                    pc = *target;
+                    Ok(())
+                }
+                Op::JumpIfFalse(target) => {
+                    if !frame.pop().to_boolean_data() {
+                        pc = *target;
+                    }
+                    Ok(())
+                }
+                Op::Branch(target) => match frame.pop() {
+                    Value::Boolean(true) => Ok(()),
+                    Value::Boolean(false) => {
+                        pc = *target;
+                        Ok(())
+                    }
+                    value => Err(RuntimeError::IllegalTypeInConditional {
+                        value_type: value.value_name(),
+                    }),
+                },
+                Op::IterNext {
+                    var,
+                    index,
+                    span,
+                    exit,
+                } => match self.next_item(chunk, frame, *var, *index, *span) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => {
+                        pc = *exit;
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+                Op::Return => return self.deref_value(frame.pop()),
+                Op::ReturnIfSome(target) => match self.deref_value(frame.pop()) {
+                    Ok(value) if !value.as_none() => return Ok(value),
+                    Ok(_) => {
+                        pc = *target;
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+                Op::Break => {
+                    return self.deref_value(frame.pop()).map(|value| {
+                        self.break_value = Some(value);
+                        Value::None
+                    })
+                }
+                op => self.run_op(chunk, frame, op),
+            };
+            step?;
+        }
+        Ok(Value::None)
+    }
+
+    fn run_op(&mut self, chunk: &Chunk, frame: &mut Frame, op: &Op) -> Result<(), RuntimeError> {
+        match op {
+            Op::Statement(span) => {
+                self.check_limits()?;
+                self.report.statements += 1;
+                frame.span = Some(chunk.spans[*span]);
+                self.statement_span = frame.span;
+            }
+            Op::Check => self.check_limits()?,
+            Op::Const(index) => frame.stack.push(chunk.consts[*index].clone()),
+            Op::Load(name) => {
+                let value = self.get_var(&chunk.names[*name])?.1;
+                frame.stack.push(value);
+            }
+            Op::LoadLocal(local) => {
+                let value = self.get_local(&chunk.locals[*local])?;
+                frame.stack.push(value);
+            }
+            Op::Define(name) => {
+                let value = frame.pop();
+                self.define_var(&chunk.names[*name], value)?;
+            }
+            Op::Assign(name) => {
+                let value = frame.pop();
+                self.set_var(&chunk.names[*name], value)?;
+            }
+            Op::Pop => {
+                frame.pop();
+            }
+            Op::TypeOf => {
+                let value = self.deref_value(frame.pop())?;
+                frame.stack.push(Value::String(value.value_name().into()));
+            }
+            Op::Is(name) => {
+                let value = self.deref_value(frame.pop())?;
+                let state = type_matches(&value, &chunk.names[*name]);
+                frame.stack.push(Value::Boolean(state));
+            }
+            Op::Tuple => {
+                let b = frame.pop();
+                let a = frame.pop();
+                frame.stack.push(Value::Tuple((Box::new(a), Box::new(b))));
+            }
+            Op::List(count) => {
+                let items = frame.pop_many(*count);
+                frame.stack.push(Value::List(Rc::new(items)));
+            }
+            Op::Dict(keys) => {
+                let keys = &chunk.keys[*keys];
+                let items = frame.pop_many(keys.len());
+                let dict = keys.iter().cloned().zip(items).collect::<HashMap<_, _>>();
+                frame.stack.push(Value::Dict(Rc::new(dict)));
+            }
+            Op::Index => {
+                let index = frame.pop();
+                let value = frame.pop();
+                let value = self.get_from_index(value, index)?;
+                frame.stack.push(value);
+            }
+            Op::EnterScope(span) => {
+                self.enter_scope(false);
+                frame.scopes.push(*span);
+            }
+            Op::LeaveScope => self.close_scope(chunk, frame, Ok(()))?,
+            Op::IterStart => {
+                let items = iter_items(frame.pop()).unwrap_or_default();
+                frame.iters.push((items.into_iter(), 0));
+            }
+            Op::IterEnd => {
+                frame.iters.pop();
+            }
+            _ => unreachable!("op is run by `run_ops`"),
+        }
+        Ok(())
+    }
+
+    // binds next item of the running loop in a new scope, false when the items are used up.
+    fn next_item(
+        &mut self,
+        chunk: &Chunk,
+        frame: &mut Frame,
+        var: usize,
+        index: Option<usize>,
+        span: usize,
+    ) -> Result<bool, RuntimeError> {
+        let (items, next) = frame.iters.last_mut().expect("no running loop.");
+        let item = match items.next() {
+            Some(item) => item,
+            None => {
+                frame.iters.pop();
+                return Ok(false);
+            }
+        };
+        let position = *next;
+        *next += 1;
+        self.enter_scope(false);
+        frame.scopes.push(span);
+        if let Some(index) = index {
+            let name = &chunk.names[index];
+            self.create_var(name, Value::Number(position as f64))?;
+        }
+        self.create_var(&chunk.names[var], item)?;
+        Ok(true)
+    }
+}
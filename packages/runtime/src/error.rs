@@ -1,4 +1,4 @@
-use dioscript_parser::error::ParseError;
+use dioscript_parser::{ast::Span, error::ParseError};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -8,6 +8,92 @@ pub enum Error {
     Parse(#[from] ParseError),
 }
 
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Runtime(e) => e.code(),
+            Error::Parse(e) => e.code(),
+        }
+    }
+
+    #[deprecated(note = "renamed to `info`")]
+    pub fn diagnostic(&self) -> ErrorInfo {
+        self.info()
+    }
+
+    // stage, message and position of the error, see `ErrorInfo`.
+    pub fn info(&self) -> ErrorInfo {
+        match self {
+            Error::Runtime(e) => {
+                let location = e.location();
+                ErrorInfo {
+                    stage: ErrorStage::Runtime,
+                    message: e.to_string(),
+                    source: location.map(|v| v.snippet.clone()),
+                    line: location.map(|v| v.line),
+                    column: location.map(|v| v.column),
+                }
+            }
+            Error::Parse(e) => {
+                let source = match e {
+                    ParseError::ParseFailure { text, .. } => Some(text),
+                    ParseError::UnMatchContent { content, .. } => Some(content),
+                    ParseError::ReservedKeyword { keyword, .. } => Some(keyword),
+                    ParseError::DuplicateParameter { name, .. } => Some(name),
+                    ParseError::DuplicateVariant { name, .. } => Some(name),
+                    ParseError::UnknownType { name, .. } => Some(name),
+                    ParseError::ReadFailure(_) => None,
+                };
+                let position = e.diagnostic();
+                ErrorInfo {
+                    stage: ErrorStage::Parse,
+                    message: e.to_string(),
+                    source: source.map(|s| s.lines().next().unwrap_or("").to_string()),
+                    line: position.line,
+                    column: position.column,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ErrorStage {
+    Parse,
+    Runtime,
+}
+
+// structured error info for embedders. editors which mark syntax errors need the range and
+// hints of `ParseError::diagnostic` instead.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ErrorInfo {
+    pub stage: ErrorStage,
+    pub message: String,
+    // first line of the source which parse failed, or of the statement which raised
+    // runtime error.
+    pub source: Option<String>,
+    // 1-based position of the failed source.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+// former name of `ErrorInfo`, it is not the `Diagnostic` of parser.
+#[deprecated(note = "renamed to `ErrorInfo`")]
+pub type Diagnostic = ErrorInfo;
+
+// problem of a function call found by `Runtime::analyze`.
+#[derive(thiserror::Error, Debug)]
+#[error("call `{call}`: {error}")]
+pub struct CallIssue {
+    // function name used in script, e.g. `std::print`.
+    pub call: String,
+    pub error: RuntimeError,
+    // statement which contains the call.
+    pub span: Span,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum RuntimeError {
     #[error("cannot use `{operator}` operator to `{value_type}` type data.")]
@@ -16,6 +102,12 @@ pub enum RuntimeError {
         value_type: String,
     },
 
+    #[error("`{operator}` operator need integer operand, found `{value}`.")]
+    NonIntegerOperand { operator: String, value: f64 },
+
+    #[error("`{operator}` shift amount must be in 0..64, found `{amount}`.")]
+    ShiftOutOfRange { operator: String, amount: i64 },
+
     #[error("cannot compare different data type: `{a}` and `{b}`.")]
     CompareDiffType { a: String, b: String },
 
@@ -25,6 +117,9 @@ pub enum RuntimeError {
     #[error("function `{name}` not found.")]
     FunctionNotFound { name: String },
 
+    #[error("`{value}` data is not callable.")]
+    NotCallable { value: String },
+
     #[error("`{name}` pointer data not found")]
     PoniterDataNotFound { name: String },
 
@@ -40,6 +135,16 @@ pub enum RuntimeError {
     #[error("cannot find index `{index}` in `{value}` value.")]
     IndexNotFound { index: String, value: String },
 
+    #[error("slice step cannot be zero.")]
+    ZeroSliceStep,
+
+    #[error("{target} expect `{expected}` type, found `{found}`.")]
+    TypeMismatch {
+        target: String,
+        expected: String,
+        found: String,
+    },
+
     #[error("need arguments number `{need}`, provided `{provided}`.")]
     IllegalArgumentsNumber { need: i16, provided: i16 },
 
@@ -52,9 +157,257 @@ pub enum RuntimeError {
     #[error("unknown attribute `{attr}` for `{value}` data.")]
     UnknownAttribute { attr: String, value: String },
 
+    #[error("unknown method `{method}` for `{value}` data.")]
+    UnknownMethod { method: String, value: String },
+
+    #[error("{message}: `{expr}` is false, operands: [{values}].")]
+    AssertionFailed {
+        expr: String,
+        values: String,
+        message: String,
+    },
+
     #[error("module: `{module}` not found.")]
     ModuleNotFound { module: String },
 
+    #[error("module `{module}` version {version} does not match `{required}`.")]
+    ModuleVersionMismatch {
+        module: String,
+        version: String,
+        required: String,
+    },
+
     #[error("cannot find namespace `{part}` in `{module}` module.")]
     ModulePartNotFound { part: String, module: String },
+
+    #[error("cannot use file `{path}`: {reason}")]
+    FileUseFailed { path: String, reason: String },
+
+    #[error("cannot destructure `{value_type}` type data, only tuple is allowed.")]
+    IllegalDestructure { value_type: String },
+    #[error("`break` can only be used inside a loop.")]
+    BreakOutsideLoop,
+    #[error("cannot {action} in read-only mode.")]
+    ReadOnlyViolation { action: String },
+    #[error("execution cancelled.")]
+    Interrupted,
+    #[error("execution limit exceeded: {limit}.")]
+    ExecutionLimitExceeded { limit: String },
+    #[error("memory limit exceeded: {limit}.")]
+    MemoryLimitExceeded { limit: String },
+
+    // error raised inside script functions, with the functions which were running.
+    #[error("{error}{}", format_trace(.trace))]
+    Traced {
+        error: Box<RuntimeError>,
+        trace: Vec<StackFrame>,
+    },
+    // error with the statement which raised it, `location` is filled once source is known.
+    #[error("{}{error}", format_location(.location))]
+    Located {
+        error: Box<RuntimeError>,
+        span: Span,
+        location: Option<Location>,
+    },
+}
+
+impl RuntimeError {
+    // stable code of the error kind, `ds explain <code>` describes it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::IllegalOperatorForType { .. } => "E0010",
+            RuntimeError::NonIntegerOperand { .. } => "E0011",
+            RuntimeError::CompareDiffType { .. } => "E0012",
+            RuntimeError::VariableNotFound { .. } => "E0013",
+            RuntimeError::FunctionNotFound { .. } => "E0014",
+            RuntimeError::NotCallable { .. } => "E0015",
+            RuntimeError::PoniterDataNotFound { .. } => "E0016",
+            RuntimeError::IllegalTypeInConditional { .. } => "E0017",
+            RuntimeError::IllegalIndexType { .. } => "E0018",
+            RuntimeError::IndexNotFound { .. } => "E0019",
+            RuntimeError::ZeroSliceStep => "E0020",
+            RuntimeError::TypeMismatch { .. } => "E0021",
+            RuntimeError::IllegalArgumentsNumber { .. } => "E0022",
+            RuntimeError::AnonymousFunctionInRoot => "E0023",
+            RuntimeError::BindFunctionNotFound { .. } => "E0024",
+            RuntimeError::UnknownAttribute { .. } => "E0025",
+            RuntimeError::UnknownMethod { .. } => "E0026",
+            RuntimeError::AssertionFailed { .. } => "E0027",
+            RuntimeError::ModuleNotFound { .. } => "E0028",
+            RuntimeError::ModuleVersionMismatch { .. } => "E0029",
+            RuntimeError::ModulePartNotFound { .. } => "E0030",
+            RuntimeError::FileUseFailed { .. } => "E0031",
+            RuntimeError::IllegalDestructure { .. } => "E0032",
+            RuntimeError::BreakOutsideLoop => "E0033",
+            RuntimeError::ReadOnlyViolation { .. } => "E0034",
+            RuntimeError::Interrupted => "E0035",
+            RuntimeError::ExecutionLimitExceeded { .. } => "E0036",
+            RuntimeError::MemoryLimitExceeded { .. } => "E0037",
+            RuntimeError::ShiftOutOfRange { .. } => "E0038",
+            RuntimeError::Traced { error, .. } | RuntimeError::Located { error, .. } => {
+                error.code()
+            }
+        }
+    }
+
+    // error without stack trace and location.
+    pub fn root(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::Traced { error, .. } | RuntimeError::Located { error, .. } => {
+                error.root()
+            }
+            _ => self,
+        }
+    }
+
+    // running functions when error was raised, innermost first.
+    pub fn trace(&self) -> &[StackFrame] {
+        match self {
+            RuntimeError::Traced { trace, .. } => trace,
+            RuntimeError::Located { error, .. } => error.trace(),
+            _ => &[],
+        }
+    }
+
+    // byte range of the statement which raised the error.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::Located { span, .. } => Some(*span),
+            RuntimeError::Traced { error, .. } => error.span(),
+            _ => None,
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            RuntimeError::Located { location, .. } => location.as_ref(),
+            RuntimeError::Traced { error, .. } => error.location(),
+            _ => None,
+        }
+    }
+
+    // attach statement span, errors from inner statements keep their own.
+    pub(crate) fn at(self, span: Span) -> Self {
+        if self.span().is_some() {
+            return self;
+        }
+        RuntimeError::Located {
+            error: Box::new(self),
+            span,
+            location: None,
+        }
+    }
+
+    // fill line, column and snippet from the source which was executed, for the statement
+    // and the call sites of the trace.
+    pub fn locate(&mut self, source: &str) {
+        self.fill_locations(&|span| Location::new(source, span));
+    }
+
+    // fill location from a used file, spans of its code start at `offset`.
+    pub(crate) fn locate_file(&mut self, file: &str, source: &str, offset: usize) {
+        self.fill_locations(&|span| {
+            if span.start < offset || span.end > offset + source.len() {
+                return None;
+            }
+            let span = Span {
+                start: span.start - offset,
+                end: span.end - offset,
+            };
+            Location::new(source, span).map(|v| Location {
+                file: Some(file.to_string()),
+                ..v
+            })
+        });
+    }
+
+    fn fill_locations(&mut self, resolve: &impl Fn(Span) -> Option<Location>) {
+        match self {
+            RuntimeError::Located {
+                error,
+                span,
+                location,
+            } => {
+                if location.is_none() {
+                    *location = resolve(*span);
+                }
+                error.fill_locations(resolve);
+            }
+            RuntimeError::Traced { error, trace } => {
+                for frame in trace.iter_mut() {
+                    if frame.location.is_none() {
+                        frame.location = frame.call_site.and_then(resolve);
+                    }
+                }
+                error.fill_locations(resolve);
+            }
+            _ => {}
+        }
+    }
+}
+
+// position of the statement which raised runtime error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    // 1-based, column is counted in chars.
+    pub line: usize,
+    pub column: usize,
+    // first line of the statement.
+    pub snippet: String,
+    // path of the used file which holds the statement, `None` for the executed script.
+    pub file: Option<String>,
+}
+
+impl Location {
+    pub fn new(source: &str, span: Span) -> Option<Self> {
+        let code = source.get(span.start..span.end)?;
+        let before = &source[..span.start];
+        let line_start = before.rfind('\n').map(|v| v + 1).unwrap_or(0);
+        Some(Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            snippet: code.lines().next().unwrap_or("").trim_end().to_string(),
+            file: None,
+        })
+    }
+}
+
+fn format_location(location: &Option<Location>) -> String {
+    match location {
+        Some(Location {
+            line,
+            file: Some(file),
+            ..
+        }) => format!("{file} line {line}: "),
+        Some(location) => format!("line {}: ", location.line),
+        None => String::new(),
+    }
+}
+
+// script function call which is running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    // defined name, `<anonymous>` for anonymous functions.
+    pub function: String,
+    // statement which called the function, `None` for calls from host.
+    pub call_site: Option<Span>,
+    // filled from `call_site` once source is known.
+    pub location: Option<Location>,
+}
+
+fn format_trace(trace: &[StackFrame]) -> String {
+    let mut result = String::new();
+    for frame in trace {
+        result.push_str(&format!("\n    at `{}`", frame.function));
+        match &frame.location {
+            Some(Location {
+                line,
+                file: Some(file),
+                ..
+            }) => result.push_str(&format!(", called at {file} line {line}")),
+            Some(location) => result.push_str(&format!(", called at line {}", location.line)),
+            None => {}
+        }
+    }
+    result
 }
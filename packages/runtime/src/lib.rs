@@ -1,22 +1,42 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+    time::Instant,
+};
 
-use error::{Error, RuntimeError};
+use bytecode::{Chunk, Engine};
+use error::{CallIssue, Error, RuntimeError, StackFrame};
 
 use dioscript_parser::{
     ast::{
-        CalculateMark, DioAstStatement, DioscriptAst, FunctionCall, FunctionDefine, FunctionName,
-        LoopExecuteType,
+        parse_expression, parse_function, AssertStatement, AssignScope, CalculateMark,
+        ConditionalStatement, Definition, DioAstStatement, DioscriptAst, EnumDefine, FunctionCall,
+        FunctionDefine, FunctionName, LoopExecuteType, LoopStatement, ObjectDefine, ParamsType,
+        Span, Statement, TupleDefine, UseStatement, VariableDefine, Version, VersionRequirement,
     },
     element::{AstElement, AstElementContentType},
+    error::ParseError,
     parser::{CalcExpr, LinkExpr},
-    types::AstValue,
+    symbol::{self, Symbol},
+    types::{AstValue, LocalVariable, SliceIndex},
 };
+use interrupt::InterruptHandle;
 use module::{ModuleGenerator, ModuleItem};
-use types::{Element, ElementContentType, FunctionType, Value};
-use uuid::Uuid;
+use observer::{ExecutionEvent, ExecutionObserver};
+use report::{ExecutionLimits, ExecutionReport};
+use slotmap::{SecondaryMap, SlotMap};
+use types::{DataId, Element, ElementContentType, FunctionType, UsedModules, Value};
 
+pub mod a11y;
+pub mod bytecode;
 pub mod error;
+pub mod interrupt;
 pub mod module;
+pub mod observer;
+pub mod prelude;
+pub mod report;
 pub mod stdlib;
 pub mod types;
 
@@ -24,20 +44,84 @@ pub struct Runtime {
     // variable content: use for save variable node-id.
     scopes: Vec<Scope>,
     // scope tree: use for build scope structure.
-    data: HashMap<Uuid, DataType>,
+    data: SlotMap<DataId, DataType>,
     // module included.
-    modules: HashMap<String, module::ModuleItem>,
-    // namespace using list
-    namespace_use: HashMap<String, Vec<String>>,
+    modules: HashMap<Symbol, module::ModuleItem>,
+    // version of top-level modules, `std` has the version of this crate.
+    module_versions: HashMap<String, Version>,
+    // names used in every scope, `use` statements are kept by each scope.
+    namespace_use: HashMap<Symbol, Vec<Symbol>>,
+    // type methods bind from rust: type name => method name => function
+    type_methods: HashMap<String, HashMap<String, FunctionType>>,
+    // execution event observers
+    observers: Vec<Box<dyn ExecutionObserver>>,
+    // root path for `use "file.ds";` statement.
+    root_path: PathBuf,
+    // path, code and span offset of used files, errors of their code are located with them.
+    used_files: Vec<(String, String, usize)>,
+    // resource accounting of current execution.
+    report: ExecutionReport,
+    // read-only mode: forbid variable assign outside functions and capability module call.
+    read_only: bool,
+    // enforce type annotations of variables, parameters and return values.
+    strict_types: bool,
+    // annotations of variables and parameters, checked again when they are assigned.
+    types: SecondaryMap<DataId, String>,
+    // scope index of each running script function.
+    function_scopes: Vec<usize>,
+    // value of executed `break` statement, taken by the innermost loop.
+    break_value: Option<Value>,
+    // value of `return` inside a loop expression, it leaves the running function or script.
+    return_value: Option<Value>,
+    // set by host to stop the running script.
+    interrupt: InterruptHandle,
+    // running script functions, innermost last.
+    call_stack: Vec<StackFrame>,
+    // running statement, it is the call site of functions called by it.
+    statement_span: Option<Span>,
+    // error raised by running rust function, it fails the call once the function returns.
+    host_error: Option<RuntimeError>,
+    limits: ExecutionLimits,
+    // fuel left and deadline of current execution.
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    // data captured by closures, it may outlive its scope and is only dropped by
+    // `collect_garbage`.
+    escaped: HashSet<DataId>,
+    engine: Engine,
+    // compiled bodies of script functions, keyed by address of their define.
+    chunks: HashMap<*const FunctionDefine, (Weak<FunctionDefine>, Rc<Chunk>)>,
 }
 
 impl Runtime {
     pub fn new() -> Self {
         let mut this = Self {
-            scopes: vec![],
-            data: HashMap::new(),
+            scopes: vec![Scope::gen()],
+            data: SlotMap::with_key(),
             modules: Default::default(),
+            module_versions: Default::default(),
             namespace_use: Default::default(),
+            type_methods: Default::default(),
+            observers: vec![],
+            root_path: PathBuf::from("."),
+            used_files: vec![],
+            report: Default::default(),
+            read_only: false,
+            strict_types: false,
+            types: SecondaryMap::new(),
+            function_scopes: vec![],
+            break_value: None,
+            return_value: None,
+            interrupt: InterruptHandle::new(),
+            call_stack: vec![],
+            statement_span: None,
+            host_error: None,
+            limits: ExecutionLimits::default(),
+            fuel: None,
+            deadline: None,
+            escaped: HashSet::new(),
+            engine: Engine::default(),
+            chunks: HashMap::new(),
         };
 
         this.setup().expect("Runtime setup failed.");
@@ -45,21 +129,25 @@ impl Runtime {
         this
     }
 
+    // create runtime with prelude executed in root scope, all definitions will be visible for scripts.
+    pub fn with_prelude(prelude: &Prelude) -> Result<Self, RuntimeError> {
+        let mut this = Self::new();
+        this.execute_scope_without_new_scope(&prelude.0.stats)?;
+        Ok(this)
+    }
+
     fn setup(&mut self) -> Result<(), RuntimeError> {
         // let scope = self.root_scope.clone();
 
         let mut module_exporter = HashMap::new();
-        module_exporter.insert("std".to_string(), stdlib::std().to_module_item());
+        module_exporter.insert(Symbol::new("std"), stdlib::std().to_module_item());
         self.modules = module_exporter;
+        self.set_module_version("std", Version::parse(env!("CARGO_PKG_VERSION")));
 
         for path in stdlib::auto_use() {
-            let temp: Vec<String> = path
-                .split("::")
-                .into_iter()
-                .map(|v| v.to_string())
-                .collect();
+            let temp: Vec<Symbol> = path.split("::").map(Symbol::new).collect();
             self.namespace_use
-                .insert(temp.last().unwrap().to_string(), temp);
+                .insert(temp.last().unwrap().clone(), temp);
         }
 
         Ok(())
@@ -70,219 +158,1119 @@ impl Runtime {
     }
 
     pub fn bind_module(&mut self, name: &str, module: ModuleGenerator) {
+        self.set_module_version(name, module.1);
         self.modules
-            .insert(name.to_string(), module.to_module_item());
+            .insert(Symbol::new(name), module.to_module_item());
+    }
+
+    fn set_module_version(&mut self, name: &str, version: Option<Version>) {
+        match version {
+            Some(version) => self.module_versions.insert(name.to_string(), version),
+            None => self.module_versions.remove(name),
+        };
+    }
+
+    // `use http(>=1.2, <2);` fails if the bound `http` module has no matching version.
+    fn check_module_version(
+        &self,
+        name: &str,
+        requirements: &[VersionRequirement],
+    ) -> Result<(), RuntimeError> {
+        if !self.modules.contains_key(&Symbol::new(name)) {
+            return Err(RuntimeError::ModuleNotFound {
+                module: name.to_string(),
+            });
+        }
+        let version = self.module_versions.get(name);
+        let matched = match version {
+            Some(version) => requirements.iter().all(|v| v.matches(version)),
+            None => requirements.is_empty(),
+        };
+        if matched {
+            return Ok(());
+        }
+        let required: Vec<String> = requirements.iter().map(|v| v.to_string()).collect();
+        Err(RuntimeError::ModuleVersionMismatch {
+            module: name.to_string(),
+            version: version.map_or("unknown".to_string(), |v| v.to_string()),
+            required: required.join(", "),
+        })
+    }
+
+    // swap functions of a bound module in place, scopes and `use` of the running session
+    // are kept, so later calls go to the new functions.
+    pub fn replace_module(
+        &mut self,
+        name: &str,
+        module: ModuleGenerator,
+    ) -> Result<(), RuntimeError> {
+        match self.modules.get_mut(&Symbol::new(name)) {
+            Some(item) => {
+                let version = module.1;
+                *item = module.to_module_item();
+                self.set_module_version(name, version);
+                Ok(())
+            }
+            None => Err(RuntimeError::ModuleNotFound {
+                module: name.to_string(),
+            }),
+        }
+    }
+
+    // bind method for built-in type: `"abc".shout()` will call `func` with `"abc"` as first argument.
+    pub fn bind_type_method(&mut self, value_type: &str, name: &str, func: module::RustyFunction) {
+        self.type_methods
+            .entry(value_type.to_string())
+            .or_default()
+            .insert(name.to_string(), FunctionType::Rusty((func, -1)));
+    }
+
+    // handle which stops the running script with `RuntimeError::Interrupted`.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+    }
+
+    // limits are applied to every `execute_ast` call, they start counting again each time.
+    pub fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.limits = limits;
+        self.reset_limits();
+    }
+
+    // fuel left of the last execution, `Some(0)` when it was stopped for running out of fuel.
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    fn reset_limits(&mut self) {
+        self.fuel = self.limits.fuel;
+        self.deadline = self.limits.timeout.map(|v| Instant::now() + v);
+    }
+
+    // interrupt and limits are checked before every statement and block.
+    fn check_limits(&mut self) -> Result<(), RuntimeError> {
+        if self.interrupt.is_interrupted() {
+            return Err(RuntimeError::Interrupted);
+        }
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Err(RuntimeError::ExecutionLimitExceeded {
+                    limit: format!("fuel of {} steps is used up", self.limits.fuel.unwrap_or(0)),
+                });
+            }
+            *fuel -= 1;
+        }
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout) {
+            if Instant::now() >= deadline {
+                return Err(RuntimeError::ExecutionLimitExceeded {
+                    limit: format!("timeout of {timeout:?} is reached"),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // strings, lists and dicts are checked where they can grow: calculation, index assignment
+    // and rusty function results.
+    fn check_size(&self, value: &Value) -> Result<(), RuntimeError> {
+        let max = match self.limits.max_size {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        let size = match value {
+            Value::String(v) => v.len(),
+            Value::List(v) => v.len(),
+            Value::Dict(v) => v.len(),
+            _ => return Ok(()),
+        };
+        if size > max {
+            return Err(RuntimeError::MemoryLimitExceeded {
+                limit: format!("{} of size {size} is larger than {max}", value.value_name()),
+            });
+        }
+        Ok(())
+    }
+
+    // maximum length of strings, lists and dicts, for builtin functions to stop early.
+    #[cfg(feature = "stdlib-ext")]
+    pub(crate) fn max_size(&self) -> Option<usize> {
+        self.limits.max_size
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    // annotations are ignored unless strict mode is enabled.
+    pub fn set_strict_types(&mut self, strict: bool) {
+        self.strict_types = strict;
+    }
+
+    // bytecode engine falls back to the interpreter for statements it can't compile, results
+    // and errors are the same with both engines.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    // inject global variable into root scope.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        let id = self.data.insert(DataType::Variable(value));
+        self.scopes[0].data.insert(Symbol::new(name), id);
+    }
+
+    pub fn set_root_path(&mut self, path: impl Into<PathBuf>) {
+        self.root_path = path.into();
     }
 
     pub fn add_script_function(
         &mut self,
         func: FunctionDefine,
-    ) -> Result<(Option<Uuid>, Value), RuntimeError> {
+    ) -> Result<(Option<DataId>, Value), RuntimeError> {
         let full_name = func.name.clone();
         if let Some(name) = full_name {
-            // let root_scope = self.root_scope.clone();
-            let new_scope = self.set_var(
-                &name,
-                Value::Function(types::FunctionType::DScript(func.clone())),
-            )?;
-
-            Ok((
-                Some(new_scope),
-                Value::Function(types::FunctionType::DScript(func)),
-            ))
+            // name is bound before capturing, so the function can call itself.
+            let new_scope = self.store_var(&Symbol::new(&name), Value::None)?;
+            let value = self.closure(func);
+            if let Some(DataType::Variable(v)) = self.data.get_mut(new_scope) {
+                *v = value.clone();
+            }
+            Ok((Some(new_scope), value))
         } else {
-            Ok((None, Value::Function(types::FunctionType::DScript(func))))
+            Ok((None, self.closure(func)))
+        }
+    }
+
+    // function value of script define, its free variables visible here are captured.
+    fn closure(&mut self, define: FunctionDefine) -> Value {
+        let captures: HashMap<Symbol, DataId> = define
+            .captures
+            .iter()
+            .filter_map(|name| self.var_ref(name).map(|(id, _)| (name.clone(), id)))
+            .collect();
+        self.escaped.extend(captures.values());
+        // `use` names of running function, top-level ones are found by name when called.
+        let mut uses = HashMap::new();
+        if let Some(start) = self.function_scopes.last() {
+            for scope in self.scopes[*start..].iter().rev() {
+                for (name, path) in scope.uses.iter() {
+                    uses.entry(name.clone()).or_insert_with(|| path.clone());
+                }
+            }
+        }
+        if captures.is_empty() && uses.is_empty() {
+            Value::Function(FunctionType::DScript(Rc::new(define)))
+        } else {
+            Value::Function(FunctionType::Closure((Rc::new(define), captures, uses)))
+        }
+    }
+
+    // function value of builtin source, it captures host `values` by name.
+    pub(crate) fn host_closure(&mut self, code: &str, values: Vec<(&str, Value)>) -> Value {
+        let define = parse_function(code).expect("Builtin function parse failed.");
+        let mut captures = HashMap::new();
+        for (name, value) in values {
+            let id = self.data.insert(DataType::Variable(value));
+            self.escaped.insert(id);
+            captures.insert(Symbol::new(name), id);
+        }
+        let define = Rc::new(define);
+        Value::Function(FunctionType::Closure((define, captures, HashMap::new())))
+    }
+
+    // fail the running rust function with `error`, its returned value is dropped. the first
+    // error is kept if it is raised more than once.
+    pub fn raise(&mut self, error: RuntimeError) {
+        if self.host_error.is_none() {
+            self.host_error = Some(error);
         }
     }
 
     pub fn execute(&mut self, code: &str) -> Result<Value, Error> {
         let ast = DioscriptAst::from_string(code)?;
-        Ok(self.execute_ast(ast)?)
+        self.execute_ast(ast).map_err(|mut e| {
+            e.locate(code);
+            e.into()
+        })
     }
 
-    pub fn execute_ast(&mut self, ast: DioscriptAst) -> Result<Value, RuntimeError> {
-        let result = self.execute_scope(ast.stats)?;
+    // doc comments of a function visible from root scope: `add`, `std::help`.
+    pub fn function_docs(&self, name: &str) -> Option<String> {
+        let parts: Vec<Symbol> = name.split("::").map(Symbol::new).collect();
+        let name = if parts.len() == 1 {
+            FunctionName::Single(parts[0].clone())
+        } else {
+            FunctionName::Namespace(parts)
+        };
+        self.get_function(&name).ok()?.define()?.docs.clone()
+    }
+
+    // items of module at `path` sorted by name, top-level modules for empty `path`. `None` if
+    // `path` is not a module.
+    pub fn module_items(&self, path: &[String]) -> Option<Vec<(String, ModuleItem)>> {
+        let mut items: Vec<(String, ModuleItem)> = if path.is_empty() {
+            self.modules
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect()
+        } else {
+            let path: Vec<Symbol> = path.iter().map(Symbol::from).collect();
+            match self.load_from_module(&path).ok()? {
+                ModuleItem::SubModule(info) => info
+                    .0
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+                _ => return None,
+            }
+        };
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        Some(items)
+    }
+
+    // check calls before execution: unknown functions or modules, and wrong number of
+    // arguments for functions whose parameters are known.
+    pub fn analyze(&self, ast: &DioscriptAst) -> Vec<CallIssue> {
+        let symbols = ast.symbols();
+        let mut uses = self.namespace_use.clone();
+        for scope in self.scopes.iter() {
+            uses.extend(scope.uses.clone());
+        }
+        for path in symbols.modules.iter() {
+            let path: Vec<Symbol> = path.iter().map(Symbol::from).collect();
+            uses.insert(path.last().unwrap().clone(), path);
+        }
+        for (alias, path) in symbols.aliases.iter() {
+            uses.insert(Symbol::new(alias), path.iter().map(Symbol::from).collect());
+        }
+        // modules of used files and `std::module::register` are bound while executing,
+        // their functions are unknown here.
+        let mut dynamic: Vec<String> = symbols
+            .files
+            .iter()
+            .filter_map(|v| Path::new(v).file_stem())
+            .map(|v| v.to_string_lossy().to_string())
+            .collect();
+        dynamic.extend(symbols.registered.iter().cloned());
+        let is_dynamic = |name: &Symbol| {
+            let root = uses.get(name).map(|path| &path[0]).unwrap_or(name);
+            dynamic.iter().any(|v| *root == *v)
+        };
+
+        let mut issues = vec![];
+        for call in symbols.calls.iter() {
+            let name = call.name.to_string();
+            let need = match &call.name {
+                FunctionName::Single(single) => match symbols.lookup(&call.scope, single) {
+                    // variable may hold any function.
+                    Some(Definition::Binding) => continue,
+                    Some(Definition::Function(Some(params))) => Some(params_number(params)),
+                    // defined more than once, the called one depends on control flow.
+                    Some(Definition::Function(None)) => continue,
+                    None => match self.var_ref(single) {
+                        Some((_, Value::Function(f))) => Some(function_params_number(f)),
+                        Some(_) => None,
+                        None => {
+                            let used = uses.get(single);
+                            match self.find_module_value(std::slice::from_ref(single), used) {
+                                Ok(ModuleItem::Function(f)) => Some(function_params_number(&f)),
+                                _ if used.is_some() && is_dynamic(single) => None,
+                                _ => {
+                                    let error =
+                                        RuntimeError::FunctionNotFound { name: name.clone() };
+                                    issues.push(CallIssue {
+                                        call: name,
+                                        error,
+                                        span: call.span,
+                                    });
+                                    continue;
+                                }
+                            }
+                        }
+                    },
+                },
+                FunctionName::Namespace(namespace) => {
+                    if is_dynamic(&namespace[0]) {
+                        continue;
+                    }
+                    let used = uses.get(&namespace[0]);
+                    match self.find_module_value(namespace, used) {
+                        Ok(ModuleItem::Function(f)) => Some(function_params_number(&f)),
+                        Ok(_) => {
+                            let error = RuntimeError::FunctionNotFound { name: name.clone() };
+                            issues.push(CallIssue {
+                                call: name,
+                                error,
+                                span: call.span,
+                            });
+                            continue;
+                        }
+                        Err(error) => {
+                            issues.push(CallIssue {
+                                call: name,
+                                error,
+                                span: call.span,
+                            });
+                            continue;
+                        }
+                    }
+                }
+            };
+            match need {
+                Some((need, variadic))
+                    if call.arguments < need || (!variadic && call.arguments != need) =>
+                {
+                    let error = RuntimeError::IllegalArgumentsNumber {
+                        need: need as i16,
+                        provided: call.arguments as i16,
+                    };
+                    issues.push(CallIssue {
+                        call: name,
+                        error,
+                        span: call.span,
+                    });
+                }
+                _ => {}
+            }
+        }
+        issues
+    }
+
+    // parse function source once, the handle can be called many times.
+    pub fn compile_function(&self, code: &str) -> Result<FunctionHandle, Error> {
+        let define = parse_function(code)?;
+        Ok(FunctionHandle(FunctionType::DScript(Rc::new(define))))
+    }
+
+    // evaluate single expression in read-only mode, use for host configuration. functions can
+    // be called inside it, but can not be returned: they would run later without read-only mode.
+    pub fn eval_expr(&mut self, code: &str) -> Result<Value, Error> {
+        let expr = parse_expression(code)?;
+        // loops and statements of element bodies can run forever, and defined functions
+        // outlive the expression, only plain expressions are evaluated.
+        if let Some(action) = expr_statement(&expr) {
+            return Err(RuntimeError::ReadOnlyViolation {
+                action: action.to_string(),
+            }
+            .into());
+        }
+        self.reset_limits();
+        let read_only = std::mem::replace(&mut self.read_only, true);
+        let result = self
+            .execute_calculate(&expr)
+            .and_then(|v| self.deref_value(v));
+        self.read_only = read_only;
+        let result = result?;
+        if contains_function(&result) {
+            return Err(RuntimeError::ReadOnlyViolation {
+                action: "return function from expression".to_string(),
+            }
+            .into());
+        }
         Ok(result)
     }
 
+    // report is kept for failed executions too, e.g. to see how far a script got before it
+    // ran out of fuel.
+    pub fn execute_with_report(&mut self, code: &str) -> (Result<Value, Error>, ExecutionReport) {
+        let timer = Instant::now();
+        self.report = ExecutionReport {
+            peak_data_size: self.data.len(),
+            ..Default::default()
+        };
+        let result = self.execute(code);
+        let mut report = std::mem::take(&mut self.report);
+        report.wall_time = timer.elapsed();
+        (result, report)
+    }
+
+    // execute many scripts with shared module tables, each script has its own globals.
+    pub fn execute_many<I>(&mut self, jobs: I) -> Vec<Result<Value, RuntimeError>>
+    where
+        I: IntoIterator<Item = (DioscriptAst, HashMap<String, Value>)>,
+    {
+        let mut results = vec![];
+        for (ast, globals) in jobs {
+            self.enter_scope(false);
+            let result = globals
+                .into_iter()
+                .try_for_each(|(name, value)| {
+                    self.create_var(&Symbol::new(&name), value).map(|_| ())
+                })
+                .and_then(|_| self.execute_ast(ast));
+            self.leave_scope();
+            results.push(result);
+        }
+        results
+    }
+
+    pub fn execute_ast(&mut self, ast: DioscriptAst) -> Result<Value, RuntimeError> {
+        self.run_ast(ast, true)
+    }
+
+    // run in current scope, so variables and functions defined by script are kept for later
+    // executions, e.g. inputs of a repl.
+    pub fn execute_ast_in_scope(&mut self, ast: DioscriptAst) -> Result<Value, RuntimeError> {
+        self.run_ast(ast, false)
+    }
+
+    fn run_ast(&mut self, ast: DioscriptAst, new_scope: bool) -> Result<Value, RuntimeError> {
+        self.reset_limits();
+        self.emit(ExecutionEvent::ScriptStarted);
+        if new_scope {
+            self.enter_scope(false);
+        }
+        let result = self.execute_body(&ast.stats, None);
+        let returned = self.return_value.take();
+        let result = result.map(|v| returned.unwrap_or(v));
+        let result = self.run_deferred(result);
+        if new_scope {
+            self.leave_scope();
+        }
+        let result = result.and_then(|v| match self.break_value.take() {
+            Some(_) => Err(RuntimeError::BreakOutsideLoop),
+            None => Ok(v),
+        });
+        let result = result.map_err(|mut e| {
+            self.locate_used_files(&mut e);
+            e
+        });
+        match &result {
+            Ok(_) => self.emit(ExecutionEvent::ScriptFinished),
+            Err(e) => self.emit(ExecutionEvent::error(e)),
+        }
+        result
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn ExecutionObserver>) {
+        self.observers.push(observer);
+    }
+
+    fn emit(&mut self, event: ExecutionEvent) {
+        for observer in self.observers.iter_mut() {
+            observer.on_event(&event);
+        }
+    }
+
+    // child scope of current one, `execute_ast_in_scope` defines variables in it until it is
+    // popped.
+    pub fn push_scope(&mut self) {
+        self.enter_scope(false);
+    }
+
+    // root scope is never popped, `false` is returned for it.
+    pub fn pop_scope(&mut self) -> bool {
+        if self.scopes.len() <= 1 {
+            return false;
+        }
+        self.leave_scope();
+        true
+    }
+
+    // number of scopes above root scope.
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len() - 1
+    }
+
     fn enter_scope(&mut self, i: bool) {
         let scope = if i { Scope::fun() } else { Scope::gen() };
         self.scopes.push(scope);
     }
 
     fn leave_scope(&mut self) {
-        self.scopes.pop();
+        if let Some(scope) = self.scopes.pop() {
+            // data only known by this scope is dropped with it.
+            for id in scope
+                .data
+                .values()
+                .chain(scope.slots.iter().map(|(_, id)| id))
+            {
+                if !self.escaped.contains(id) {
+                    self.data.remove(*id);
+                }
+            }
+        }
+    }
+
+    // drop data which cannot be reached from scopes, modules and type methods, returns the
+    // number of dropped entries. call it between executions: closures held by host are not
+    // known to runtime, their captured data is dropped too.
+    pub fn collect_garbage(&mut self) -> usize {
+        let mut pending = vec![];
+        for scope in self.scopes.iter() {
+            pending.extend(
+                scope
+                    .data
+                    .values()
+                    .chain(scope.slots.iter().map(|(_, id)| id)),
+            );
+        }
+        for item in self.modules.values() {
+            module_data(item, &mut pending);
+        }
+        for methods in self.type_methods.values() {
+            for func in methods.values() {
+                function_data(func, &mut pending);
+            }
+        }
+        for value in self.break_value.iter().chain(self.return_value.iter()) {
+            value_data(value, &mut pending);
+        }
+        let mut marked = HashSet::new();
+        while let Some(id) = pending.pop() {
+            if marked.insert(id) {
+                if let Some(DataType::Variable(value)) = self.data.get(id) {
+                    value_data(value, &mut pending);
+                }
+            }
+        }
+        let size = self.data.len();
+        self.data.retain(|id, _| marked.contains(&id));
+        self.escaped.retain(|id| marked.contains(id));
+        self.chunks
+            .retain(|_, (define, _)| define.strong_count() > 0);
+        size - self.data.len()
     }
 
-    fn execute_scope(&mut self, statements: Vec<DioAstStatement>) -> Result<Value, RuntimeError> {
+    // statements of script or function in current scope, run by selected engine. function
+    // bodies are compiled once, script statements at every execution.
+    fn execute_body(
+        &mut self,
+        statements: &[Statement],
+        define: Option<&Rc<FunctionDefine>>,
+    ) -> Result<Value, RuntimeError> {
+        match (self.engine, define) {
+            (Engine::Interpreter, _) => self.execute_scope_without_new_scope(statements),
+            (Engine::Bytecode, Some(define)) => {
+                let chunk = self.function_chunk(define);
+                self.run_chunk(&chunk)
+            }
+            (Engine::Bytecode, None) => self.run_chunk(&bytecode::compile(statements)),
+        }
+    }
+
+    fn execute_scope(&mut self, statements: &[Statement]) -> Result<Value, RuntimeError> {
+        self.enter_scope(false);
+        let result = self.execute_scope_without_new_scope(statements);
+        let result = self.run_deferred(result);
+        self.leave_scope();
+        result
+    }
+
+    // run `defer` expressions of current scope in reverse order, the first error is returned.
+    fn run_deferred<T>(&mut self, result: Result<T, RuntimeError>) -> Result<T, RuntimeError> {
+        let deferred = match self.scopes.last_mut() {
+            Some(scope) => std::mem::take(&mut scope.deferred),
+            None => vec![],
+        };
+        if deferred.is_empty() {
+            return result;
+        }
+        // pending `break` must not be taken by functions called in deferred expressions.
+        let pending = self.break_value.take();
+        let mut error = None;
+        for expr in deferred.iter().rev() {
+            if let Err(e) = self.execute_calculate(expr) {
+                error.get_or_insert(e);
+            }
+        }
+        self.break_value = pending;
+        match error {
+            Some(e) if result.is_ok() => Err(e),
+            _ => result,
+        }
+    }
+
+    fn execute_scope_without_new_scope(
+        &mut self,
+        statements: &[Statement],
+    ) -> Result<Value, RuntimeError> {
         let mut result: Value = Value::None;
         let mut finish = false;
-        self.enter_scope(false);
+        // empty loop bodies are checked here too.
+        self.check_limits()?;
         for v in statements {
-            if finish {
+            if finish || self.break_value.is_some() || self.return_value.is_some() {
                 break;
             }
-            match v {
-                DioAstStatement::ModuleUse(u) => {
-                    let u = u.0;
-                    let last = u.last().unwrap();
-                    self.namespace_use.insert(last.to_string(), u.clone());
-                }
-                DioAstStatement::VariableAss(var) => {
-                    // let name = var.0.clone();
-                    // let value = var.1.clone();
-                    let name = var.name.clone();
-                    let value = var.expr.clone();
-                    let value = self.execute_calculate(value)?;
-                    let _scope = self.set_var(&name, value)?;
-                }
-                DioAstStatement::ReturnValue(r) => {
-                    result = self.execute_calculate(r.clone())?;
-                    result = self.deref_value(result)?;
-                    finish = true;
-                }
-                DioAstStatement::IfStatement(cond) => {
-                    let condition_expr = cond.condition.clone();
-                    let inner_ast = cond.inner.clone();
-                    let otherwise = cond.otherwise.clone();
-                    let state = self.execute_calculate(condition_expr)?;
-                    if let Value::Boolean(state) = state {
-                        if state {
-                            result = self.execute_scope(inner_ast)?;
-                            if !result.as_none() {
-                                finish = true;
-                            }
-                        } else {
-                            if let Some(otherwise) = otherwise {
-                                result = self.execute_scope(otherwise)?;
-                                if !result.as_none() {
-                                    finish = true;
-                                }
-                            }
-                        }
-                    } else {
-                        return Err(RuntimeError::IllegalTypeInConditional {
-                            value_type: state.value_name(),
-                        });
-                    }
+            self.check_limits()?;
+            self.report.statements += 1;
+            let span = v.span;
+            let outer = self.statement_span.replace(span);
+            let executed = self.execute_statement(&v.kind, &mut result, &mut finish);
+            self.statement_span = outer;
+            executed.map_err(|e| e.at(span))?;
+        }
+        Ok(result)
+    }
+
+    // `result` is set by `return` and blocks with value, `finish` stops the rest statements.
+    // arms are kept free of `?` like `execute_calculate`.
+    fn execute_statement(
+        &mut self,
+        stat: &DioAstStatement,
+        result: &mut Value,
+        finish: &mut bool,
+    ) -> Result<(), RuntimeError> {
+        let mut stop = |value: Value| {
+            *result = value;
+            *finish = true;
+        };
+        match stat {
+            DioAstStatement::ModuleUse(u) => self.use_module(u),
+            DioAstStatement::FileUse(path) => self.use_file(path),
+            DioAstStatement::VariableAss(var) => self.execute_assign(var),
+            DioAstStatement::TupleAss(var) => self.execute_tuple_assign(var),
+            DioAstStatement::ReturnValue(r) => self.calculate_data(r).map(stop),
+            DioAstStatement::IfStatement(cond) => self.execute_conditional(cond).map(|value| {
+                if !value.as_none() {
+                    stop(value);
                 }
-                DioAstStatement::LoopStatement(data) => {
-                    let execute_type = data.execute_type;
-                    match execute_type {
-                        LoopExecuteType::Conditional(cond) => loop {
-                            let cond = cond.clone();
-                            let state = self.execute_calculate(cond)?;
-                            let state = state.to_boolean_data();
-                            if !state {
-                                break;
-                            } else {
-                                let res = self.execute_scope(data.inner.clone())?;
-                                if !res.as_none() {
-                                    result = res;
-                                    finish = true;
-                                    break;
-                                }
-                            }
-                        },
-                        LoopExecuteType::Iter { iter, var } => {
-                            let iter = self.to_value(iter)?;
-                            if iter.value_name() == "list" {
-                                for i in iter.as_list().unwrap() {
-                                    self.set_var(&var, i.clone())?;
-                                    let res = self.execute_scope(data.inner.clone())?;
-                                    if !res.as_none() {
-                                        result = res;
-                                        finish = true;
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    }
+            }),
+            DioAstStatement::LoopStatement(data) => self.execute_loop(data).map(|exit| {
+                if let LoopExit::Return(value) = exit {
+                    stop(value);
+                }
+            }),
+            DioAstStatement::Break(value) => self.execute_break(value.as_ref()),
+            DioAstStatement::FunctionCall(func) => self.execute_function(func).map(drop),
+            DioAstStatement::MethodCall(expr) => self.execute_calculate(expr).map(drop),
+            DioAstStatement::EnumDefine(define) => self.define_enum(define),
+            DioAstStatement::ObjectDefine(define) => self
+                .set_var(&Symbol::new(&define.name), Value::Object(define.clone()))
+                .map(drop),
+            DioAstStatement::Assert(assert) => self.execute_assert(assert),
+            DioAstStatement::Defer(expr) => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.deferred.push(expr.clone());
+                }
+                Ok(())
+            }
+            DioAstStatement::FunctionDefine(define) => self.define_function(define),
+            _ => Ok(()),
+        }
+    }
+
+    fn use_module(&mut self, u: &UseStatement) -> Result<(), RuntimeError> {
+        if !u.requirements.is_empty() {
+            self.check_module_version(&u.path[0], &u.requirements)?;
+        }
+        // visible until current scope is left.
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.uses.insert(Symbol::new(u.name()), u.path.clone());
+        }
+        self.emit(ExecutionEvent::ModuleUsed {
+            path: u.path.iter().map(|v| v.to_string()).collect(),
+        });
+        Ok(())
+    }
+
+    // result of expression with references resolved.
+    fn calculate_data(&mut self, expr: &CalcExpr) -> Result<Value, RuntimeError> {
+        let value = self.execute_calculate(expr)?;
+        self.deref_value(value)
+    }
+
+    fn execute_break(&mut self, expr: Option<&CalcExpr>) -> Result<(), RuntimeError> {
+        let value = match expr {
+            Some(expr) => self.calculate_data(expr)?,
+            None => Value::None,
+        };
+        self.break_value = Some(value);
+        Ok(())
+    }
+
+    fn execute_assign(&mut self, var: &VariableDefine) -> Result<(), RuntimeError> {
+        let name = var.name.clone();
+        let value = self.execute_calculate(&var.expr)?;
+        let target = format!("variable `{name}`");
+        self.check_type(&target, &value, var.annotation.as_ref())?;
+        if let Some(scope) = var.scope {
+            self.set_outer_var(&name, scope, &var.index, value)?;
+        } else if var.index.is_empty() {
+            if var.new {
+                let id = self.define_var(&name, value)?;
+                if let Some(annotation) = &var.annotation {
+                    self.types.insert(id, annotation.clone());
+                }
+            } else {
+                self.set_var(&name, value)?;
+            }
+        } else {
+            self.set_index_var(&name, &var.index, value)?;
+        }
+        Ok(())
+    }
+
+    fn execute_tuple_assign(&mut self, var: &TupleDefine) -> Result<(), RuntimeError> {
+        let value = self.execute_calculate(&var.expr)?;
+        match self.deref_value(value)? {
+            Value::Tuple((a, b)) if var.new => {
+                self.define_var(&var.names.0, *a)?;
+                self.define_var(&var.names.1, *b)?;
+            }
+            Value::Tuple((a, b)) => {
+                self.set_var(&var.names.0, *a)?;
+                self.set_var(&var.names.1, *b)?;
+            }
+            value => {
+                return Err(RuntimeError::IllegalDestructure {
+                    value_type: value.value_name(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_conditional(&mut self, cond: &ConditionalStatement) -> Result<Value, RuntimeError> {
+        let state = self.execute_calculate(&cond.condition)?;
+        match state {
+            Value::Boolean(true) => self.execute_scope(&cond.inner),
+            Value::Boolean(false) => match &cond.otherwise {
+                Some(otherwise) => self.execute_scope(otherwise),
+                None => Ok(Value::None),
+            },
+            state => Err(RuntimeError::IllegalTypeInConditional {
+                value_type: state.value_name(),
+            }),
+        }
+    }
+
+    fn define_enum(&mut self, define: &EnumDefine) -> Result<(), RuntimeError> {
+        let mut variants = HashMap::new();
+        for variant in define.variants.iter() {
+            let value = Value::Enum((define.name.clone(), variant.clone()));
+            variants.insert(variant.clone(), value);
+        }
+        // enum and object names are variables, read-only mode guards them like `=`.
+        self.set_var(&Symbol::new(&define.name), Value::Dict(Rc::new(variants)))?;
+        Ok(())
+    }
+
+    fn define_function(&mut self, define: &FunctionDefine) -> Result<(), RuntimeError> {
+        if !define.decorators.is_empty() {
+            let name = define
+                .name
+                .clone()
+                .ok_or(RuntimeError::AnonymousFunctionInRoot)?;
+            let value = self.decorate(define.clone())?;
+            self.store_var(&Symbol::new(&name), value)?;
+            return Ok(());
+        }
+        let f = self.add_script_function(define.clone())?;
+        if f.0.is_none() {
+            return Err(RuntimeError::AnonymousFunctionInRoot);
+        }
+        Ok(())
+    }
+
+    fn execute_loop(&mut self, data: &LoopStatement) -> Result<LoopExit, RuntimeError> {
+        match &data.execute_type {
+            LoopExecuteType::Conditional(cond) => loop {
+                let state = self.execute_calculate(cond)?;
+                if !state.to_boolean_data() {
+                    break;
                 }
-                DioAstStatement::FunctionCall(func) => {
-                    let _result = self.execute_function(func)?;
+                let res = self.execute_scope(&data.inner)?;
+                if let Some(exit) = self.loop_exit(res) {
+                    return Ok(exit);
                 }
-                DioAstStatement::FunctionDefine(define) => {
-                    let f = self.add_script_function(define)?;
-                    if f.0.is_none() {
-                        return Err(RuntimeError::AnonymousFunctionInRoot);
+            },
+            LoopExecuteType::Iter { iter, var, index } => {
+                let iter = self.to_value(iter)?;
+                if let Some(list) = iter_items(iter) {
+                    for (idx, i) in list.into_iter().enumerate() {
+                        let index = index.as_ref().map(|name| (name, idx));
+                        let res = self.execute_iteration(&data.inner, var, i, index)?;
+                        if let Some(exit) = self.loop_exit(res) {
+                            return Ok(exit);
+                        }
                     }
                 }
-                _ => {}
             }
         }
+        Ok(LoopExit::Finished)
+    }
+
+    // `break`, `return` or a block value stop the loop after an iteration.
+    fn loop_exit(&mut self, res: Value) -> Option<LoopExit> {
+        if let Some(value) = self.break_value.take() {
+            return Some(LoopExit::Break(value));
+        }
+        if let Some(value) = self.return_value.take() {
+            return Some(LoopExit::Return(value));
+        }
+        if !res.as_none() {
+            return Some(LoopExit::Return(res));
+        }
+        None
+    }
+
+    // every iteration runs in a fresh scope, loop variables shadow outer variables with the
+    // same name and are dropped after the iteration.
+    fn execute_iteration(
+        &mut self,
+        inner: &[Statement],
+        var: &Symbol,
+        item: Value,
+        index: Option<(&Symbol, usize)>,
+    ) -> Result<Value, RuntimeError> {
+        self.enter_scope(false);
+        if let Some((name, idx)) = index {
+            self.create_var(name, Value::Number(idx as f64))?;
+        }
+        self.create_var(var, item)?;
+        let result = self.execute_scope_without_new_scope(inner);
+        let result = self.run_deferred(result);
         self.leave_scope();
-        Ok(result)
+        result
+    }
+
+    // named functions in the file will be registered as module, module name is the file stem.
+    fn use_file(&mut self, path: &str) -> Result<(), RuntimeError> {
+        if self.read_only {
+            return Err(RuntimeError::ReadOnlyViolation {
+                action: format!("use file `{path}`"),
+            });
+        }
+        let file = self.root_path.join(path);
+        let failed = |reason: String| RuntimeError::FileUseFailed {
+            path: path.to_string(),
+            reason,
+        };
+        let name = file
+            .file_stem()
+            .and_then(|v| v.to_str())
+            .ok_or_else(|| failed("illegal file name".to_string()))?
+            .to_string();
+        // file is bound by its name, it must not replace `std` or a module of another file.
+        let used = self.used_files.iter().any(|v| v.0 == path);
+        if !used && self.modules.contains_key(&Symbol::new(&name)) {
+            return Err(failed(format!(
+                "module `{name}` is already bound, rename the file"
+            )));
+        }
+        let code = std::fs::read_to_string(&file).map_err(|e| failed(e.to_string()))?;
+        let offset = self.used_file_offset(path, &code);
+        let ast = DioscriptAst::from_string_at(&code, offset).map_err(|e| failed(e.to_string()))?;
+        let mut module = ModuleGenerator::new();
+        for stat in ast.stats {
+            if let DioAstStatement::FunctionDefine(f) = stat.kind {
+                if let Some(func_name) = f.name.clone() {
+                    // decorators are looked up from the scope which uses the file.
+                    let item = match self.decorate(f)? {
+                        Value::Function(f) => ModuleItem::Function(f),
+                        value => ModuleItem::Variable(value),
+                    };
+                    module.insert(&func_name, item);
+                }
+            }
+        }
+        self.emit(ExecutionEvent::ModuleUsed {
+            path: vec![name.clone()],
+        });
+        self.bind_module(&name, module);
+        Ok(())
+    }
+
+    // spans of used files start after the ones before them, far beyond any executed script.
+    fn used_file_offset(&mut self, path: &str, code: &str) -> usize {
+        if let Some((_, _, offset)) = self.used_files.iter().find(|v| v.0 == path && v.1 == code) {
+            return *offset;
+        }
+        let offset = match self.used_files.last() {
+            Some((_, code, offset)) => offset + code.len() + 1,
+            None => usize::MAX / 2,
+        };
+        self.used_files
+            .push((path.to_string(), code.to_string(), offset));
+        offset
+    }
+
+    fn locate_used_files(&self, e: &mut RuntimeError) {
+        for (path, code, offset) in self.used_files.iter() {
+            e.locate_file(path, code, *offset);
+        }
+    }
+
+    fn execute_assert(&mut self, assert: &AssertStatement) -> Result<(), RuntimeError> {
+        let (state, operands) = match comparison_parts(&assert.expr) {
+            Some((l, r, mark)) => {
+                let l = self.execute_calculate(l)?;
+                let r = self.execute_calculate(r)?;
+                let state = self.calc_value(l.clone(), r.clone(), mark)?;
+                (state, vec![l, r])
+            }
+            None => {
+                let value = self.execute_calculate(&assert.expr)?;
+                (value.clone(), vec![value])
+            }
+        };
+        if state.to_boolean_data() {
+            return Ok(());
+        }
+        let message = match &assert.message {
+            Some(m) => self.execute_calculate(m)?.to_string(),
+            None => "assertion failed".to_string(),
+        };
+        Err(RuntimeError::AssertionFailed {
+            expr: assert.source.clone(),
+            values: operands
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            message,
+        })
+    }
+
+    // arms are kept free of `?` like `execute_calculate`.
+    fn to_value(&mut self, value: &AstValue) -> Result<Value, RuntimeError> {
+        match value {
+            AstValue::None => Ok(Value::None),
+            AstValue::String(v) => Ok(Value::String(v.as_str().into())),
+            AstValue::Number(v) => Ok(Value::Number(*v)),
+            AstValue::Boolean(v) => Ok(Value::Boolean(*v)),
+            AstValue::List(v) => self.list_value(v),
+            AstValue::Dict(v) => self.dict_value(v),
+            AstValue::Tuple((a, b)) => self.tuple_value(a, b),
+            AstValue::Element(e) => self.to_element(e).map(Value::Element),
+            AstValue::Variable(n) => self.get_var(n).map(|v| v.1),
+            AstValue::Local(local) => self.get_local(local),
+            AstValue::Loop(data) => self.loop_value(data),
+            AstValue::VariableIndex((n, i)) => self.index_value(n, i),
+            AstValue::VariableSlice((n, slice)) => self.slice_value(n, slice),
+            AstValue::FunctionCaller(caller) => self.execute_function(caller),
+            AstValue::FunctionDefine(define) => Ok(self.closure(define.clone())),
+        }
+    }
+
+    fn tuple_value(&mut self, a: &AstValue, b: &AstValue) -> Result<Value, RuntimeError> {
+        let a = self.to_value(a)?;
+        let b = self.to_value(b)?;
+        Ok(Value::Tuple((Box::new(a), Box::new(b))))
+    }
+
+    fn list_value(&mut self, items: &[AstValue]) -> Result<Value, RuntimeError> {
+        let mut res = Vec::new();
+        for i in items {
+            let value = self.to_value(i)?;
+            res.push(value);
+        }
+        Ok(Value::List(Rc::new(res)))
+    }
+
+    fn dict_value(&mut self, items: &HashMap<String, AstValue>) -> Result<Value, RuntimeError> {
+        let mut res = HashMap::new();
+        for (k, v) in items {
+            res.insert(k.clone(), self.to_value(v)?);
+        }
+        Ok(Value::Dict(Rc::new(res)))
+    }
+
+    // loop used as expression: value of `break` stops it, `none` otherwise. `return` leaves the
+    // function around the loop instead.
+    fn loop_value(&mut self, data: &LoopStatement) -> Result<Value, RuntimeError> {
+        match self.execute_loop(data)? {
+            LoopExit::Break(v) => Ok(v),
+            LoopExit::Return(v) => {
+                self.return_value = Some(v);
+                Ok(Value::None)
+            }
+            LoopExit::Finished => Ok(Value::None),
+        }
+    }
+
+    fn index_value(&mut self, name: &Symbol, index: &CalcExpr) -> Result<Value, RuntimeError> {
+        let value = self.get_var(name)?.1;
+        let index = self.execute_calculate(index)?;
+        self.get_from_index(value, index)
+    }
+
+    fn slice_value(&mut self, name: &Symbol, slice: &SliceIndex) -> Result<Value, RuntimeError> {
+        let value = self.get_var(name)?.1;
+        let mut parts = vec![];
+        for part in [&slice.start, &slice.end, &slice.step] {
+            let part = match part {
+                Some(v) => match self.execute_calculate(v)? {
+                    Value::Number(num) => Some(num),
+                    Value::None => None,
+                    other => {
+                        return Err(RuntimeError::IllegalIndexType {
+                            index_type: other.value_name(),
+                            value_type: value.value_name(),
+                        })
+                    }
+                },
+                None => None,
+            };
+            parts.push(part);
+        }
+        self.get_from_slice(value, parts[0], parts[1], parts[2])
+    }
+
+    // borrow value if it contains no reference, so read-only access doesn't copy.
+    fn deref_ref<'a>(&'a self, value: &'a Value) -> Result<Cow<'a, Value>, RuntimeError> {
+        match value {
+            Value::Reference(id) => match self.data.get(*id) {
+                Some(DataType::Variable(v)) if !v.has_reference() => Ok(Cow::Borrowed(v)),
+                Some(DataType::Variable(v)) => Ok(Cow::Owned(self.deref_value(v.clone())?)),
+                None => Err(RuntimeError::PoniterDataNotFound {
+                    name: format!("{:?}", id),
+                }),
+            },
+            _ if !value.has_reference() => Ok(Cow::Borrowed(value)),
+            _ => Ok(Cow::Owned(self.deref_value(value.clone())?)),
+        }
     }
 
-    fn to_value(&mut self, value: AstValue) -> Result<Value, RuntimeError> {
-        match value {
-            AstValue::None => Ok(Value::None),
-            AstValue::String(v) => Ok(Value::String(v)),
-            AstValue::Number(v) => Ok(Value::Number(v)),
-            AstValue::Boolean(v) => Ok(Value::Boolean(v)),
-            AstValue::List(v) => {
-                let mut res = Vec::new();
-                for i in v {
-                    let value = self.to_value(i)?;
-                    res.push(value);
-                }
-                Ok(Value::List(res))
-            }
-            AstValue::Dict(v) => {
-                let mut res = HashMap::new();
-                for (k, v) in v {
-                    res.insert(k, self.to_value(v)?);
-                }
-                Ok(Value::Dict(res))
-            }
-            AstValue::Tuple((a, b)) => {
-                let a = self.to_value(*a)?;
-                let b = self.to_value(*b)?;
-                Ok(Value::Tuple((Box::new(a), Box::new(b))))
-            }
-            AstValue::Element(e) => {
-                let element = self.to_element(e)?;
-                Ok(Value::Element(element))
-            }
-            AstValue::Variable(n) => {
-                let value = self.get_var(&n)?.1;
-                Ok(value)
-            }
-            AstValue::VariableIndex((n, i)) => {
-                let value = self.to_value(AstValue::Variable(n))?;
-                let index = self.to_value(*i)?;
-                let data = self.get_from_index(value, index)?;
-                Ok(data)
-            }
-            AstValue::FunctionCaller(caller) => {
-                let data = self.execute_function(caller)?;
-                Ok(data)
-            }
-            AstValue::FunctionDefine(define) => {
-                Ok(Value::Function(types::FunctionType::DScript(define)))
-            }
+    fn check_type(
+        &self,
+        target: &str,
+        value: &Value,
+        expected: Option<&String>,
+    ) -> Result<(), RuntimeError> {
+        let expected = match expected {
+            Some(expected) if self.strict_types => expected,
+            _ => return Ok(()),
+        };
+        let value = match value {
+            Value::Reference(_) => Cow::Owned(self.deref_value(value.clone())?),
+            _ => Cow::Borrowed(value),
+        };
+        if type_matches(&value, expected) {
+            Ok(())
+        } else {
+            Err(RuntimeError::TypeMismatch {
+                target: target.to_string(),
+                expected: expected.to_string(),
+                found: value.value_name(),
+            })
         }
     }
 
     fn deref_value(&self, value: Value) -> Result<Value, RuntimeError> {
+        if !value.has_reference() {
+            return Ok(value);
+        }
         match value {
             Value::List(list) => {
                 let mut new = vec![];
-                for i in list {
-                    let v = self.deref_value(i)?;
+                for i in list.iter() {
+                    let v = self.deref_value(i.clone())?;
                     new.push(v);
                 }
-                Ok(Value::List(new))
+                Ok(Value::List(Rc::new(new)))
             }
             Value::Dict(dict) => {
                 let mut new = HashMap::new();
-                for (k, v) in dict {
-                    let v = self.deref_value(v)?;
-                    new.insert(k, v);
+                for (k, v) in dict.iter() {
+                    let v = self.deref_value(v.clone())?;
+                    new.insert(k.clone(), v);
                 }
-                Ok(Value::Dict(new))
+                Ok(Value::Dict(Rc::new(new)))
             }
             Value::Tuple(tuple) => {
                 let first = self.deref_value(*tuple.0)?;
@@ -290,17 +1278,14 @@ impl Runtime {
                 Ok(Value::Tuple((Box::new(first), Box::new(second))))
             }
             Value::Reference(id) => {
-                let data = self
-                    .data
-                    .get(&id)
-                    .ok_or(RuntimeError::PoniterDataNotFound {
-                        name: id.to_string(),
-                    })?;
+                let data = self.data.get(id).ok_or(RuntimeError::PoniterDataNotFound {
+                    name: format!("{:?}", id),
+                })?;
                 #[allow(unreachable_patterns)]
                 match data {
                     DataType::Variable(v) => Ok(v.clone()),
                     _ => Err(RuntimeError::PoniterDataNotFound {
-                        name: id.to_string(),
+                        name: format!("{:?}", id),
                     }),
                 }
             }
@@ -308,49 +1293,162 @@ impl Runtime {
         }
     }
 
-    fn execute_function(&mut self, caller: FunctionCall) -> Result<Value, RuntimeError> {
-        let name = caller.name;
-        let params = caller.arguments;
+    fn execute_function(&mut self, caller: &FunctionCall) -> Result<Value, RuntimeError> {
         let mut par = vec![];
-        for i in params {
+        for i in caller.arguments.iter() {
             let v = self.to_value(i)?;
             par.push(v);
         }
+        self.execute_function_by_name(&caller.name, par)
+    }
+
+    fn execute_function_by_name(
+        &mut self,
+        name: &FunctionName,
+        par: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if let FunctionName::Single(n) = name {
+            if let Some((_, Value::Object(define))) = self.var_ref(n) {
+                return self.construct_object(define.clone(), par);
+            }
+        }
 
         let func = self.get_function(name)?;
+        self.emit(ExecutionEvent::FunctionCalled {
+            name: name.to_string(),
+        });
+        self.execute_function_by_ft(func, par)
+    }
 
-        match func {
-            types::FunctionType::DScript(f) => {
-                let f = f.clone();
-                match &f.params {
-                    dioscript_parser::ast::ParamsType::Variable(v) => {
-                        self.set_var(&v, Value::List(par))?;
-                    }
-                    dioscript_parser::ast::ParamsType::List(v) => {
-                        if v.len() != par.len() {
-                            return Err(RuntimeError::IllegalArgumentsNumber {
-                                need: v.len() as i16,
-                                provided: par.len() as i16,
-                            });
-                        }
-                        for (i, v) in v.iter().enumerate() {
-                            self.set_var(v, par.get(i).unwrap().clone())?;
-                        }
-                    }
+    // object instance is a dict with fields, methods and `__object__` name tag.
+    fn construct_object(
+        &mut self,
+        define: ObjectDefine,
+        par: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut instance = HashMap::new();
+        instance.insert(
+            "__object__".to_string(),
+            Value::String(define.name.as_str().into()),
+        );
+        self.enter_scope(false);
+        for field in define.fields {
+            let value = self
+                .execute_calculate(&field.expr)
+                .and_then(|v| self.deref_value(v));
+            match value {
+                Ok(value) => {
+                    instance.insert(field.name.to_string(), value);
+                }
+                Err(e) => {
+                    self.leave_scope();
+                    return Err(e);
                 }
-                let result = self.execute_scope(f.inner)?;
-                return Ok(result);
             }
-            types::FunctionType::Rusty((f, need_param_num)) => {
-                if need_param_num != -1 && (par.len() as i32) != need_param_num {
+        }
+        self.leave_scope();
+        for method in define.methods {
+            let name = method.name.clone().unwrap_or_default();
+            instance.insert(name, self.decorate(method)?);
+        }
+        let instance = Value::Dict(Rc::new(instance));
+        match instance.as_dict().unwrap().get("init") {
+            Some(Value::Function(init)) => {
+                let (_, instance) = self.execute_method(init.clone(), instance, par)?;
+                Ok(instance)
+            }
+            _ => {
+                if !par.is_empty() {
                     return Err(RuntimeError::IllegalArgumentsNumber {
-                        need: need_param_num as i16,
+                        need: 0,
                         provided: par.len() as i16,
                     });
                 }
-                return Ok(f(self, par));
+                Ok(instance)
+            }
+        }
+    }
+
+    // execute function with `self` binding, return the result and `self` value after execute.
+    fn execute_method(
+        &mut self,
+        func: FunctionType,
+        this: Value,
+        par: Vec<Value>,
+    ) -> Result<(Value, Value), RuntimeError> {
+        let (f, captures, uses) = match func {
+            FunctionType::DScript(f) => (f, HashMap::new(), HashMap::new()),
+            FunctionType::Closure(closure) => closure,
+            FunctionType::Rusty(_) => {
+                return self
+                    .execute_function_by_ft(func, par)
+                    .map(|result| (result, this));
             }
+        };
+        self.report.functions_called += 1;
+        self.enter_function_scope(captures, uses)?;
+        self.enter_frame(&f);
+        let result = self.create_var(&Symbol::new("self"), this).and_then(|id| {
+            let result = self.execute_script_function(&f, par)?;
+            let this = self.deref_value(Value::Reference(id))?;
+            Ok((result, this))
+        });
+        let result = self.run_deferred(result);
+        let result = self.leave_frame(result);
+        self.function_scopes.pop();
+        self.leave_scope();
+        result
+    }
+
+    fn enter_frame(&mut self, f: &FunctionDefine) {
+        let function = f.name.clone().unwrap_or("<anonymous>".to_string());
+        self.call_stack.push(StackFrame {
+            function,
+            call_site: self.statement_span,
+            location: None,
+        });
+    }
+
+    // error leaving the innermost function gets the whole stack, outer functions keep it.
+    fn leave_frame<T>(&mut self, result: Result<T, RuntimeError>) -> Result<T, RuntimeError> {
+        let result = match result {
+            Err(e) if e.trace().is_empty() => Err(RuntimeError::Traced {
+                error: Box::new(e),
+                trace: self.call_stack.iter().rev().cloned().collect(),
+            }),
+            result => result,
+        };
+        // statements of the function are done, the caller's one is running again.
+        if let Some(frame) = self.call_stack.pop() {
+            self.statement_span = frame.call_site;
         }
+        result
+    }
+
+    // script functions which are running, innermost last.
+    pub fn call_stack(&self) -> &[StackFrame] {
+        &self.call_stack
+    }
+
+    // apply `@` decorators to function, the nearest one first.
+    fn decorate(&mut self, mut define: FunctionDefine) -> Result<Value, RuntimeError> {
+        let decorators = std::mem::take(&mut define.decorators);
+        let mut value = self.closure(define);
+        for decorator in decorators.iter().rev() {
+            value = self.execute_function_by_name(decorator, vec![value])?;
+        }
+        self.deref_value(value)
+    }
+
+    pub fn call_function(
+        &mut self,
+        func: FunctionType,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        self.execute_function_by_ft(func, args).map_err(|mut e| {
+            self.locate_used_files(&mut e);
+            e
+        })
     }
 
     fn execute_function_by_ft(
@@ -358,93 +1456,218 @@ impl Runtime {
         func: FunctionType,
         par: Vec<Value>,
     ) -> Result<Value, RuntimeError> {
-        match func {
-            types::FunctionType::DScript(f) => {
-                let f = f.clone();
-                match &f.params {
-                    dioscript_parser::ast::ParamsType::Variable(v) => {
-                        self.set_var(&v, Value::List(par))?;
-                    }
-                    dioscript_parser::ast::ParamsType::List(v) => {
-                        if v.len() != par.len() {
-                            return Err(RuntimeError::IllegalArgumentsNumber {
-                                need: v.len() as i16,
-                                provided: par.len() as i16,
-                            });
-                        }
-                        for (i, v) in v.iter().enumerate() {
-                            self.set_var(v, par.get(i).unwrap().clone())?;
-                        }
-                    }
-                }
-                let result = self.execute_scope(f.inner)?;
-                return Ok(result);
-            }
+        self.report.functions_called += 1;
+        let (f, captures, uses) = match func {
+            types::FunctionType::DScript(f) => (f, HashMap::new(), HashMap::new()),
+            types::FunctionType::Closure(closure) => closure,
             types::FunctionType::Rusty((f, need_param_num)) => {
-                if need_param_num != -1 && (par.len() as i32) != need_param_num {
-                    return Err(RuntimeError::IllegalArgumentsNumber {
-                        need: need_param_num as i16,
-                        provided: par.len() as i16,
-                    });
-                }
-                return Ok(f(self, par));
+                return self.execute_rusty_function(f, need_param_num, par);
+            }
+        };
+        self.enter_function_scope(captures, uses)?;
+        self.enter_frame(&f);
+        let result = self.execute_script_function(&f, par);
+        let result = self.run_deferred(result);
+        let result = self.leave_frame(result);
+        self.function_scopes.pop();
+        self.leave_scope();
+        result
+    }
+
+    fn execute_rusty_function(
+        &mut self,
+        f: module::RustyFunction,
+        need_param_num: i32,
+        par: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        if need_param_num != -1 && (par.len() as i32) != need_param_num {
+            return Err(RuntimeError::IllegalArgumentsNumber {
+                need: need_param_num as i16,
+                provided: par.len() as i16,
+            });
+        }
+        let value = f(self, par);
+        if let Some(e) = self.host_error.take() {
+            return Err(e);
+        }
+        self.check_size(&value)?;
+        Ok(value)
+    }
+
+    // captured variables are bound in function scope, they share data with where they come from.
+    fn enter_function_scope(
+        &mut self,
+        captures: HashMap<Symbol, DataId>,
+        uses: UsedModules,
+    ) -> Result<(), RuntimeError> {
+        if let Some(max) = self.limits.max_depth {
+            if self.function_scopes.len() >= max {
+                return Err(RuntimeError::ExecutionLimitExceeded {
+                    limit: format!("call depth of {max} functions is reached"),
+                });
+            }
+        }
+        self.enter_scope(false);
+        self.function_scopes.push(self.scopes.len() - 1);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.uses = uses;
+            let captured = captures.iter().map(|(k, v)| (k.clone(), *v));
+            scope.data.extend(captured);
+            scope.captures = captures;
+        }
+        Ok(())
+    }
+
+    fn execute_script_function(
+        &mut self,
+        f: &Rc<FunctionDefine>,
+        par: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        self.bind_params(f, par)?;
+        // `return` pending from caller belongs to caller.
+        let pending = self.return_value.take();
+        let result = self.execute_body(&f.inner, Some(f));
+        let returned = std::mem::replace(&mut self.return_value, pending);
+        let result = returned.unwrap_or(result?);
+        if self.break_value.take().is_some() {
+            return Err(RuntimeError::BreakOutsideLoop);
+        }
+        if let Some(expected) = &f.return_type {
+            let target = format!("return value of {}", function_label(&f.name));
+            self.check_type(&target, &result, Some(expected))?;
+        }
+        Ok(result)
+    }
+
+    fn bind_params(&mut self, f: &FunctionDefine, par: Vec<Value>) -> Result<(), RuntimeError> {
+        let (fixed, rest) = match &f.params {
+            ParamsType::Variable(fixed, rest) => (fixed, Some(rest)),
+            ParamsType::List(fixed) => (fixed, None),
+        };
+        let illegal = match rest {
+            Some(_) => par.len() < fixed.len(),
+            None => par.len() != fixed.len(),
+        };
+        if illegal {
+            return Err(RuntimeError::IllegalArgumentsNumber {
+                need: fixed.len() as i16,
+                provided: par.len() as i16,
+            });
+        }
+        let mut par = par.into_iter();
+        for (index, (name, value)) in fixed.iter().zip(par.by_ref()).enumerate() {
+            let expected = match f.param_types.get(index) {
+                Some(Some(expected)) => Some(expected),
+                _ => None,
+            };
+            if let Some(expected) = expected {
+                let target = format!("parameter `{name}` of {}", function_label(&f.name));
+                self.check_type(&target, &value, Some(expected))?;
+            }
+            let id = self.create_var(name, value)?;
+            if let Some(expected) = expected {
+                self.types.insert(id, expected.clone());
             }
+            self.push_slot(name, id);
         }
+        if let Some(rest) = rest {
+            let id = self.create_var(rest, Value::List(Rc::new(par.collect())))?;
+            self.push_slot(rest, id);
+        }
+        Ok(())
     }
 
-    fn get_function(&self, name: FunctionName) -> Result<FunctionType, RuntimeError> {
+    fn get_function(&self, name: &FunctionName) -> Result<FunctionType, RuntimeError> {
         match name {
             FunctionName::Single(name) => {
-                let info = self.get_var(&name);
-                if let Ok((_, Value::Function(f))) = info {
-                    Ok(f)
+                if let Some((_, Value::Function(f))) = self.var_ref(name) {
+                    Ok(f.clone())
                 } else {
-                    let function = self.get_module_value(vec![name.clone()]);
+                    let function = self.get_module_value(std::slice::from_ref(name));
                     if let Ok(ModuleItem::Function(f)) = function {
                         Ok(f)
                     } else {
-                        Err(RuntimeError::FunctionNotFound { name })
+                        Err(RuntimeError::FunctionNotFound {
+                            name: name.to_string(),
+                        })
                     }
                 }
             }
             FunctionName::Namespace(namespace) => {
-                let v = self.get_module_value(namespace.clone())?;
+                let v = self.get_module_value(namespace)?;
                 if let ModuleItem::Function(f) = v {
                     Ok(f)
                 } else {
                     Err(RuntimeError::FunctionNotFound {
-                        name: namespace.join("::"),
+                        name: symbol::join(namespace, "::"),
                     })
                 }
             }
         }
     }
 
-    fn get_module_value(&self, mut namespace: Vec<String>) -> Result<ModuleItem, RuntimeError> {
-        let data = self.load_from_module(namespace.clone());
+    fn get_module_value(&self, namespace: &[Symbol]) -> Result<ModuleItem, RuntimeError> {
+        let used = self.used_module(&namespace[0]);
+        self.find_module_value(namespace, used)
+    }
+
+    // module path of `use` statement visible here, inner scopes first. scopes of callers are
+    // skipped: a function sees its own `use`s, those captured where it is defined and top-level
+    // ones.
+    fn used_module(&self, name: &Symbol) -> Option<&Vec<Symbol>> {
+        let function = self.function_scopes.last().copied().unwrap_or(0);
+        let top = self.function_scopes.first().copied().unwrap_or(0);
+        self.scopes[function..]
+            .iter()
+            .rev()
+            .chain(self.scopes[..top].iter().rev())
+            .find_map(|scope| scope.uses.get(name))
+            .or_else(|| self.namespace_use.get(name))
+    }
+
+    // `used`: full module path of the first segment, from `use` statements.
+    fn find_module_value(
+        &self,
+        namespace: &[Symbol],
+        used: Option<&Vec<Symbol>>,
+    ) -> Result<ModuleItem, RuntimeError> {
+        let data = self.load_from_module(namespace);
         match data {
             Ok(v) => {
-                return Ok(v);
+                self.check_capability(namespace)?;
+                Ok(v)
             }
-            Err(_) => {
-                let v = self.namespace_use.get(&namespace[0]);
-                if let Some(used) = v {
-                    if used.last().unwrap() == &namespace[0] {
-                        namespace.remove(0);
-                    }
-                    let module_path = used.iter().chain(namespace.iter()).cloned().collect();
-                    let v = self.load_from_module(module_path)?;
-                    return Ok(v);
+            Err(e) => {
+                if let Some(used) = used {
+                    // first segment is the used name, it may be an alias.
+                    let module_path: Vec<Symbol> =
+                        used.iter().chain(namespace[1..].iter()).cloned().collect();
+                    let v = self.load_from_module(&module_path)?;
+                    self.check_capability(&module_path)?;
+                    Ok(v)
+                } else if self.modules.contains_key(&namespace[0]) {
+                    // keep the error of a bound module, e.g. function not found in it.
+                    Err(e)
                 } else {
-                    return Err(RuntimeError::ModuleNotFound {
+                    Err(RuntimeError::ModuleNotFound {
                         module: namespace[0].to_string(),
-                    });
+                    })
                 }
             }
         }
     }
 
-    fn load_from_module(&self, namespace: Vec<String>) -> Result<ModuleItem, RuntimeError> {
+    // only `std` module is available in read-only mode, other modules are host capabilities.
+    fn check_capability(&self, namespace: &[Symbol]) -> Result<(), RuntimeError> {
+        if self.read_only && namespace[0] != "std" {
+            return Err(RuntimeError::ReadOnlyViolation {
+                action: format!("call `{}`", symbol::join(namespace, "::")),
+            });
+        }
+        Ok(())
+    }
+
+    fn load_from_module(&self, namespace: &[Symbol]) -> Result<ModuleItem, RuntimeError> {
         let map = &self.modules;
         let mut cur_item: ModuleItem = map
             .get(&namespace[0])
@@ -473,214 +1696,479 @@ impl Runtime {
                 }
             }
         }
-        let r = cur_item.clone();
-        Ok(r)
+        let r = cur_item.clone();
+        Ok(r)
+    }
+
+    // arms hand over to other functions instead of using `?` here: this is part of every
+    // script function call, and a debug build keeps the temporaries of each arm on the stack.
+    fn execute_calculate(&mut self, expr: &CalcExpr) -> Result<Value, RuntimeError> {
+        if let Some((l, r, mark)) = binary_operands(expr) {
+            return self.execute_binary(l, r, mark);
+        }
+        match expr {
+            CalcExpr::Value(v) => self.to_value(v),
+            CalcExpr::LinkExpr(v) => self.execute_link_expr(v),
+            CalcExpr::Pipe(value, call) => self.execute_pipe(value, call),
+            CalcExpr::Call(f, arguments) => self.execute_call(f, arguments),
+            CalcExpr::TypeOf(v) => self
+                .calculate_data(v)
+                .map(|v| Value::String(v.value_name().into())),
+            CalcExpr::Is(v, name) => self
+                .calculate_data(v)
+                .map(|v| Value::Boolean(type_matches(&v, name))),
+            CalcExpr::Tuple(a, b) => self.execute_tuple(a, b),
+            CalcExpr::Coalesce(l, r) => self.execute_coalesce(l, r),
+            CalcExpr::Mod(_, _) => Ok(Value::Boolean(false)),
+            _ => unreachable!("binary operators are handled above"),
+        }
+    }
+
+    fn execute_binary(
+        &mut self,
+        l: &CalcExpr,
+        r: &CalcExpr,
+        mark: CalculateMark,
+    ) -> Result<Value, RuntimeError> {
+        let l = self.execute_calculate(l)?;
+        let r = self.execute_calculate(r)?;
+        self.calc_value(l, r, mark)
+    }
+
+    fn execute_tuple(&mut self, a: &CalcExpr, b: &CalcExpr) -> Result<Value, RuntimeError> {
+        let a = self.execute_calculate(a)?;
+        let b = self.execute_calculate(b)?;
+        Ok(Value::Tuple((Box::new(a), Box::new(b))))
+    }
+
+    fn execute_pipe(
+        &mut self,
+        value: &CalcExpr,
+        call: &FunctionCall,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.execute_calculate(value)?;
+        let mut par = vec![self.deref_value(value)?];
+        for i in call.arguments.iter() {
+            par.push(self.to_value(i)?);
+        }
+        self.execute_function_by_name(&call.name, par)
+    }
+
+    fn execute_call(
+        &mut self,
+        f: &CalcExpr,
+        arguments: &[AstValue],
+    ) -> Result<Value, RuntimeError> {
+        let f = self.execute_calculate(f)?;
+        let func = match self.deref_value(f)? {
+            Value::Function(func) => func,
+            v => {
+                return Err(RuntimeError::NotCallable {
+                    value: v.value_name(),
+                })
+            }
+        };
+        let mut par = vec![];
+        for i in arguments.iter() {
+            par.push(self.to_value(i)?);
+        }
+        self.execute_function_by_ft(func, par)
+    }
+
+    fn execute_coalesce(&mut self, l: &CalcExpr, r: &CalcExpr) -> Result<Value, RuntimeError> {
+        // missing dict key or list index is treated as `none` here, errors raised inside
+        // called functions are not.
+        let l = match self.execute_calculate(l) {
+            Ok(v) => self.deref_value(v)?,
+            Err(e)
+                if e.trace().is_empty()
+                    && matches!(
+                        e.root(),
+                        RuntimeError::UnknownAttribute { .. } | RuntimeError::IndexNotFound { .. }
+                    ) =>
+            {
+                Value::None
+            }
+            Err(e) => return Err(e),
+        };
+        if l.as_none() {
+            self.execute_calculate(r)
+        } else {
+            Ok(l)
+        }
+    }
+
+    // dict value can overload operator by `__add__`, `__eq__` ... function fields.
+    fn calc_value(
+        &mut self,
+        l: Value,
+        r: Value,
+        mark: CalculateMark,
+    ) -> Result<Value, RuntimeError> {
+        if matches!(l, Value::Dict(_)) || matches!(r, Value::Dict(_)) {
+            if let Some(value) = self.overload(&l, &r, &mark)? {
+                return Ok(value);
+            }
+        }
+        let value = l.calc(&r, mark)?;
+        self.check_size(&value)?;
+        Ok(value)
+    }
+
+    // method of object operand which handles the operator. the right operand is tried when the
+    // left one has no method: `5 + v` calls `v.__radd__(5)`, `w == v` calls `v.__eq__(w)`.
+    // `!=` is `!__eq__`, and comparisons without own method are derived from the other side
+    // or from `__lt__`/`__gt__`: `v > w` is `w < v`, `v >= w` is `!(v < w)`.
+    fn overload(
+        &mut self,
+        l: &Value,
+        r: &Value,
+        mark: &CalculateMark,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let name = match overload_name(mark) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let (flipped, negated) = match mark {
+            CalculateMark::Equal => ("__eq__", None),
+            CalculateMark::NotEqual => ("__ne__", Some(CalculateMark::Equal)),
+            CalculateMark::Small => ("__gt__", None),
+            CalculateMark::Large => ("__lt__", None),
+            CalculateMark::SmallOrEqual => ("__ge__", Some(CalculateMark::Large)),
+            CalculateMark::LargeOrEqual => ("__le__", Some(CalculateMark::Small)),
+            _ => {
+                if let Some(v) = self.call_operator(l, name, r)? {
+                    return Ok(Some(v));
+                }
+                return self.call_operator(r, &format!("__r{}", &name[2..]), l);
+            }
+        };
+        if let Some(v) = self.call_comparison(l, name, r)? {
+            return Ok(Some(v));
+        }
+        if let Some(v) = self.call_comparison(r, flipped, l)? {
+            return Ok(Some(v));
+        }
+        match negated {
+            Some(mark) => Ok(self
+                .overload(l, r, &mark)?
+                .map(|v| Value::Boolean(!v.to_boolean_data()))),
+            None => Ok(None),
+        }
+    }
+
+    fn call_operator(
+        &mut self,
+        this: &Value,
+        name: &str,
+        other: &Value,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let f = match this {
+            Value::Dict(dict) => match dict.get(name) {
+                Some(Value::Function(f)) => f.clone(),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        self.execute_function_by_ft(f, vec![this.clone(), other.clone()])
+            .map(Some)
+    }
+
+    fn call_comparison(
+        &mut self,
+        this: &Value,
+        name: &str,
+        other: &Value,
+    ) -> Result<Option<Value>, RuntimeError> {
+        match self.call_operator(this, name, other)? {
+            Some(Value::Boolean(v)) => Ok(Some(Value::Boolean(v))),
+            Some(v) => Err(RuntimeError::TypeMismatch {
+                target: format!("result of `{name}`"),
+                expected: "boolean".to_string(),
+                found: v.value_name(),
+            }),
+            None => Ok(None),
+        }
+    }
+
+    fn execute_link_expr(&mut self, v: &LinkExpr) -> Result<Value, RuntimeError> {
+        // variable the chain starts from and fields to the current value, a method which
+        // changes `self` writes it back there: `counter.inc()`, `page.counter.inc()`,
+        // `counters[0].inc()`.
+        let (mut this, root, start) = self.link_start(&v.this)?;
+        let mut path = root.as_ref().map(|_| start);
+        for op in v.list.iter() {
+            match op {
+                dioscript_parser::parser::LinkExprPart::Optional => {
+                    this = self.deref_value(this)?;
+                    if this.as_none() {
+                        return Ok(Value::None);
+                    }
+                }
+                dioscript_parser::parser::LinkExprPart::Field(field) => {
+                    if let Some(path) = path.as_mut() {
+                        path.push(Value::String(field.as_str().into()));
+                    }
+                    this = self.get_field(&this, field)?;
+                }
+                dioscript_parser::parser::LinkExprPart::FunctionCall(call) => {
+                    // the path ends at a call, its result is a new value.
+                    let path = path.take();
+                    this = self.call_link_method(this, call, root.as_ref().zip(path.as_ref()))?;
+                }
+            }
+        }
+        self.deref_value(this)
+    }
+
+    // `list.0`, `dict.key`, `tuple.1` and element `name`, `attributes` or `content`.
+    fn get_field(&self, this: &Value, field: &str) -> Result<Value, RuntimeError> {
+        let target = self.deref_ref(this)?;
+        let next = match target.as_ref() {
+            Value::List(list) => {
+                let index = field.parse::<usize>();
+                if let Ok(index) = index {
+                    if index < list.len() {
+                        list.get(index).unwrap().clone()
+                    } else {
+                        return Err(RuntimeError::UnknownAttribute {
+                            attr: field.to_string(),
+                            value: target.value_name(),
+                        });
+                    }
+                } else {
+                    return Err(RuntimeError::UnknownAttribute {
+                        attr: field.to_string(),
+                        value: target.value_name(),
+                    });
+                }
+            }
+            Value::Dict(dict) => {
+                if dict.contains_key(field) {
+                    dict.get(field).unwrap().clone()
+                } else {
+                    return Err(RuntimeError::UnknownAttribute {
+                        attr: field.to_string(),
+                        value: target.value_name(),
+                    });
+                }
+            }
+            Value::Tuple(tuple) => match field {
+                "0" => *tuple.0.clone(),
+                "1" => *tuple.1.clone(),
+                _ => {
+                    return Err(RuntimeError::UnknownAttribute {
+                        attr: field.to_string(),
+                        value: target.value_name(),
+                    });
+                }
+            },
+            Value::Element(element) => match field {
+                "name" => Value::String(element.name.as_str().into()),
+                "attributes" => Value::Dict(Rc::new(element.attributes.clone())),
+                "content" => {
+                    let mut content = vec![];
+                    for i in &element.content {
+                        match i {
+                            ElementContentType::Children(c) => {
+                                content.push(Value::Element(c.clone()));
+                            }
+                            ElementContentType::Content(c) => {
+                                content.push(Value::String(c.as_str().into()));
+                            }
+                        }
+                    }
+                    Value::List(Rc::new(content))
+                }
+                _ => {
+                    return Err(RuntimeError::UnknownAttribute {
+                        attr: field.to_string(),
+                        value: target.value_name(),
+                    });
+                }
+            },
+            _ => {
+                return Err(RuntimeError::UnknownAttribute {
+                    attr: field.to_string(),
+                    value: target.value_name(),
+                });
+            }
+        };
+        Ok(next)
+    }
+
+    fn call_link_method(
+        &mut self,
+        this: Value,
+        call: &FunctionCall,
+        root: Option<(&LinkRoot, &Vec<Value>)>,
+    ) -> Result<Value, RuntimeError> {
+        let this = self.deref_value(this)?;
+        match dict_method(&this, &call.name.as_single()) {
+            Some(method) => self.call_dict_method(method, this, call, root),
+            None => self.call_type_method(this, call),
+        }
+    }
+
+    fn call_dict_method(
+        &mut self,
+        method: FunctionType,
+        this: Value,
+        call: &FunctionCall,
+        root: Option<(&LinkRoot, &Vec<Value>)>,
+    ) -> Result<Value, RuntimeError> {
+        let mut params = vec![];
+        for i in call.arguments.iter() {
+            params.push(self.to_value(i)?);
+        }
+        self.emit(ExecutionEvent::FunctionCalled {
+            name: call.name.as_single(),
+        });
+        let receiver = this.clone();
+        let (result, updated) = self.execute_method(method, this, params)?;
+        if let Some(((name, id), path)) = root {
+            if updated != receiver {
+                self.write_back(name, *id, path, updated)?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn call_type_method(
+        &mut self,
+        this: Value,
+        call: &FunctionCall,
+    ) -> Result<Value, RuntimeError> {
+        let method = self.get_type_method(&this, &call.name.as_single())?;
+        self.emit(ExecutionEvent::FunctionCalled {
+            name: format!("{}.{}", this.value_name(), call.name.as_single()),
+        });
+        let mut params = vec![this];
+        for i in call.arguments.iter() {
+            let v = self.to_value(i)?;
+            params.push(v);
+        }
+        self.execute_function_by_ft(method, params)
+    }
+
+    // the index of `items[0].inc()` is evaluated once, it starts the path of the chain.
+    fn link_start(
+        &mut self,
+        this: &CalcExpr,
+    ) -> Result<(Value, Option<LinkRoot>, Vec<Value>), RuntimeError> {
+        let root = match this {
+            CalcExpr::Value(AstValue::Variable(name)) => {
+                self.var_ref(name).map(|(id, _)| (name.clone(), id))
+            }
+            CalcExpr::Value(AstValue::Local(local)) => {
+                self.local_id(local).map(|id| (local.name.clone(), id))
+            }
+            CalcExpr::Value(AstValue::VariableIndex((name, index))) => {
+                let (id, value) = self.get_var(name)?;
+                let index = self.execute_calculate(index)?;
+                let index = self.deref_value(index)?;
+                let value = self.get_from_index(value, index.clone())?;
+                return Ok((value, Some((name.clone(), id)), vec![index]));
+            }
+            _ => None,
+        };
+        let value = self.execute_calculate(this)?;
+        Ok((value, root, vec![]))
+    }
+
+    // store changed `self` of a method at `path` of the variable it was loaded from.
+    fn write_back(
+        &mut self,
+        name: &Symbol,
+        id: DataId,
+        path: &[Value],
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        self.check_assign(name)?;
+        let mut target = match self.data.get_mut(id) {
+            Some(DataType::Variable(v)) => std::mem::replace(v, Value::None),
+            None => return Ok(()),
+        };
+        let result = set_index(&mut target, path, value);
+        if let Some(DataType::Variable(v)) = self.data.get_mut(id) {
+            *v = target;
+        }
+        result
     }
 
-    fn execute_calculate(&mut self, expr: CalcExpr) -> Result<Value, RuntimeError> {
-        match expr {
-            CalcExpr::Value(v) => Ok(self.to_value(v)?),
-            CalcExpr::LinkExpr(v) => Ok(self.execute_link_expr(v)?),
-            CalcExpr::Add(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Plus)
-            }
-            CalcExpr::Sub(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Minus)
-            }
-            CalcExpr::Mul(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Multiply)
-            }
-            CalcExpr::Div(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Divide)
-            }
-            CalcExpr::Mod(_, _) => Ok(Value::Boolean(false)),
-            CalcExpr::Eq(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Equal)
-            }
-            CalcExpr::Ne(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::NotEqual)
-            }
-            CalcExpr::Gt(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Large)
-            }
-            CalcExpr::Lt(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Small)
-            }
-            CalcExpr::Ge(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::LargeOrEqual)
-            }
-            CalcExpr::Le(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::SmallOrEqual)
-            }
-            CalcExpr::And(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::And)
-            }
-            CalcExpr::Or(l, r) => {
-                let l = self.execute_calculate(*l)?;
-                let r = self.execute_calculate(*r)?;
-                l.calc(&r, CalculateMark::Or)
-            },
+    // type method is loaded from bind methods or `std::{type}` module:
+    // `"abc".len()` => `std::string::len("abc")`
+    fn get_type_method(&self, value: &Value, name: &str) -> Result<FunctionType, RuntimeError> {
+        if let Some(f) = self
+            .type_methods
+            .get(&value.value_name())
+            .and_then(|v| v.get(name))
+        {
+            return Ok(f.clone());
+        }
+        let namespace = [
+            Symbol::new("std"),
+            Symbol::new(&value.value_name()),
+            Symbol::new(name),
+        ];
+        if let Ok(ModuleItem::Function(f)) = self.load_from_module(&namespace) {
+            Ok(f)
+        } else {
+            Err(RuntimeError::UnknownMethod {
+                method: name.to_string(),
+                value: value.value_name(),
+            })
         }
     }
 
-    fn execute_link_expr(&mut self, v: LinkExpr) -> Result<Value, RuntimeError> {
-        let mut this = self.to_value(v.this)?;
-        let list = v.list;
-        for op in list {
-            match op {
-                dioscript_parser::parser::LinkExprPart::Field(field) => {
-                    this = self.deref_value(this.clone())?;
-                    match &this {
-                        Value::List(list) => {
-                            let index = field.parse::<usize>();
-                            if let Ok(index) = index {
-                                if list.len() - 1 >= index {
-                                    this = list.get(index).unwrap().clone();
-                                } else {
-                                    return Err(RuntimeError::UnknownAttribute {
-                                        attr: field,
-                                        value: this.value_name(),
-                                    });
-                                }
-                            } else {
-                                return Err(RuntimeError::UnknownAttribute {
-                                    attr: field,
-                                    value: this.value_name(),
-                                });
-                            }
-                        }
-                        Value::Dict(dict) => {
-                            if dict.contains_key(&field) {
-                                this = dict.get(&field).unwrap().clone();
-                            } else {
-                                return Err(RuntimeError::UnknownAttribute {
-                                    attr: field,
-                                    value: this.value_name(),
-                                });
-                            }
-                        }
-                        Value::Tuple(tuple) => match field.as_str() {
-                            "0" => {
-                                this = *tuple.0.clone();
-                            }
-                            "1" => {
-                                this = *tuple.1.clone();
-                            }
-                            _ => {
-                                return Err(RuntimeError::UnknownAttribute {
-                                    attr: field,
-                                    value: this.value_name(),
-                                });
-                            }
-                        },
-                        Value::Element(element) => match field.as_str() {
-                            "name" => {
-                                this = Value::String(element.name.clone());
-                            }
-                            "attributes" => {
-                                this = Value::Dict(element.attributes.clone());
-                            }
-                            "content" => {
-                                let mut content = vec![];
-                                for i in &element.content {
-                                    match i {
-                                        ElementContentType::Children(c) => {
-                                            content.push(Value::Element(c.clone()));
-                                        }
-                                        ElementContentType::Content(c) => {
-                                            content.push(Value::String(c.clone()));
-                                        }
-                                    }
-                                }
-                                this = Value::List(content);
-                            }
-                            _ => {
-                                return Err(RuntimeError::UnknownAttribute {
-                                    attr: field,
-                                    value: this.value_name(),
-                                });
-                            }
-                        },
-                        _ => {
-                            return Err(RuntimeError::UnknownAttribute {
-                                attr: field,
-                                value: this.value_name(),
-                            });
-                        }
-                    }
-                }
-                dioscript_parser::parser::LinkExprPart::FunctionCall(call) => match &this {
-                    Value::String(content) => {
-                        let mut pararms = vec![Value::String(content.to_string())];
-                        for i in call.arguments {
-                            let v = self.to_value(i)?;
-                            pararms.push(v);
-                        }
-                        let v = self.get_var("string")?.1;
-                        let v = self.deref_value(v)?;
-                        if let Value::Dict(v) = v {
-                            if let Some(Value::Function(f)) = v.get(&call.name.as_single()) {
-                                this = self.execute_function_by_ft(f.clone(), pararms)?;
-                            }
-                        }
-                    }
-                    Value::Number(_) => todo!(),
-                    Value::Boolean(_) => todo!(),
-                    Value::List(_) => todo!(),
-                    Value::Dict(_) => todo!(),
-                    Value::Tuple(_) => todo!(),
-                    Value::Element(_) => todo!(),
-                    Value::Function(_) => todo!(),
-                    _ => {
-                        unimplemented!()
-                    }
-                },
-            }
+    fn get_var(&self, name: &Symbol) -> Result<(DataId, Value), RuntimeError> {
+        match self.var_ref(name) {
+            Some((id, value)) => Ok((id, value.clone())),
+            None => Err(RuntimeError::VariableNotFound {
+                name: name.to_string(),
+            }),
         }
-        Ok(self.deref_value(this)?)
     }
 
-    fn get_var(&self, name: &str) -> Result<(Uuid, Value), RuntimeError> {
+    fn var_ref(&self, name: &Symbol) -> Option<(DataId, &Value)> {
         for scope in self.scopes.iter().rev() {
             if scope.isolate {
                 break;
             }
-            if let Some(uuid) = scope.data.get(name) {
-                if let Some(data_type) = self.data.get(uuid) {
-                    let value = data_type.as_variable().unwrap();
-                    return Ok((uuid.clone(), value));
-                }
-                break;
+            if let Some(id) = scope.data.get(name) {
+                return self.data.get(*id).map(|v| (*id, v.variable()));
             }
         }
-        Err(RuntimeError::VariableNotFound {
-            name: name.to_string(),
-        })
+        None
+    }
+
+    // load resolved variable from scope slot, fallback to name lookup.
+    fn get_local(&self, local: &LocalVariable) -> Result<Value, RuntimeError> {
+        let id = self.slot(local);
+        if let Some(data_type) = id.and_then(|id| self.data.get(id)) {
+            return Ok(data_type.variable().clone());
+        }
+        Ok(self.get_var(&local.name)?.1)
+    }
+
+    // slot is skipped when it holds another name, e.g. the `let` was not executed.
+    fn slot(&self, local: &LocalVariable) -> Option<DataId> {
+        let index = self.scopes.len().checked_sub(local.depth + 1)?;
+        match self.scopes[index].slots.get(local.slot) {
+            Some((name, id)) if *name == local.name => Some(*id),
+            _ => None,
+        }
+    }
+
+    fn local_id(&self, local: &LocalVariable) -> Option<DataId> {
+        let slot = self.slot(local).filter(|id| self.data.contains_key(*id));
+        match slot {
+            Some(id) => Some(id),
+            None => self.var_ref(&local.name).map(|(id, _)| id),
+        }
     }
 
-    fn set_var(&mut self, name: &str, value: Value) -> Result<Uuid, RuntimeError> {
+    fn set_var(&mut self, name: &Symbol, value: Value) -> Result<DataId, RuntimeError> {
         // let value = match value {
         //     Value::List(list) => {
         //         let mut result = vec![];
@@ -736,8 +2224,50 @@ impl Runtime {
         //     _ => value,
         // };
 
-        let id = if let Ok((id, _)) = self.get_var(name) {
-            let data = self.data.get_mut(&id).unwrap();
+        self.check_assign(name)?;
+        let id = match self.strict_types {
+            true => self.var_ref(name).map(|(id, _)| id),
+            false => None,
+        };
+        if let Some(id) = id {
+            self.check_assigned_type(id, name, &value)?;
+        }
+        self.store_var(name, value)
+    }
+
+    // annotated variable keeps its type when it is assigned again.
+    fn check_assigned_type(
+        &self,
+        id: DataId,
+        name: &Symbol,
+        value: &Value,
+    ) -> Result<(), RuntimeError> {
+        match self.types.get(id) {
+            Some(expected) => self.check_type(&format!("variable `{name}`"), value, Some(expected)),
+            None => Ok(()),
+        }
+    }
+
+    fn check_assign(&self, name: &Symbol) -> Result<(), RuntimeError> {
+        if self.read_only {
+            let base = self.function_scopes.last().copied();
+            let outer = match self.var_scope_index(name) {
+                Some(index) => base.map(|b| index < b).unwrap_or(true),
+                None => base.is_none(),
+            };
+            if outer {
+                return Err(RuntimeError::ReadOnlyViolation {
+                    action: format!("assign variable `{name}` outside function"),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // function define is allowed in read-only mode, so it skip the check in `set_var`.
+    fn store_var(&mut self, name: &Symbol, value: Value) -> Result<DataId, RuntimeError> {
+        let id = if let Some((id, _)) = self.var_ref(name) {
+            let data = self.data.get_mut(id).unwrap();
             #[allow(unreachable_patterns)]
             match data {
                 DataType::Variable(v) => {
@@ -747,42 +2277,93 @@ impl Runtime {
             }
             id
         } else {
-            let id = Uuid::new_v4();
-            let _ = self
-                .data
-                .insert(id, DataType::Variable(value));
+            let id = self.insert_data(value)?;
             if let Some(current_scope) = self.scopes.last_mut() {
-                current_scope.data.insert(name.to_string(), id);
+                current_scope.data.insert(name.clone(), id);
             }
             id
         };
-        return Ok(id);
+        Ok(id)
+    }
+
+    // `let` always binds a new variable in current scope: outer variable with same name is
+    // shadowed, and a redeclared name in the same scope points to the new value.
+    fn define_var(&mut self, name: &Symbol, value: Value) -> Result<DataId, RuntimeError> {
+        if self.read_only && self.function_scopes.is_empty() {
+            return Err(RuntimeError::ReadOnlyViolation {
+                action: format!("define variable `{name}` outside function"),
+            });
+        }
+        let id = self.create_var(name, value)?;
+        // resolver gives `let` variables of functions slots in declaration order.
+        if !self.function_scopes.is_empty() {
+            self.push_slot(name, id);
+        }
+        Ok(id)
+    }
+
+    // create variable in current scope, outer variable with same name will be shadowed.
+    fn create_var(&mut self, name: &Symbol, value: Value) -> Result<DataId, RuntimeError> {
+        let id = self.insert_data(value)?;
+        if let Some(current_scope) = self.scopes.last_mut() {
+            if let Some(old) = current_scope.data.insert(name.clone(), id) {
+                // redeclared variable is unreachable, unless it is captured or in a slot.
+                let slot = current_scope.slots.iter().any(|(_, id)| *id == old);
+                if !self.escaped.contains(&old) && !slot {
+                    self.data.remove(old);
+                }
+            }
+        }
+        Ok(id)
+    }
+
+    fn push_slot(&mut self, name: &Symbol, id: DataId) {
+        if let Some(current_scope) = self.scopes.last_mut() {
+            current_scope.slots.push((name.clone(), id));
+        }
+    }
+
+    fn var_scope_index(&self, name: &Symbol) -> Option<usize> {
+        for (index, scope) in self.scopes.iter().enumerate().rev() {
+            if scope.isolate {
+                break;
+            }
+            if scope.data.contains_key(name) {
+                return Some(index);
+            }
+        }
+        None
     }
 
     #[allow(dead_code)]
-    fn create_data(&mut self, data: Value) -> Result<Uuid, RuntimeError> {
-        let id = Uuid::new_v4();
-        self.data.insert(id, DataType::Variable(
-            data
-        ));
+    fn create_data(&mut self, data: Value) -> Result<DataId, RuntimeError> {
+        self.insert_data(data)
+    }
+
+    fn insert_data(&mut self, value: Value) -> Result<DataId, RuntimeError> {
+        if let Some(max) = self.limits.max_data {
+            if self.data.len() >= max {
+                return Err(RuntimeError::MemoryLimitExceeded {
+                    limit: format!("data store is full with {max} entries"),
+                });
+            }
+        }
+        let id = self.data.insert(DataType::Variable(value));
+        self.report.peak_data_size = self.report.peak_data_size.max(self.data.len());
         Ok(id)
     }
 
     fn get_from_index(&self, value: Value, index: Value) -> Result<Value, RuntimeError> {
+        let not_found = || RuntimeError::IndexNotFound {
+            index: index.to_string(),
+            value: value.value_name(),
+        };
         match &value {
             Value::String(v) => {
                 if let Value::Number(num) = index {
-                    let num = num as usize;
-                    let chars = v.chars();
-                    let c = chars.collect::<Vec<char>>();
-                    if c.len() >= num + 1 {
-                        return Ok(Value::String(c[num].to_string()));
-                    } else {
-                        Err(RuntimeError::IndexNotFound {
-                            index: index.value_name(),
-                            value: value.value_name(),
-                        })
-                    }
+                    let c = v.chars().collect::<Vec<char>>();
+                    let num = list_index(num, c.len()).ok_or_else(not_found)?;
+                    Ok(Value::String(c[num].to_string().into()))
                 } else {
                     Err(RuntimeError::IllegalIndexType {
                         index_type: index.value_name(),
@@ -792,16 +2373,8 @@ impl Runtime {
             }
             Value::List(v) => {
                 if let Value::Number(num) = index {
-                    let num = num as usize;
-                    if v.len() >= num + 1 {
-                        let v = v[num].clone();
-                        Ok(v)
-                    } else {
-                        Err(RuntimeError::IndexNotFound {
-                            index: index.value_name(),
-                            value: value.value_name(),
-                        })
-                    }
+                    let num = list_index(num, v.len()).ok_or_else(not_found)?;
+                    Ok(v[num].clone())
                 } else {
                     Err(RuntimeError::IllegalIndexType {
                         index_type: index.value_name(),
@@ -811,14 +2384,7 @@ impl Runtime {
             }
             Value::Dict(v) => {
                 if let Value::String(key) = &index {
-                    if let Some(value) = v.get(key) {
-                        Ok(value.clone())
-                    } else {
-                        Err(RuntimeError::IndexNotFound {
-                            index: index.value_name(),
-                            value: value.value_name(),
-                        })
-                    }
+                    v.get(&**key).cloned().ok_or_else(not_found)
                 } else {
                     Err(RuntimeError::IllegalIndexType {
                         index_type: index.value_name(),
@@ -828,16 +2394,9 @@ impl Runtime {
             }
             Value::Tuple(v) => {
                 if let Value::Number(num) = index {
-                    let num = num as usize;
-                    if num == 0 {
-                        Ok(*v.0.clone())
-                    } else if num == 1 {
-                        Ok(*v.1.clone())
-                    } else {
-                        Err(RuntimeError::IndexNotFound {
-                            index: index.value_name(),
-                            value: value.value_name(),
-                        })
+                    match list_index(num, 2).ok_or_else(not_found)? {
+                        0 => Ok(*v.0.clone()),
+                        _ => Ok(*v.1.clone()),
                     }
                 } else {
                     Err(RuntimeError::IllegalIndexType {
@@ -853,106 +2412,197 @@ impl Runtime {
         }
     }
 
-    fn to_element(&mut self, element: AstElement) -> Result<Element, RuntimeError> {
+    // assign to indexed location of variable: `a["b"][2] = value;`, `el.attributes["id"] = value;`
+    fn set_index_var(
+        &mut self,
+        name: &Symbol,
+        index: &[CalcExpr],
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        let mut path = vec![];
+        for i in index {
+            path.push(self.execute_calculate(i)?);
+        }
+        let value = self.deref_value(value)?;
+        self.check_assign(name)?;
+        let id = match self.var_ref(name) {
+            Some((id, _)) => id,
+            None => {
+                return Err(RuntimeError::VariableNotFound {
+                    name: name.to_string(),
+                })
+            }
+        };
+        // value is taken out of the variable while it is changed, so the list or dict is not
+        // shared with the variable and can be updated in place instead of copied.
+        let mut target = match self.data.get_mut(id).unwrap() {
+            DataType::Variable(v) => std::mem::replace(v, Value::None),
+        };
+        let result = set_index(&mut target, &path, value).and_then(|_| self.check_size(&target));
+        self.store_var(name, target)?;
+        result
+    }
+
+    // `outer x = v;` and `global x = v;` skip variables of current function (or all functions).
+    fn captured_var(&self, name: &Symbol) -> Option<DataId> {
+        let scope = &self.scopes[*self.function_scopes.last()?];
+        scope.captures.get(name).copied()
+    }
+
+    fn set_outer_var(
+        &mut self,
+        name: &Symbol,
+        scope: AssignScope,
+        index: &[CalcExpr],
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        if self.read_only {
+            return Err(RuntimeError::ReadOnlyViolation {
+                action: format!("assign variable `{name}` outside function"),
+            });
+        }
+        // `outer` resolves where the running function is defined: captured variables of nested
+        // functions, top-level variables otherwise. variables of callers are never visible.
+        let captured = match scope {
+            AssignScope::Outer => self.captured_var(name),
+            AssignScope::Global => None,
+        };
+        let end = self.function_scopes.first().copied();
+        let end = end.unwrap_or(self.scopes.len());
+        let id = captured
+            .or_else(|| {
+                self.scopes[..end]
+                    .iter()
+                    .rev()
+                    .take_while(|scope| !scope.isolate)
+                    .find_map(|scope| scope.data.get(name).copied())
+            })
+            .ok_or_else(|| RuntimeError::VariableNotFound {
+                name: name.to_string(),
+            })?;
+        let value = if index.is_empty() {
+            value
+        } else {
+            let mut path = vec![];
+            for i in index {
+                path.push(self.execute_calculate(i)?);
+            }
+            let mut target = self.deref_value(Value::Reference(id))?;
+            set_index(&mut target, &path, self.deref_value(value)?)?;
+            self.check_size(&target)?;
+            target
+        };
+        self.check_assigned_type(id, name, &value)?;
+        if let Some(DataType::Variable(v)) = self.data.get_mut(id) {
+            *v = value;
+        }
+        Ok(())
+    }
+
+    fn get_from_slice(
+        &self,
+        value: Value,
+        start: Option<f64>,
+        end: Option<f64>,
+        step: Option<f64>,
+    ) -> Result<Value, RuntimeError> {
+        let len = match &value {
+            Value::String(v) => v.chars().count(),
+            Value::List(v) => v.len(),
+            _ => {
+                return Err(RuntimeError::IllegalIndexType {
+                    index_type: "slice".to_string(),
+                    value_type: value.value_name(),
+                })
+            }
+        };
+        let indices = slice_indices(len, start, end, step.unwrap_or(1.0))?;
+        match value {
+            Value::String(v) => {
+                let chars = v.chars().collect::<Vec<char>>();
+                Ok(Value::String(
+                    indices
+                        .into_iter()
+                        .map(|i| chars[i])
+                        .collect::<String>()
+                        .into(),
+                ))
+            }
+            Value::List(v) => Ok(Value::List(Rc::new(
+                indices.into_iter().map(|i| v[i].clone()).collect(),
+            ))),
+            _ => unreachable!(),
+        }
+    }
+
+    fn to_element(&mut self, element: &AstElement) -> Result<Element, RuntimeError> {
         let mut attrs = HashMap::new();
-        for i in element.attributes {
-            let name = i.0;
-            let data = i.1;
-            attrs.insert(name, self.to_value(data)?);
+        for (name, data) in element.attributes.iter() {
+            attrs.insert(name.clone(), self.to_value(data)?);
         }
         let mut content = vec![];
-        for i in element.content {
+        for i in element.content.iter() {
             match i {
                 AstElementContentType::Children(v) => {
                     let executed_element = self.to_element(v)?;
                     content.push(ElementContentType::Children(executed_element));
                 }
                 AstElementContentType::Content(v) => {
-                    content.push(ElementContentType::Content(v));
+                    content.push(ElementContentType::Content(v.clone()));
                 }
                 AstElementContentType::Condition(v) => {
-                    let value = self.execute_calculate(v.condition)?;
+                    let value = self.execute_calculate(&v.condition)?;
                     if let Value::Boolean(b) = value {
                         let mut temp = Value::None;
                         if b {
-                            temp = self.execute_scope(v.inner)?;
+                            temp = self.execute_scope(&v.inner)?;
                         } else {
-                            if let Some(otherwise) = v.otherwise {
+                            if let Some(otherwise) = &v.otherwise {
                                 temp = self.execute_scope(otherwise)?;
                             }
                         }
-                        if let Value::Tuple((k, v)) = &temp {
-                            if let Value::String(k) = *k.clone() {
-                                attrs.insert(k.to_string(), *v.clone());
-                            }
-                        }
-                        if let Value::String(v) = &temp {
-                            content.push(ElementContentType::Content(v.clone()));
-                        }
-                        if let Value::Number(v) = &temp {
-                            content.push(ElementContentType::Content(format!("{v}")));
-                        }
-                        if let Value::Element(v) = temp {
-                            content.push(ElementContentType::Children(v));
-                        }
+                        append_scope_result(temp, &mut attrs, &mut content);
                     }
                 }
-                AstElementContentType::Loop(v) => {
-                    let execute_type = v.execute_type;
-                    match execute_type {
-                        LoopExecuteType::Conditional(cond) => loop {
-                            let cond = cond.clone();
-                            let state = self.execute_calculate(cond)?;
-                            let state = state.to_boolean_data();
-                            if !state {
+                AstElementContentType::Loop(v) => match &v.execute_type {
+                    LoopExecuteType::Conditional(cond) => loop {
+                        let state = self.execute_calculate(cond)?;
+                        let state = state.to_boolean_data();
+                        if !state {
+                            break;
+                        } else {
+                            let temp = self.execute_scope(&v.inner)?;
+                            if let Some(value) = self.break_value.take() {
+                                append_scope_result(value, &mut attrs, &mut content);
                                 break;
-                            } else {
-                                let temp = self.execute_scope(v.inner.clone())?;
-                                if let Value::Tuple((k, v)) = &temp {
-                                    if let Value::String(k) = *k.clone() {
-                                        attrs.insert(k.to_string(), *v.clone());
-                                    }
-                                }
-                                if let Value::String(v) = &temp {
-                                    content.push(ElementContentType::Content(v.clone()));
-                                }
-                                if let Value::Number(v) = &temp {
-                                    content.push(ElementContentType::Content(format!("{v}")));
-                                }
-                                if let Value::Element(v) = temp {
-                                    content.push(ElementContentType::Children(v));
-                                }
                             }
-                        },
-                        LoopExecuteType::Iter { iter, var } => {
-                            let iter = self.to_value(iter)?;
-                            if iter.value_name() == "list" {
-                                for i in iter.as_list().unwrap() {
-                                    self.set_var(&var, i.clone())?;
-                                    let temp = self.execute_scope(v.inner.clone())?;
-                                    if let Value::Tuple((k, v)) = &temp {
-                                        if let Value::String(k) = *k.clone() {
-                                            attrs.insert(k.to_string(), *v.clone());
-                                        }
-                                    }
-                                    if let Value::String(v) = &temp {
-                                        content.push(ElementContentType::Content(v.clone()));
-                                    }
-                                    if let Value::Number(v) = &temp {
-                                        content.push(ElementContentType::Content(format!("{v}")));
-                                    }
-                                    if let Value::Element(v) = temp {
-                                        content.push(ElementContentType::Children(v));
-                                    }
+                            append_scope_result(temp, &mut attrs, &mut content);
+                        }
+                    },
+                    LoopExecuteType::Iter { iter, var, index } => {
+                        let iter = self.to_value(iter)?;
+                        if let Some(list) = iter_items(iter) {
+                            for (idx, i) in list.into_iter().enumerate() {
+                                let index = index.as_ref().map(|name| (name, idx));
+                                let temp = self.execute_iteration(&v.inner, var, i, index)?;
+                                if let Some(value) = self.break_value.take() {
+                                    append_scope_result(value, &mut attrs, &mut content);
+                                    break;
                                 }
+                                append_scope_result(temp, &mut attrs, &mut content);
                             }
                         }
                     }
+                },
+                AstElementContentType::Comment(_) => {}
+                AstElementContentType::Block(stats) => {
+                    let temp = self.execute_scope(stats)?;
+                    append_scope_result(temp, &mut attrs, &mut content);
                 }
                 AstElementContentType::InlineExpr(v) => {
                     let result = self.execute_calculate(v)?;
                     if let Value::String(s) = &result {
-                        content.push(ElementContentType::Content(s.clone()));
+                        content.push(ElementContentType::Content(s.to_string()));
                     }
                     if let Value::Number(s) = result {
                         content.push(ElementContentType::Content(format!("{s}")));
@@ -960,18 +2610,512 @@ impl Runtime {
                 }
             }
         }
+        // `div.card { class: c }` renders `class="card {c}"`.
+        if !element.classes.is_empty() {
+            let mut classes = element.classes.join(" ");
+            match attrs.remove("class") {
+                Some(Value::String(v)) if !v.is_empty() => classes = format!("{classes} {v}"),
+                Some(Value::Number(v)) => classes = format!("{classes} {v}"),
+                Some(Value::String(_)) | Some(Value::None) | None => {}
+                Some(v) => {
+                    return Err(RuntimeError::TypeMismatch {
+                        target: "attribute `class`".to_string(),
+                        expected: "string".to_string(),
+                        found: v.value_name(),
+                    })
+                }
+            }
+            attrs.insert("class".to_string(), Value::String(classes.as_str().into()));
+        }
         Ok(Element {
-            name: element.name,
+            name: element.name.clone(),
             attributes: attrs,
             content,
+            span: Some(element.span),
+        })
+    }
+}
+
+// variable a link expression starts from.
+type LinkRoot = (Symbol, DataId);
+
+enum LoopExit {
+    Finished,
+    // stopped by `break`, with its value.
+    Break(Value),
+    // body returned a value.
+    Return(Value),
+}
+
+// value returned by a block inside element: `(key, value)` tuple sets an attribute,
+// string, number and element are appended to the content.
+fn append_scope_result(
+    value: Value,
+    attrs: &mut HashMap<String, Value>,
+    content: &mut Vec<ElementContentType>,
+) {
+    match value {
+        Value::Tuple((k, v)) => {
+            if let Value::String(k) = *k {
+                attrs.insert(k.to_string(), *v);
+            }
+        }
+        Value::String(v) => content.push(ElementContentType::Content(v.to_string())),
+        Value::Number(v) => content.push(ElementContentType::Content(format!("{v}"))),
+        Value::Element(v) => content.push(ElementContentType::Children(v)),
+        _ => {}
+    }
+}
+
+// items visited by `for` loop: list items, characters of string or both tuple items.
+fn iter_items(value: Value) -> Option<Vec<Value>> {
+    match value {
+        Value::List(list) => Some(Rc::unwrap_or_clone(list)),
+        Value::String(s) => Some(
+            s.chars()
+                .map(|c| Value::String(c.to_string().into()))
+                .collect(),
+        ),
+        Value::Tuple((a, b)) => Some(vec![*a, *b]),
+        _ => None,
+    }
+}
+
+// `any` matches every value, object instance also matches its object name.
+fn type_matches(value: &Value, name: &str) -> bool {
+    let object = match value {
+        Value::Dict(dict) => dict.get("__object__"),
+        _ => None,
+    };
+    name == "any"
+        || value.value_name() == name
+        || matches!(object, Some(Value::String(object)) if &**object == name)
+}
+
+fn function_label(name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("`{name}`"),
+        None => "anonymous function".to_string(),
+    }
+}
+
+// number of fixed parameters, and whether more arguments are accepted.
+fn params_number(params: &ParamsType) -> (usize, bool) {
+    match params {
+        ParamsType::Variable(fixed, _) => (fixed.len(), true),
+        ParamsType::List(list) => (list.len(), false),
+    }
+}
+
+pub(crate) fn function_params_number(func: &FunctionType) -> (usize, bool) {
+    match func {
+        FunctionType::Rusty((_, need)) if *need < 0 => (0, true),
+        FunctionType::Rusty((_, need)) => (*need as usize, false),
+        FunctionType::DScript(define) | FunctionType::Closure((define, ..)) => {
+            params_number(&define.params)
+        }
+    }
+}
+
+// negative index count from the end: `list[-1]` is the last item.
+fn list_index(num: f64, len: usize) -> Option<usize> {
+    if num.fract() != 0.0 {
+        return None;
+    }
+    let index = if num < 0.0 { len as f64 + num } else { num };
+    if index >= 0.0 && index < len as f64 {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+// runtime data used by value: captures of closures, nested in lists, dicts and elements.
+fn value_data(value: &Value, ids: &mut Vec<DataId>) {
+    match value {
+        Value::Reference(id) => ids.push(*id),
+        Value::Function(func) => function_data(func, ids),
+        Value::List(list) => list.iter().for_each(|v| value_data(v, ids)),
+        Value::Dict(dict) => dict.values().for_each(|v| value_data(v, ids)),
+        Value::Tuple((first, second)) => {
+            value_data(first, ids);
+            value_data(second, ids);
+        }
+        Value::Element(element) => element_data(element, ids),
+        _ => {}
+    }
+}
+
+// part of expression which is not allowed by `eval_expr`.
+fn expr_statement(expr: &CalcExpr) -> Option<&'static str> {
+    match expr {
+        CalcExpr::Value(v) => value_statement(v),
+        CalcExpr::LinkExpr(link) => expr_statement(&link.this).or_else(|| {
+            link.list.iter().find_map(|part| match part {
+                dioscript_parser::parser::LinkExprPart::FunctionCall(call) => {
+                    call.arguments.iter().find_map(value_statement)
+                }
+                _ => None,
+            })
+        }),
+        CalcExpr::Pipe(v, call) => {
+            expr_statement(v).or_else(|| call.arguments.iter().find_map(value_statement))
+        }
+        CalcExpr::TypeOf(v) | CalcExpr::Is(v, _) => expr_statement(v),
+        CalcExpr::Call(f, arguments) => {
+            expr_statement(f).or_else(|| arguments.iter().find_map(value_statement))
+        }
+        CalcExpr::Add(l, r)
+        | CalcExpr::Sub(l, r)
+        | CalcExpr::Mul(l, r)
+        | CalcExpr::Div(l, r)
+        | CalcExpr::Mod(l, r)
+        | CalcExpr::Pow(l, r)
+        | CalcExpr::BitAnd(l, r)
+        | CalcExpr::BitOr(l, r)
+        | CalcExpr::BitXor(l, r)
+        | CalcExpr::Shl(l, r)
+        | CalcExpr::Shr(l, r)
+        | CalcExpr::Eq(l, r)
+        | CalcExpr::Ne(l, r)
+        | CalcExpr::Gt(l, r)
+        | CalcExpr::Lt(l, r)
+        | CalcExpr::Ge(l, r)
+        | CalcExpr::Le(l, r)
+        | CalcExpr::And(l, r)
+        | CalcExpr::Or(l, r)
+        | CalcExpr::Coalesce(l, r)
+        | CalcExpr::Tuple(l, r) => expr_statement(l).or_else(|| expr_statement(r)),
+    }
+}
+
+fn value_statement(value: &AstValue) -> Option<&'static str> {
+    match value {
+        AstValue::Loop(_) => Some("run loop in expression"),
+        AstValue::FunctionDefine(_) => Some("define function in expression"),
+        AstValue::VariableIndex((_, index)) => expr_statement(index),
+        AstValue::VariableSlice((_, slice)) => [&slice.start, &slice.end, &slice.step]
+            .into_iter()
+            .flatten()
+            .find_map(|v| expr_statement(v)),
+        AstValue::List(list) => list.iter().find_map(value_statement),
+        AstValue::Dict(dict) => dict.values().find_map(value_statement),
+        AstValue::Tuple((a, b)) => value_statement(a).or_else(|| value_statement(b)),
+        AstValue::FunctionCaller(call) => call.arguments.iter().find_map(value_statement),
+        AstValue::Element(element) => element_statement(element),
+        AstValue::None
+        | AstValue::String(_)
+        | AstValue::Number(_)
+        | AstValue::Boolean(_)
+        | AstValue::Variable(_)
+        | AstValue::Local(_) => None,
+    }
+}
+
+fn element_statement(element: &AstElement) -> Option<&'static str> {
+    if let Some(v) = element.attributes.values().find_map(value_statement) {
+        return Some(v);
+    }
+    element.content.iter().find_map(|content| match content {
+        AstElementContentType::Children(child) => element_statement(child),
+        AstElementContentType::InlineExpr(expr) => expr_statement(expr),
+        AstElementContentType::Condition(_)
+        | AstElementContentType::Loop(_)
+        | AstElementContentType::Block(_) => Some("run statements in expression"),
+        AstElementContentType::Content(_) | AstElementContentType::Comment(_) => None,
+    })
+}
+
+fn contains_function(value: &Value) -> bool {
+    match value {
+        Value::Function(_) => true,
+        Value::List(list) => list.iter().any(contains_function),
+        Value::Dict(dict) => dict.values().any(contains_function),
+        Value::Tuple((first, second)) => contains_function(first) || contains_function(second),
+        Value::Element(element) => element.attributes.values().any(contains_function),
+        _ => false,
+    }
+}
+
+fn function_data(func: &FunctionType, ids: &mut Vec<DataId>) {
+    if let FunctionType::Closure((_, captures, _)) = func {
+        ids.extend(captures.values());
+    }
+}
+
+fn element_data(element: &Element, ids: &mut Vec<DataId>) {
+    element.attributes.values().for_each(|v| value_data(v, ids));
+    for content in element.content.iter() {
+        if let ElementContentType::Children(child) = content {
+            element_data(child, ids);
+        }
+    }
+}
+
+fn module_data(item: &ModuleItem, ids: &mut Vec<DataId>) {
+    match item {
+        ModuleItem::Function(func) => function_data(func, ids),
+        ModuleItem::Variable(value) => value_data(value, ids),
+        ModuleItem::SubModule(info) => info.0.values().for_each(|v| module_data(v, ids)),
+    }
+}
+
+fn set_index(target: &mut Value, path: &[Value], value: Value) -> Result<(), RuntimeError> {
+    let (index, rest) = match path.split_first() {
+        Some(v) => v,
+        None => {
+            *target = value;
+            return Ok(());
+        }
+    };
+    let value_type = target.value_name();
+    let not_found = || RuntimeError::IndexNotFound {
+        index: index.to_string(),
+        value: value_type.clone(),
+    };
+    match (target, index) {
+        // shared containers are copied before they are changed.
+        (Value::List(list), Value::Number(num)) => {
+            let num = list_index(*num, list.len()).ok_or_else(not_found)?;
+            set_index(&mut Rc::make_mut(list)[num], rest, value)
+        }
+        (Value::List(list), Value::String(field)) => {
+            let num = field.parse::<f64>().map_err(|_| not_found())?;
+            let num = list_index(num, list.len()).ok_or_else(not_found)?;
+            set_index(&mut Rc::make_mut(list)[num], rest, value)
+        }
+        (Value::Tuple((first, second)), Value::String(field)) => match &**field {
+            "0" => set_index(first, rest, value),
+            "1" => set_index(second, rest, value),
+            _ => Err(not_found()),
+        },
+        (Value::Element(element), Value::String(field)) => match &**field {
+            "name" if rest.is_empty() => {
+                element.name = value.as_string().ok_or(RuntimeError::IllegalIndexType {
+                    index_type: value.value_name(),
+                    value_type: value_type.clone(),
+                })?;
+                Ok(())
+            }
+            "attributes" => {
+                let attributes = std::mem::take(&mut element.attributes);
+                let mut attributes = Value::Dict(Rc::new(attributes));
+                set_index(&mut attributes, rest, value)?;
+                match attributes {
+                    Value::Dict(v) => {
+                        element.attributes = Rc::unwrap_or_clone(v);
+                        Ok(())
+                    }
+                    other => Err(RuntimeError::IllegalIndexType {
+                        index_type: other.value_name(),
+                        value_type: value_type.clone(),
+                    }),
+                }
+            }
+            "content" => {
+                let mut content = Value::List(Rc::new(
+                    element
+                        .content
+                        .drain(..)
+                        .map(|v| match v {
+                            ElementContentType::Children(e) => Value::Element(e),
+                            ElementContentType::Content(s) => Value::String(s.into()),
+                        })
+                        .collect(),
+                ));
+                set_index(&mut content, rest, value)?;
+                for v in content.as_list().unwrap_or_default() {
+                    match v {
+                        Value::Element(e) => element.content.push(ElementContentType::Children(e)),
+                        Value::String(s) => element
+                            .content
+                            .push(ElementContentType::Content(s.to_string())),
+                        Value::Number(n) => element
+                            .content
+                            .push(ElementContentType::Content(n.to_string())),
+                        other => {
+                            return Err(RuntimeError::IllegalIndexType {
+                                index_type: other.value_name(),
+                                value_type: value_type.clone(),
+                            })
+                        }
+                    }
+                }
+                Ok(())
+            }
+            _ => Err(not_found()),
+        },
+        (Value::Dict(dict), Value::String(key)) => {
+            if rest.is_empty() {
+                Rc::make_mut(dict).insert(key.to_string(), value);
+                Ok(())
+            } else {
+                let inner = Rc::make_mut(dict).get_mut(&**key).ok_or_else(not_found)?;
+                set_index(inner, rest, value)
+            }
+        }
+        _ => Err(RuntimeError::IllegalIndexType {
+            index_type: index.value_name(),
+            value_type: value_type.clone(),
+        }),
+    }
+}
+
+// python style slice: bounds are clamped, negative step walks backward.
+fn slice_indices(
+    len: usize,
+    start: Option<f64>,
+    end: Option<f64>,
+    step: f64,
+) -> Result<Vec<usize>, RuntimeError> {
+    let to_int = |v: f64| {
+        if v.fract() != 0.0 {
+            return Err(RuntimeError::IndexNotFound {
+                index: v.to_string(),
+                value: "slice".to_string(),
+            });
+        }
+        Ok(v as i64)
+    };
+    let step = to_int(step)?;
+    if step == 0 {
+        return Err(RuntimeError::ZeroSliceStep);
+    }
+    let len = len as i64;
+    let bound = |v: Option<f64>, default: i64| -> Result<i64, RuntimeError> {
+        let v = match v {
+            Some(v) => to_int(v)?,
+            None => return Ok(default),
+        };
+        let v = if v < 0 { v + len } else { v };
+        Ok(if step > 0 {
+            v.clamp(0, len)
+        } else {
+            v.clamp(-1, len - 1)
         })
+    };
+    let mut result = vec![];
+    if step > 0 {
+        let (mut i, end) = (bound(start, 0)?, bound(end, len)?);
+        while i < end {
+            result.push(i as usize);
+            i += step;
+        }
+    } else {
+        let (mut i, end) = (bound(start, len - 1)?, bound(end, -1)?);
+        while i > end {
+            result.push(i as usize);
+            i += step;
+        }
+    }
+    Ok(result)
+}
+
+// function field of dict (or object instance) is callable as method with `self` binding.
+fn dict_method(this: &Value, name: &str) -> Option<FunctionType> {
+    if let Value::Dict(dict) = this {
+        if let Some(Value::Function(f)) = dict.get(name) {
+            return Some(f.clone());
+        }
+    }
+    None
+}
+
+// split comparison expression to operands, use for assert failure message.
+fn comparison_parts(expr: &CalcExpr) -> Option<(&CalcExpr, &CalcExpr, CalculateMark)> {
+    let (l, r, mark) = match expr {
+        CalcExpr::Eq(l, r) => (l, r, CalculateMark::Equal),
+        CalcExpr::Ne(l, r) => (l, r, CalculateMark::NotEqual),
+        CalcExpr::Gt(l, r) => (l, r, CalculateMark::Large),
+        CalcExpr::Lt(l, r) => (l, r, CalculateMark::Small),
+        CalcExpr::Ge(l, r) => (l, r, CalculateMark::LargeOrEqual),
+        CalcExpr::Le(l, r) => (l, r, CalculateMark::SmallOrEqual),
+        _ => return None,
+    };
+    Some((l, r, mark))
+}
+
+// split arithmetic, logic and comparison expression to operands and operator.
+fn binary_operands(expr: &CalcExpr) -> Option<(&CalcExpr, &CalcExpr, CalculateMark)> {
+    let (l, r, mark) = match expr {
+        CalcExpr::Add(l, r) => (l, r, CalculateMark::Plus),
+        CalcExpr::Sub(l, r) => (l, r, CalculateMark::Minus),
+        CalcExpr::Mul(l, r) => (l, r, CalculateMark::Multiply),
+        CalcExpr::Div(l, r) => (l, r, CalculateMark::Divide),
+        CalcExpr::Pow(l, r) => (l, r, CalculateMark::Power),
+        CalcExpr::BitAnd(l, r) => (l, r, CalculateMark::BitAnd),
+        CalcExpr::BitOr(l, r) => (l, r, CalculateMark::BitOr),
+        CalcExpr::BitXor(l, r) => (l, r, CalculateMark::BitXor),
+        CalcExpr::Shl(l, r) => (l, r, CalculateMark::Shl),
+        CalcExpr::Shr(l, r) => (l, r, CalculateMark::Shr),
+        CalcExpr::And(l, r) => (l, r, CalculateMark::And),
+        CalcExpr::Or(l, r) => (l, r, CalculateMark::Or),
+        _ => return comparison_parts(expr),
+    };
+    Some((l, r, mark))
+}
+
+fn overload_name(mark: &CalculateMark) -> Option<&'static str> {
+    let name = match mark {
+        CalculateMark::Plus => "__add__",
+        CalculateMark::Minus => "__sub__",
+        CalculateMark::Multiply => "__mul__",
+        CalculateMark::Divide => "__div__",
+        CalculateMark::Power => "__pow__",
+        CalculateMark::BitAnd => "__bitand__",
+        CalculateMark::BitOr => "__bitor__",
+        CalculateMark::BitXor => "__bitxor__",
+        CalculateMark::Shl => "__shl__",
+        CalculateMark::Shr => "__shr__",
+        CalculateMark::Equal => "__eq__",
+        CalculateMark::NotEqual => "__ne__",
+        CalculateMark::Large => "__gt__",
+        CalculateMark::Small => "__lt__",
+        CalculateMark::LargeOrEqual => "__ge__",
+        CalculateMark::SmallOrEqual => "__le__",
+        CalculateMark::And => "__and__",
+        CalculateMark::Or => "__or__",
+        CalculateMark::None => return None,
+    };
+    Some(name)
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionHandle(FunctionType);
+
+impl FunctionHandle {
+    pub fn call(&self, runtime: &mut Runtime, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let result = runtime.execute_function_by_ft(self.0.clone(), args)?;
+        runtime.deref_value(result)
+    }
+}
+
+// prelude script parsed once, can be shared by many runtimes.
+#[derive(Debug, Clone)]
+pub struct Prelude(DioscriptAst);
+
+impl Prelude {
+    pub fn from_string(code: &str) -> Result<Self, ParseError> {
+        Ok(Self(DioscriptAst::from_string(code)?))
+    }
+
+    pub fn from_ast(ast: DioscriptAst) -> Self {
+        Self(ast)
     }
 }
 
 #[derive(Debug)]
 pub struct Scope {
     isolate: bool,
-    data: HashMap<String, Uuid>,
+    data: HashMap<Symbol, DataId>,
+    // function parameters and `let` variables in order, use for resolved variable access.
+    slots: Vec<(Symbol, DataId)>,
+    // `defer` expressions, they are evaluated in reverse order when scope exits.
+    deferred: Vec<CalcExpr>,
+    // short name => module path of `use` statements in this scope.
+    uses: HashMap<Symbol, Vec<Symbol>>,
+    // variables captured by function of this scope, `data` may shadow them.
+    captures: HashMap<Symbol, DataId>,
 }
 
 impl Scope {
@@ -979,6 +3123,10 @@ impl Scope {
         Self {
             isolate: false,
             data: HashMap::new(),
+            slots: vec![],
+            deferred: vec![],
+            uses: HashMap::new(),
+            captures: HashMap::new(),
         }
     }
 
@@ -986,6 +3134,10 @@ impl Scope {
         Self {
             isolate: true,
             data: HashMap::new(),
+            slots: vec![],
+            deferred: vec![],
+            uses: HashMap::new(),
+            captures: HashMap::new(),
         }
     }
 }
@@ -1003,5 +3155,11 @@ impl DataType {
         }
         None
     }
+
+    pub fn variable(&self) -> &Value {
+        match self {
+            Self::Variable(r) => r,
+        }
+    }
 }
 
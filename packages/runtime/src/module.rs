@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use dioscript_parser::{ast::Version, symbol::Symbol};
 
 use crate::{types::{Value, FunctionType}, Runtime};
 
@@ -14,16 +15,21 @@ pub enum ModuleItem {
 
 
 #[derive(Clone)]
-pub struct ModuleInfo(pub HashMap<String, ModuleItem>);
+pub struct ModuleInfo(pub HashMap<Symbol, ModuleItem>);
 
-pub struct ModuleGenerator(pub HashMap<String, ModuleItem>);
+// items, and version checked by `use name(>=1.2);` when it is bound as top-level module.
+pub struct ModuleGenerator(pub HashMap<Symbol, ModuleItem>, pub Option<Version>);
 impl ModuleGenerator {
     pub fn new() -> Self {
-        Self(Default::default())
+        Self(Default::default(), None)
+    }
+
+    pub fn set_version(&mut self, version: Version) {
+        self.1 = Some(version);
     }
 
     pub fn insert(&mut self, k: &str, v: ModuleItem) {
-        self.0.insert(k.to_string(), v);
+        self.0.insert(Symbol::new(k), v);
     }
 
     pub fn insert_rusty_function(&mut self, k: &str, func: RustyFunction, arg: i32) {
@@ -0,0 +1,23 @@
+use crate::error::RuntimeError;
+
+#[derive(Debug, Clone)]
+pub enum ExecutionEvent {
+    ScriptStarted,
+    ScriptFinished,
+    FunctionCalled { name: String },
+    ModuleUsed { path: Vec<String> },
+    ErrorRaised { message: String },
+}
+
+impl ExecutionEvent {
+    pub fn error(err: &RuntimeError) -> Self {
+        Self::ErrorRaised {
+            message: err.to_string(),
+        }
+    }
+}
+
+// observer can be add to runtime for collect execution events.
+pub trait ExecutionObserver {
+    fn on_event(&mut self, event: &ExecutionEvent);
+}
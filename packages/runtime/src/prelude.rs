@@ -0,0 +1,18 @@
+// commonly used items, so downstream code can `use dioscript_runtime::prelude::*`
+// instead of importing from the parser and runtime crates separately.
+
+pub use crate::error::{Error, RuntimeError};
+pub use crate::interrupt::InterruptHandle;
+pub use crate::module::{ModuleGenerator, RustyFunction};
+pub use crate::report::ExecutionLimits;
+pub use crate::types::{Element, ElementContentType, Value};
+pub use crate::{FunctionHandle, Prelude, Runtime};
+
+pub use dioscript_parser::ast::{
+    parse_element, parse_expression, parse_function, DioAstStatement, DioscriptAst, FunctionCall,
+    FunctionDefine, FunctionName, Version,
+};
+pub use dioscript_parser::element::{AstElement, AstElementContentType};
+pub use dioscript_parser::error::ParseError;
+pub use dioscript_parser::parser::CalcExpr;
+pub use dioscript_parser::types::AstValue;
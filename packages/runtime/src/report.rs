@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+// resource usage of one execution, use for quota check in hosts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionReport {
+    pub statements: usize,
+    pub functions_called: usize,
+    pub peak_data_size: usize,
+    pub wall_time: Duration,
+}
+
+// limits of one execution, scripts which exceed them fail with
+// `RuntimeError::ExecutionLimitExceeded` or `RuntimeError::MemoryLimitExceeded`. `timeout`
+// reads the clock, use `fuel` on targets without one, e.g. wasm in browsers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecutionLimits {
+    // statements and blocks which can be executed, every loop iteration is a block.
+    pub fuel: Option<u64>,
+    pub timeout: Option<Duration>,
+    // entries in runtime data store, every variable takes one.
+    pub max_data: Option<usize>,
+    // length of a single string (in bytes), list or dict.
+    pub max_size: Option<usize>,
+    // script functions running at once. deep recursion overflows the native stack and aborts
+    // the host, so it is limited by default. a call takes up to about 20KB of stack in debug
+    // builds, the default fits a 2MB thread (the size of spawned threads), lower it for smaller
+    // stacks.
+    pub max_depth: Option<usize>,
+}
+
+impl ExecutionLimits {
+    pub const DEFAULT_MAX_DEPTH: usize = 64;
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            fuel: None,
+            timeout: None,
+            max_data: None,
+            max_size: None,
+            max_depth: Some(Self::DEFAULT_MAX_DEPTH),
+        }
+    }
+}
@@ -1,19 +1,24 @@
 use crate::module::ModuleGenerator;
+#[cfg(feature = "stdlib-ext")]
+use crate::{error::RuntimeError, types::Value, Runtime};
 
 pub mod root {
 
-    use crate::{module::ModuleGenerator, types::Value, Runtime};
+    use crate::{error::RuntimeError, module::ModuleGenerator, types::Value, Runtime};
 
+    #[cfg(feature = "console")]
     pub fn print(_: &mut Runtime, args: Vec<Value>) -> Value {
         print!("{}", iterable_to_str(args));
         return Value::None;
     }
 
+    #[cfg(feature = "console")]
     pub fn println(_: &mut Runtime, args: Vec<Value>) -> Value {
         println!("{}", iterable_to_str(args));
         return Value::None;
     }
 
+    #[cfg(feature = "console")]
     fn iterable_to_str<I, D>(iterable: I) -> String
     where
         I: IntoIterator<Item = D>,
@@ -30,18 +35,35 @@ pub mod root {
     }
 
     pub fn type_name(_: &mut Runtime, args: Vec<Value>) -> Value {
-        let name = args.get(0).unwrap().value_name();
-        return Value::String(name);
+        let name = args.first().unwrap().value_name();
+        return Value::String(name.into());
+    }
+
+    // doc comments of script function, `none` for functions without docs.
+    pub fn help(_: &mut Runtime, args: Vec<Value>) -> Value {
+        match args.first() {
+            Some(Value::Function(func)) => match func.define().and_then(|v| v.docs.clone()) {
+                Some(docs) => Value::String(docs.into()),
+                None => Value::None,
+            },
+            _ => Value::None,
+        }
     }
 
     pub fn execute(rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let value = args.get(0).unwrap();
-        if let Value::String(v) = value {
-            return match rt.execute(&v) {
+        // executed code is not checked like the expression of `eval_expr`.
+        if rt.read_only {
+            rt.raise(RuntimeError::ReadOnlyViolation {
+                action: "execute code".to_string(),
+            });
+            return Value::None;
+        }
+        if let Some(Value::String(v)) = args.first() {
+            return match rt.execute(v) {
                 Ok(result) => result,
                 Err(err) => Value::Tuple((
-                    Box::from(Value::String("error".to_string())),
-                    Box::from(Value::String(err.to_string())),
+                    Box::from(Value::String("error".into())),
+                    Box::from(Value::String(err.to_string().into())),
                 )),
             };
         }
@@ -51,62 +73,73 @@ pub mod root {
     pub fn export() -> ModuleGenerator {
         let mut module = ModuleGenerator::new();
 
-        module.insert_rusty_function("print", print, -1);
-        module.insert_rusty_function("println", println, -1);
+        #[cfg(feature = "console")]
+        {
+            module.insert_rusty_function("print", print, -1);
+            module.insert_rusty_function("println", println, -1);
+        }
         module.insert_rusty_function("type", type_name, 1);
+        module.insert_rusty_function("help", help, 1);
         module.insert_rusty_function("execute", execute, -1);
 
         return module;
     }
 }
 
+#[cfg(feature = "stdlib-ext")]
 mod string {
+    use std::rc::Rc;
+
     use crate::{module::ModuleGenerator, types::Value, Runtime};
 
     pub fn join(_rt: &mut Runtime, mut args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
+        let this = args.first().unwrap().as_string().unwrap();
         let mut result = this;
         args.remove(0);
         for i in args {
             result.push_str(&i.to_string());
         }
-        Value::String(result)
+        Value::String(result.into())
     }
 
     pub fn len(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
+        let this = args.first().unwrap().as_string().unwrap();
         Value::Number(this.len() as f64)
     }
 
-    pub fn repeat(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
-        let number = args.get(1).unwrap().as_number().unwrap_or(1.0);
-        Value::String(this.repeat(number as usize))
+    pub fn repeat(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let this = args.first().unwrap().as_string().unwrap();
+        let mut number = args.get(1).unwrap().as_number().unwrap_or(1.0) as usize;
+        // stop just over the limit, the result is rejected after call instead of using up memory.
+        if let Some(max) = rt.max_size() {
+            number = number.min(max / this.len().max(1) + 1);
+        }
+        Value::String(this.repeat(number).into())
     }
 
     pub fn is_empty(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
+        let this = args.first().unwrap().as_string().unwrap();
         Value::Boolean(this.is_empty())
     }
 
     pub fn lowercase(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
-        Value::String(this.to_lowercase())
+        let this = args.first().unwrap().as_string().unwrap();
+        Value::String(this.to_lowercase().into())
     }
 
     pub fn uppercase(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
-        Value::String(this.to_uppercase())
+        let this = args.first().unwrap().as_string().unwrap();
+        Value::String(this.to_uppercase().into())
     }
 
     pub fn split(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let this = args.get(0).unwrap().as_string().unwrap();
+        let this = args.first().unwrap().as_string().unwrap();
         let sep = args.get(1).unwrap().as_string().unwrap();
         let result = this
             .split(&sep)
-            .map(|v| Value::String(v.to_string()))
+            .map(|v| Value::String(v.into()))
             .collect::<Vec<Value>>();
-        Value::List(result)
+        Value::List(Rc::new(result))
     }
 
     pub fn export() -> ModuleGenerator {
@@ -127,12 +160,13 @@ mod string {
     }
 }
 
+#[cfg(feature = "stdlib-ext")]
 mod number {
 
     use crate::{module::ModuleGenerator, types::Value, Runtime};
 
     pub fn abs(_rt: &mut Runtime, args: Vec<Value>) -> Value {
-        let num = args.get(0).unwrap().as_number().unwrap();
+        let num = args.first().unwrap().as_number().unwrap();
         Value::Number(num.abs())
     }
 
@@ -145,14 +179,507 @@ mod number {
     }
 }
 
+#[cfg(feature = "stdlib-ext")]
+mod list {
+
+    use std::rc::Rc;
+
+    use super::argument;
+    use crate::{
+        module::ModuleGenerator,
+        types::{FunctionType, Value},
+        Runtime,
+    };
+
+    fn this(rt: &mut Runtime, args: &[Value], name: &str) -> Option<Vec<Value>> {
+        let name = format!("list::{name}");
+        argument(rt, args, 0, &name, "list", Value::as_list)
+    }
+
+    fn callback(rt: &mut Runtime, args: &[Value], name: &str) -> Option<FunctionType> {
+        let name = format!("list::{name}");
+        argument(rt, args, 1, &name, "function", |v| match v {
+            Value::Function(f) => Some(f.clone()),
+            _ => None,
+        })
+    }
+
+    pub fn len(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        match this(rt, &args, "len") {
+            Some(this) => Value::Number(this.len() as f64),
+            None => Value::None,
+        }
+    }
+
+    pub fn is_empty(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        match this(rt, &args, "is_empty") {
+            Some(this) => Value::Boolean(this.is_empty()),
+            None => Value::None,
+        }
+    }
+
+    pub fn filter(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let (this, f) = match (this(rt, &args, "filter"), callback(rt, &args, "filter")) {
+            (Some(this), Some(f)) => (this, f),
+            _ => return Value::None,
+        };
+        let mut result = vec![];
+        for i in this {
+            match rt.call_function(f.clone(), vec![i.clone()]) {
+                Ok(keep) if keep.to_boolean_data() => result.push(i),
+                Ok(_) => {}
+                Err(e) => {
+                    rt.raise(e);
+                    return Value::None;
+                }
+            }
+        }
+        Value::List(Rc::new(result))
+    }
+
+    pub fn map(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let (this, f) = match (this(rt, &args, "map"), callback(rt, &args, "map")) {
+            (Some(this), Some(f)) => (this, f),
+            _ => return Value::None,
+        };
+        let mut result = vec![];
+        for i in this {
+            match rt.call_function(f.clone(), vec![i]) {
+                Ok(v) => result.push(v),
+                Err(e) => {
+                    rt.raise(e);
+                    return Value::None;
+                }
+            }
+        }
+        Value::List(Rc::new(result))
+    }
+
+    pub fn export() -> ModuleGenerator {
+        let mut module = ModuleGenerator::new();
+
+        module.insert_rusty_function("len", len, 1);
+        module.insert_rusty_function("is_empty", is_empty, 1);
+
+        module.insert_rusty_function("filter", filter, 2);
+        module.insert_rusty_function("map", map, 2);
+
+        module
+    }
+}
+
+#[cfg(feature = "stdlib-ext")]
+mod dict {
+
+    use std::{collections::HashMap, rc::Rc};
+
+    use super::argument;
+    use crate::{module::ModuleGenerator, types::Value, Runtime};
+
+    fn this(rt: &mut Runtime, args: &[Value], name: &str) -> Option<HashMap<String, Value>> {
+        let name = format!("dict::{name}");
+        argument(rt, args, 0, &name, "dict", Value::as_dict)
+    }
+
+    pub fn len(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        match this(rt, &args, "len") {
+            Some(this) => Value::Number(this.len() as f64),
+            None => Value::None,
+        }
+    }
+
+    pub fn keys(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let this = match this(rt, &args, "keys") {
+            Some(this) => this,
+            None => return Value::None,
+        };
+        let mut keys = this.into_keys().collect::<Vec<String>>();
+        keys.sort();
+        Value::List(Rc::new(
+            keys.into_iter().map(|v| Value::String(v.into())).collect(),
+        ))
+    }
+
+    pub fn contains(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        match (this(rt, &args, "contains"), args.get(1)) {
+            (Some(this), Some(key)) => Value::Boolean(this.contains_key(&key.to_string())),
+            _ => Value::None,
+        }
+    }
+
+    pub fn export() -> ModuleGenerator {
+        let mut module = ModuleGenerator::new();
+
+        module.insert_rusty_function("len", len, 1);
+        module.insert_rusty_function("keys", keys, 1);
+        module.insert_rusty_function("contains", contains, 2);
+
+        module
+    }
+}
+
+// argument of a std function, a type error is raised when it is missing or has another type.
+// methods are called with any number of arguments, e.g. `[1].map()`, so the count is
+// checked here too.
+#[cfg(feature = "stdlib-ext")]
+fn argument<T>(
+    rt: &mut Runtime,
+    args: &[Value],
+    index: usize,
+    function: &str,
+    expected: &str,
+    convert: impl FnOnce(&Value) -> Option<T>,
+) -> Option<T> {
+    let error = match args.get(index) {
+        Some(v) => match convert(v) {
+            Some(v) => return Some(v),
+            None => RuntimeError::TypeMismatch {
+                target: format!("argument {} of `{function}`", index + 1),
+                expected: expected.to_string(),
+                found: v.value_name(),
+            },
+        },
+        None => RuntimeError::IllegalArgumentsNumber {
+            need: index as i16 + 1,
+            provided: args.len() as i16,
+        },
+    };
+    rt.raise(error);
+    None
+}
+
+mod function {
+
+    use std::{
+        collections::HashMap,
+        rc::Rc,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        function_params_number,
+        module::{ModuleGenerator, RustyFunction},
+        types::{FunctionType, Value},
+        Runtime,
+    };
+
+    // signature of function: `name`, `params`, `variadic` and `arity` (number of fixed params).
+    pub fn info(_rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let f = match args.first() {
+            Some(Value::Function(f)) => f,
+            _ => return Value::None,
+        };
+        let (arity, variadic) = function_params_number(f);
+        let (name, params) = match f.define() {
+            Some(define) => (define.name.clone(), define.params.names()),
+            None => (None, vec![]),
+        };
+        let mut info = HashMap::new();
+        let name = name.map(|v| Value::String(v.into())).unwrap_or(Value::None);
+        info.insert("name".to_string(), name);
+        info.insert(
+            "params".to_string(),
+            Value::List(Rc::new(
+                params
+                    .into_iter()
+                    .map(|v| Value::String(v.as_str().into()))
+                    .collect(),
+            )),
+        );
+        info.insert("variadic".to_string(), Value::Boolean(variadic));
+        info.insert("arity".to_string(), Value::Number(arity as f64));
+        Value::Dict(Rc::new(info))
+    }
+
+    pub fn identity(_rt: &mut Runtime, args: Vec<Value>) -> Value {
+        args.into_iter().next().unwrap_or(Value::None)
+    }
+
+    // call `f` with list of arguments.
+    pub fn apply(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        match (args.first(), args.get(1)) {
+            (Some(f), Some(list)) => call_list(rt, f, list),
+            _ => Value::None,
+        }
+    }
+
+    const CHAIN: &str =
+        "fn (x) { let value = x; for f in fns { value = f(value); } return value; }";
+
+    // `compose(f, g)(x)` is `f(g(x))`.
+    pub fn compose(rt: &mut Runtime, mut args: Vec<Value>) -> Value {
+        args.reverse();
+        rt.host_closure(CHAIN, vec![("fns", Value::List(Rc::new(args)))])
+    }
+
+    // `pipe(f, g)(x)` is `g(f(x))`.
+    pub fn pipe(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        rt.host_closure(CHAIN, vec![("fns", Value::List(Rc::new(args)))])
+    }
+
+    // wrapper keeps `state` between calls, `handler` returns result and the next state.
+    const STATEFUL: &str =
+        "fn (@args) { let (result, next) = handler(f, args, state); state = next; return result; }";
+
+    fn stateful(
+        rt: &mut Runtime,
+        f: Value,
+        handler: RustyFunction,
+        state: Vec<(&str, Value)>,
+    ) -> Value {
+        let state = Rc::new(state.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+        let handler = Value::Function(FunctionType::Rusty((handler, 3)));
+        rt.host_closure(
+            STATEFUL,
+            vec![
+                ("f", f),
+                ("handler", handler),
+                ("state", Value::Dict(state)),
+            ],
+        )
+    }
+
+    // error of the call is raised, the running rust function must not keep its result.
+    fn call_list(rt: &mut Runtime, f: &Value, args: &Value) -> Value {
+        match (f, args) {
+            (Value::Function(f), Value::List(args)) => {
+                match rt.call_function(f.clone(), args.to_vec()) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        rt.raise(e);
+                        Value::None
+                    }
+                }
+            }
+            _ => Value::None,
+        }
+    }
+
+    fn unpack(args: Vec<Value>) -> (Value, Value, HashMap<String, Value>) {
+        let mut args = args.into_iter();
+        let f = args.next().unwrap_or(Value::None);
+        let params = args.next().unwrap_or(Value::None);
+        let state = match args.next() {
+            Some(Value::Dict(state)) => Rc::unwrap_or_clone(state),
+            _ => HashMap::new(),
+        };
+        (f, params, state)
+    }
+
+    fn finish(result: Value, state: HashMap<String, Value>) -> Value {
+        Value::Tuple((Box::new(result), Box::new(Value::Dict(Rc::new(state)))))
+    }
+
+    // milliseconds since unix epoch.
+    fn now() -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs_f64() * 1000.0)
+            .unwrap_or(0.0)
+    }
+
+    // result of every arguments list is cached after first call.
+    pub fn memoize(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let f = args.into_iter().next().unwrap_or(Value::None);
+        stateful(rt, f, memoize_call, vec![])
+    }
+
+    fn memoize_call(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let (f, params, mut state) = unpack(args);
+        // arguments without a key, e.g. functions, are never cached.
+        let key = match cache_key(&params) {
+            Some(key) => key,
+            None => return finish(call_list(rt, &f, &params), state),
+        };
+        if let Some(result) = state.get(&key) {
+            return finish(result.clone(), state);
+        }
+        let result = call_list(rt, &f, &params);
+        state.insert(key, result.clone());
+        finish(result, state)
+    }
+
+    // same key for equal values: dict entries are sorted and every value is tagged with its
+    // type, so `1` and `"1"` differ.
+    fn cache_key(value: &Value) -> Option<String> {
+        let key = match value {
+            Value::None => "n".to_string(),
+            Value::String(v) => format!("s{v:?}"),
+            Value::Number(v) => format!("f{v}"),
+            Value::Boolean(v) => format!("b{v}"),
+            Value::List(list) => {
+                let items: Option<Vec<String>> = list.iter().map(cache_key).collect();
+                format!("l[{}]", items?.join(","))
+            }
+            Value::Tuple((a, b)) => format!("t({},{})", cache_key(a)?, cache_key(b)?),
+            Value::Dict(dict) => {
+                let mut entries = vec![];
+                for (k, v) in dict.iter() {
+                    entries.push(format!("{k:?}:{}", cache_key(v)?));
+                }
+                entries.sort();
+                format!("d{{{}}}", entries.join(","))
+            }
+            Value::Enum((name, variant)) => format!("e{name}::{variant}"),
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    // `f` runs at most once in `wait` milliseconds, skipped calls return the last result.
+    pub fn throttle(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let mut args = args.into_iter();
+        let f = args.next().unwrap_or(Value::None);
+        let wait = args.next().unwrap_or(Value::Number(0.0));
+        stateful(rt, f, throttle_call, vec![("wait", wait)])
+    }
+
+    fn throttle_call(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let (f, params, mut state) = unpack(args);
+        let now = now();
+        let ready = match (state.get("last"), state.get("wait")) {
+            (Some(Value::Number(last)), Some(Value::Number(wait))) => now - last >= *wait,
+            _ => true,
+        };
+        if ready {
+            let result = call_list(rt, &f, &params);
+            state.insert("last".to_string(), Value::Number(now));
+            state.insert("result".to_string(), result);
+        }
+        let result = state.get("result").cloned().unwrap_or(Value::None);
+        finish(result, state)
+    }
+
+    // `f` runs only after `wait` milliseconds without calls, every call restarts the wait.
+    // runtime has no timer yet, so the waiting call runs on leading edge instead of later.
+    pub fn debounce(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let mut args = args.into_iter();
+        let f = args.next().unwrap_or(Value::None);
+        let wait = args.next().unwrap_or(Value::Number(0.0));
+        stateful(rt, f, debounce_call, vec![("wait", wait)])
+    }
+
+    fn debounce_call(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let (f, params, mut state) = unpack(args);
+        let now = now();
+        let ready = match (state.get("last"), state.get("wait")) {
+            (Some(Value::Number(last)), Some(Value::Number(wait))) => now - last >= *wait,
+            _ => true,
+        };
+        state.insert("last".to_string(), Value::Number(now));
+        let result = if ready {
+            call_list(rt, &f, &params)
+        } else {
+            Value::None
+        };
+        finish(result, state)
+    }
+
+    pub fn export() -> ModuleGenerator {
+        let mut module = ModuleGenerator::new();
+
+        module.insert_rusty_function("info", info, 1);
+        module.insert_rusty_function("identity", identity, 1);
+        module.insert_rusty_function("apply", apply, 2);
+        module.insert_rusty_function("compose", compose, -1);
+        module.insert_rusty_function("pipe", pipe, -1);
+        module.insert_rusty_function("memoize", memoize, 1);
+        module.insert_rusty_function("throttle", throttle, 2);
+        module.insert_rusty_function("debounce", debounce, 2);
+
+        module
+    }
+}
+
+mod element {
+
+    use crate::{module::ModuleGenerator, types::Value, Runtime};
+
+    // `Value::sanitize`: element safe to write as html, lists and dicts of elements are
+    // sanitized too.
+    pub fn sanitize(_rt: &mut Runtime, mut args: Vec<Value>) -> Value {
+        let mut value = args.pop().unwrap_or(Value::None);
+        value.sanitize();
+        value
+    }
+
+    pub fn export() -> ModuleGenerator {
+        let mut module = ModuleGenerator::new();
+
+        module.insert_rusty_function("sanitize", sanitize, 1);
+
+        module
+    }
+}
+
+mod modules {
+
+    use std::{collections::HashMap, rc::Rc};
+
+    use crate::{
+        module::{ModuleGenerator, ModuleItem},
+        types::Value,
+        Runtime,
+    };
+
+    // `register("utils", { helper: fn () {...} })` makes `use utils::helper;` available,
+    // nested dicts become sub modules. returns `false` if the module can not be registered.
+    pub fn register(rt: &mut Runtime, args: Vec<Value>) -> Value {
+        let (name, items) = match (args.first(), args.get(1)) {
+            (Some(Value::String(name)), Some(Value::Dict(items))) => (name, items),
+            _ => return Value::Boolean(false),
+        };
+        // `std` can not be replaced, and read-only runtime only uses host modules.
+        if &**name == "std" || rt.read_only {
+            return Value::Boolean(false);
+        }
+        rt.bind_module(name, generator(HashMap::clone(items)));
+        Value::Boolean(true)
+    }
+
+    fn generator(items: HashMap<String, Value>) -> ModuleGenerator {
+        let mut module = ModuleGenerator::new();
+        for (name, value) in items {
+            match value {
+                Value::Function(f) => module.insert(&name, ModuleItem::Function(f)),
+                Value::Dict(sub) => {
+                    module.insert_sub_module(&name, generator(Rc::unwrap_or_clone(sub)))
+                }
+                value => module.insert(&name, ModuleItem::Variable(value)),
+            }
+        }
+        module
+    }
+
+    pub fn export() -> ModuleGenerator {
+        let mut module = ModuleGenerator::new();
+
+        module.insert_rusty_function("register", register, 2);
+
+        module
+    }
+}
+
 pub fn std() -> ModuleGenerator {
+    #[allow(unused_mut)]
     let mut export = root::export();
-    export.insert_sub_module("string", string::export());
-    export.insert_sub_module("number", number::export());
+    export.insert_sub_module("fn", function::export());
+    export.insert_sub_module("module", modules::export());
+    export.insert_sub_module("element", element::export());
+    #[cfg(feature = "stdlib-ext")]
+    {
+        export.insert_sub_module("string", string::export());
+        export.insert_sub_module("number", number::export());
+        export.insert_sub_module("list", list::export());
+        export.insert_sub_module("dict", dict::export());
+    }
     export
 }
 
 pub fn auto_use() -> Vec<String> {
-    let v = vec!["std::print", "std::println", "std::type", "std::execute"];
+    let mut v = vec!["std::type", "std::execute"];
+    if cfg!(feature = "console") {
+        v.extend(["std::print", "std::println"]);
+    }
     v.iter().map(|v| v.to_string()).collect()
 }
@@ -1,35 +1,69 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
-use dioscript_parser::ast::{CalculateMark, FunctionDefine};
-use uuid::Uuid;
+use dioscript_parser::{
+    ast::{CalculateMark, FunctionDefine, ObjectDefine, Span},
+    symbol::Symbol,
+};
 
 use crate::error::RuntimeError;
 
+slotmap::new_key_type! {
+    // identifier of runtime data: u32 index with u32 generation.
+    pub struct DataId;
+}
+
+// strings, lists and dicts are shared between clones, and copied only when they are changed.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     None,
-    String(String),
+    String(Rc<str>),
     Number(f64),
     Boolean(bool),
-    List(Vec<Value>),
-    Dict(HashMap<String, Value>),
+    List(Rc<Vec<Value>>),
+    Dict(Rc<HashMap<String, Value>>),
     Tuple((Box<Value>, Box<Value>)),
     Element(Element),
     Function(FunctionType),
-    Reference(Uuid),
+    // enum variant: (enum name, variant name)
+    Enum((String, String)),
+    // object define, call it to construct an instance dict.
+    Object(ObjectDefine),
+    Reference(DataId),
 }
 
+// script define is shared by clones of the function, calls don't copy the function body.
 #[derive(Debug, Clone, PartialEq)]
 pub enum FunctionType {
     Rusty((crate::module::RustyFunction, i32)),
-    DScript(FunctionDefine),
+    DScript(Rc<FunctionDefine>),
+    // script function with variables captured (by reference) where it was created, and `use`
+    // names visible there.
+    Closure((Rc<FunctionDefine>, HashMap<Symbol, DataId>, UsedModules)),
+}
+
+// short name => module path, from `use` statements.
+pub type UsedModules = HashMap<Symbol, Vec<Symbol>>;
+
+impl FunctionType {
+    // script define of function, `None` for rust function.
+    pub fn define(&self) -> Option<&FunctionDefine> {
+        match self {
+            FunctionType::Rusty(_) => None,
+            FunctionType::DScript(define) | FunctionType::Closure((define, ..)) => Some(&**define),
+        }
+    }
+
+    // number of fixed parameters, and whether more arguments are accepted.
+    pub fn arity(&self) -> (usize, bool) {
+        crate::function_params_number(self)
+    }
 }
 
 impl ToString for Value {
     fn to_string(&self) -> String {
         match self {
             Value::None => "none".to_string(),
-            Value::String(v) => v.clone(),
+            Value::String(v) => v.to_string(),
             Value::Number(v) => v.to_string(),
             Value::Boolean(v) => v.to_string(),
             Value::List(_) => "[ /* list */ ]".to_string(),
@@ -37,6 +71,8 @@ impl ToString for Value {
             Value::Tuple(_) => "( /* tuple */ )".to_string(),
             Value::Element(_) => "element { /* element attributes */  }".to_string(),
             Value::Function(_) => "fn () { /* function impl */  }".to_string(),
+            Value::Enum((name, variant)) => format!("{name}::{variant}"),
+            Value::Object(o) => format!("object {} {{ /* object define */ }}", o.name),
             Value::Reference(_) => "/* &reference */".to_string(),
         }
     }
@@ -54,6 +90,8 @@ impl Value {
             Value::Tuple(_) => "tuple",
             Value::Element(_) => "element",
             Value::Function(_) => "function",
+            Value::Enum(_) => "enum",
+            Value::Object(_) => "object",
             Value::Reference(_) => "reference",
         }
         .to_string()
@@ -93,7 +131,7 @@ impl Value {
 
     pub fn as_list(&self) -> Option<Vec<Value>> {
         if let Self::List(v) = self {
-            Some(v.clone())
+            Some(v.to_vec())
         } else {
             None
         }
@@ -101,7 +139,7 @@ impl Value {
 
     pub fn as_dict(&self) -> Option<HashMap<String, Value>> {
         if let Self::Dict(v) = self {
-            Some(v.clone())
+            Some(HashMap::clone(v))
         } else {
             None
         }
@@ -123,6 +161,41 @@ impl Value {
         }
     }
 
+    // reference is only stored in runtime data, value without it can be used directly.
+    pub fn has_reference(&self) -> bool {
+        match self {
+            Value::Reference(_) => true,
+            Value::List(v) => v.iter().any(|v| v.has_reference()),
+            Value::Dict(v) => v.values().any(|v| v.has_reference()),
+            Value::Tuple((a, b)) => a.has_reference() || b.has_reference(),
+            _ => false,
+        }
+    }
+
+    // `Element::sanitize` for elements in the value, lists and dicts included. blocked element
+    // in a list is removed, other blocked element becomes `none`. strings are written to html
+    // as they are, so they are escaped.
+    pub fn sanitize(&mut self) {
+        match self {
+            Value::Element(e) if e.is_blocked() => *self = Value::None,
+            Value::Element(e) => e.sanitize(),
+            Value::String(v) => *v = escape_html(v).into(),
+            Value::List(list) => {
+                let list = Rc::make_mut(list);
+                list.retain(|v| !matches!(v, Value::Element(e) if e.is_blocked()));
+                for v in list.iter_mut() {
+                    v.sanitize();
+                }
+            }
+            Value::Dict(dict) => {
+                for v in Rc::make_mut(dict).values_mut() {
+                    v.sanitize();
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub fn to_boolean_data(&self) -> bool {
         match self {
             Value::Number(v) => *v != 0.0,
@@ -131,6 +204,17 @@ impl Value {
         }
     }
 
+    // bitwise operators work on i64, fractional number is not allowed.
+    fn to_integer(v: f64, operator: &str) -> Result<i64, RuntimeError> {
+        if v.fract() != 0.0 || !v.is_finite() {
+            return Err(RuntimeError::NonIntegerOperand {
+                operator: operator.to_string(),
+                value: v,
+            });
+        }
+        Ok(v as i64)
+    }
+
     pub fn calc(&self, o: &Value, s: CalculateMark) -> Result<Value, RuntimeError> {
         if self.value_name() != o.value_name() {
             return Err(RuntimeError::CompareDiffType {
@@ -141,7 +225,9 @@ impl Value {
 
         return match s {
             CalculateMark::Plus => match self {
-                Value::String(v) => Ok(Self::String(format!("{}{}", v, o.as_string().unwrap()))),
+                Value::String(v) => Ok(Self::String(
+                    format!("{}{}", v, o.as_string().unwrap()).into(),
+                )),
                 Value::Number(v) => Ok(Self::Number(v + o.as_number().unwrap())),
                 _ => Err(RuntimeError::IllegalOperatorForType {
                     operator: "+".to_string(),
@@ -169,15 +255,52 @@ impl Value {
                     value_type: self.value_name(),
                 }),
             },
+            CalculateMark::BitAnd
+            | CalculateMark::BitOr
+            | CalculateMark::BitXor
+            | CalculateMark::Shl
+            | CalculateMark::Shr => match self {
+                Value::Number(v) => {
+                    let operator = s.to_string();
+                    let l = Self::to_integer(*v, &operator)?;
+                    let r = Self::to_integer(o.as_number().unwrap(), &operator)?;
+                    let result = match s {
+                        CalculateMark::BitAnd => l & r,
+                        CalculateMark::BitOr => l | r,
+                        CalculateMark::BitXor => l ^ r,
+                        _ if !(0..64).contains(&r) => {
+                            return Err(RuntimeError::ShiftOutOfRange {
+                                operator,
+                                amount: r,
+                            })
+                        }
+                        CalculateMark::Shl => l << r,
+                        _ => l >> r,
+                    };
+                    Ok(Self::Number(result as f64))
+                }
+                _ => Err(RuntimeError::IllegalOperatorForType {
+                    operator: s.to_string(),
+                    value_type: self.value_name(),
+                }),
+            },
+            CalculateMark::Power => match self {
+                Value::Number(v) => Ok(Self::Number(v.powf(o.as_number().unwrap()))),
+                _ => Err(RuntimeError::IllegalOperatorForType {
+                    operator: "**".to_string(),
+                    value_type: self.value_name(),
+                }),
+            },
 
             CalculateMark::Equal => match self {
                 Value::String(v) => Ok(Value::Boolean(v.to_string() == o.as_string().unwrap())),
                 Value::Number(v) => Ok(Value::Boolean(*v == o.as_number().unwrap())),
                 Value::Boolean(v) => Ok(Value::Boolean(*v == o.as_boolean().unwrap())),
-                Value::List(v) => Ok(Value::Boolean(v.clone() == o.as_list().unwrap())),
-                Value::Dict(v) => Ok(Value::Boolean(v.clone() == o.as_dict().unwrap())),
-                Value::Tuple(v) => Ok(Value::Boolean(v.clone() == o.as_tuple().unwrap())),
-                Value::Element(v) => Ok(Value::Boolean(v.clone() == o.as_element().unwrap())),
+                Value::List(_)
+                | Value::Dict(_)
+                | Value::Tuple(_)
+                | Value::Element(_)
+                | Value::Enum(_) => Ok(Value::Boolean(self == o)),
                 _ => Err(RuntimeError::IllegalOperatorForType {
                     operator: "==".to_string(),
                     value_type: self.value_name(),
@@ -187,10 +310,11 @@ impl Value {
                 Value::String(v) => Ok(Value::Boolean(v.to_string() != o.as_string().unwrap())),
                 Value::Number(v) => Ok(Value::Boolean(*v != o.as_number().unwrap())),
                 Value::Boolean(v) => Ok(Value::Boolean(*v != o.as_boolean().unwrap())),
-                Value::List(v) => Ok(Value::Boolean(v.clone() != o.as_list().unwrap())),
-                Value::Dict(v) => Ok(Value::Boolean(v.clone() != o.as_dict().unwrap())),
-                Value::Tuple(v) => Ok(Value::Boolean(v.clone() != o.as_tuple().unwrap())),
-                Value::Element(v) => Ok(Value::Boolean(v.clone() != o.as_element().unwrap())),
+                Value::List(_)
+                | Value::Dict(_)
+                | Value::Tuple(_)
+                | Value::Element(_)
+                | Value::Enum(_) => Ok(Value::Boolean(self != o)),
                 _ => Err(RuntimeError::IllegalOperatorForType {
                     operator: "!=".to_string(),
                     value_type: self.value_name(),
@@ -246,11 +370,22 @@ impl Value {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Element {
     pub name: String,
     pub attributes: HashMap<String, Value>,
     pub content: Vec<ElementContentType>,
+    // source range of the script element, `None` for elements built by host.
+    pub span: Option<Span>,
+}
+
+// equal elements written in different places are still equal.
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.attributes == other.attributes
+            && self.content == other.content
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -261,10 +396,96 @@ pub enum ElementContentType {
 
 impl Element {
     pub fn to_html(&self) -> String {
+        let attr_str = self.attributes_html();
+        let mut content_str = String::new();
+        for sub in &self.content {
+            let v = match sub {
+                ElementContentType::Children(v) => v.to_html(),
+                ElementContentType::Content(v) => v.clone(),
+            };
+            content_str.push_str(&v);
+        }
+        let result = format!("<{tag}{attr_str}>{content_str}</{tag}>", tag = self.name);
+        result
+    }
+
+    // indented html, every child element starts a new line.
+    pub fn to_pretty_html(&self) -> String {
+        let mut result = String::new();
+        self.write_pretty_html(0, &mut result);
+        result
+    }
+
+    fn write_pretty_html(&self, depth: usize, output: &mut String) {
+        let indent = "  ".repeat(depth);
+        let attr_str = self.attributes_html();
+        let has_children = self
+            .content
+            .iter()
+            .any(|v| matches!(v, ElementContentType::Children(_)));
+        if !has_children {
+            // text only element stays in one line.
+            output.push_str(&format!(
+                "{indent}<{tag}{attr_str}>{content}</{tag}>\n",
+                tag = self.name,
+                content = self.text_content(),
+            ));
+            return;
+        }
+        output.push_str(&format!("{indent}<{}{attr_str}>\n", self.name));
+        for sub in &self.content {
+            match sub {
+                ElementContentType::Children(v) => v.write_pretty_html(depth + 1, output),
+                ElementContentType::Content(v) => output.push_str(&format!("{indent}  {v}\n")),
+            }
+        }
+        output.push_str(&format!("{indent}</{}>\n", self.name));
+    }
+
+    // make element built from untrusted data safe to write as html: remove children which
+    // embed other documents or run script (`script`, `iframe`, `style`, ...), attributes with
+    // invalid names, `on*` event attributes and `javascript:`, `vbscript:` and `data:` urls.
+    // text content is escaped, attribute values are escaped by `to_html` already.
+    pub fn sanitize(&mut self) {
+        self.attributes.retain(|name, value| {
+            is_valid_name(name) && !is_event_attribute(name) && !is_script_url(value)
+        });
+        self.content.retain_mut(|sub| match sub {
+            ElementContentType::Children(child) if child.is_blocked() => false,
+            ElementContentType::Children(child) => {
+                child.sanitize();
+                true
+            }
+            ElementContentType::Content(v) => {
+                *v = escape_html(v);
+                true
+            }
+        });
+    }
+
+    fn is_blocked(&self) -> bool {
+        const BLOCKED: [&str; 11] = [
+            "script", "style", "iframe", "frame", "frameset", "object", "embed", "applet", "base",
+            "link", "meta",
+        ];
+        !is_valid_name(&self.name) || BLOCKED.iter().any(|v| self.name.eq_ignore_ascii_case(v))
+    }
+
+    fn text_content(&self) -> String {
+        let mut content_str = String::new();
+        for sub in &self.content {
+            if let ElementContentType::Content(v) = sub {
+                content_str.push_str(v);
+            }
+        }
+        content_str
+    }
+
+    fn attributes_html(&self) -> String {
         let mut attr_str = String::new();
         for (name, value) in &self.attributes {
             if let Value::String(value) = value {
-                attr_str.push_str(&format!(" {0}=\"{1}\"", name, value));
+                attr_str.push_str(&format!(" {0}=\"{1}\"", name, escape_html(value)));
             } else if let Value::Boolean(value) = value {
                 if *value {
                     attr_str.push_str(&format!(" {name}"));
@@ -273,16 +494,57 @@ impl Element {
                 attr_str.push_str(&format!(" {0}=\"{1}\"", name, value));
             }
         }
-        let mut content_str = String::new();
-        for sub in &self.content {
-            let v = match sub {
-                ElementContentType::Children(v) => v.to_html(),
-                ElementContentType::Content(v) => v.clone(),
-            };
-            content_str.push_str(&v);
+        attr_str
+    }
+}
+
+fn is_event_attribute(name: &str) -> bool {
+    name.len() > 2
+        && name
+            .get(..2)
+            .map(|v| v.eq_ignore_ascii_case("on"))
+            .unwrap_or(false)
+}
+
+// browsers skip whitespace and control characters in url scheme: `java\tscript:`.
+fn is_script_url(value: &Value) -> bool {
+    match value {
+        Value::String(v) => {
+            let url: String = v
+                .chars()
+                .filter(|c| !c.is_whitespace() && !c.is_control())
+                .collect();
+            match url.split_once(':') {
+                Some((scheme, _)) => ["javascript", "vbscript", "data"]
+                    .iter()
+                    .any(|v| scheme.eq_ignore_ascii_case(v)),
+                None => false,
+            }
         }
-        let result = format!("<{tag}{attr_str}>{content_str}</{tag}>", tag = self.name);
-        result
+        _ => false,
     }
 }
 
+// tag and attribute names which can not end the tag or start another attribute.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+}
+
+// text for html content and quoted attribute values.
+fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
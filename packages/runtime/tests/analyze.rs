@@ -0,0 +1,90 @@
+use dioscript_parser::ast::DioscriptAst;
+use dioscript_runtime::Runtime;
+
+// calls reported by analysis, as `name: error`.
+fn issues(code: &str) -> Vec<String> {
+    let ast = DioscriptAst::from_string(code).unwrap();
+    Runtime::new()
+        .analyze(&ast)
+        .iter()
+        .map(|v| format!("{}: {}", v.call, v.error))
+        .collect()
+}
+
+#[test]
+fn nested_functions_are_keyed_by_path() {
+    let code = r#"
+        fn a() {
+            fn inner(x) { return x; }
+            return inner(1);
+        }
+        fn b() {
+            fn inner(x, y) { return x + y; }
+            return inner(1, 2);
+        }
+        return [a(), b()];
+    "#;
+    assert_eq!(issues(code), Vec::<String>::new());
+}
+
+#[test]
+fn block_definitions_are_not_visible_outside() {
+    let code = r#"
+        fn a() {
+            let f = 1;
+            return f;
+        }
+        if true {
+            fn g() { return 1; }
+            g();
+        }
+        f();
+        return g(1);
+    "#;
+    let issues = issues(code);
+    assert_eq!(issues.len(), 2, "{issues:?}");
+    assert!(issues[0].starts_with("f: "), "{issues:?}");
+    assert!(issues[1].starts_with("g: "), "{issues:?}");
+}
+
+#[test]
+fn redefined_functions_skip_arity() {
+    let code = r#"
+        fn f(a) { return a; }
+        let x = f(1);
+        fn f(a, b) { return a + b; }
+        return f(1, 2) + x;
+    "#;
+    assert_eq!(issues(code), Vec::<String>::new());
+}
+
+#[test]
+fn registered_modules_are_skipped() {
+    let code = r#"
+        fn to_lower(text) { return text.lowercase(); }
+        std::module::register("text", { "lower": to_lower });
+        use text::lower;
+        lower("A");
+        text::lower("A");
+        return other::lower("A");
+    "#;
+    let issues = issues(code);
+    assert_eq!(issues.len(), 1, "{issues:?}");
+    assert!(issues[0].starts_with("other::lower: "), "{issues:?}");
+}
+
+#[test]
+fn issues_and_warnings_are_located() {
+    let code =
+        "fn f(a) { return a; }\nlet x = f(1, 2);\nreturn div { class: \"a\", class: \"b\" };";
+    let ast = DioscriptAst::from_string(code).unwrap();
+    let issues = Runtime::new().analyze(&ast);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(
+        &code[issues[0].span.start..issues[0].span.end],
+        "let x = f(1, 2);"
+    );
+    let warnings = ast.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(code[warnings[0].span().start..].starts_with("return div"));
+}
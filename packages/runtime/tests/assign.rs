@@ -0,0 +1,137 @@
+mod common;
+
+use common::run;
+use dioscript_runtime::{bytecode::Engine, types::Value, Runtime};
+
+#[test]
+fn outer_assigns_where_function_is_defined() {
+    let code = r#"
+        let x = 0;
+        fn g() { outer x = 1; }
+        fn f() {
+            let x = 100;
+            g();
+            return x;
+        }
+        let a = f();
+        return (a, x);
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((Box::new(Value::Number(100.0)), Box::new(Value::Number(1.0))))
+    );
+}
+
+#[test]
+fn outer_assigns_captured_variable() {
+    let code = r#"
+        fn counter() {
+            let n = 0;
+            fn inc() { outer n = n + 1; }
+            inc();
+            inc();
+            return n;
+        }
+        return counter();
+    "#;
+    assert_eq!(run(code), Value::Number(2.0));
+}
+
+#[test]
+fn outer_skips_shadowing_local() {
+    let code = r#"
+        let count = 0;
+        fn inc() {
+            let count = 100;
+            outer count = count + 1;
+            return count;
+        }
+        let a = inc();
+        return (a, count);
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((
+            Box::new(Value::Number(100.0)),
+            Box::new(Value::Number(101.0))
+        ))
+    );
+}
+
+#[test]
+fn global_assigns_top_level_variable() {
+    let code = r#"
+        let total = 0;
+        fn add(v) {
+            let total = 5;
+            global total = total + v;
+        }
+        add(1);
+        add(2);
+        return total;
+    "#;
+    assert_eq!(run(code), Value::Number(7.0));
+}
+
+#[test]
+fn read_only_guards_enum_and_object_defines() {
+    for code in ["enum Mode { Light, Dark }", "object Mode { n = 0; }"] {
+        for engine in [Engine::Interpreter, Engine::Bytecode] {
+            let mut runtime = Runtime::new();
+            runtime.set_engine(engine);
+            runtime.set_global("Mode", Value::Number(1.0));
+            runtime.set_read_only(true);
+            let error = runtime.execute(code).unwrap_err();
+            assert!(error.to_string().contains("`Mode`"), "{error}");
+            let mode = runtime.execute("return Mode;").unwrap();
+            assert_eq!(mode, Value::Number(1.0));
+        }
+    }
+}
+
+#[test]
+fn contextual_keywords_are_names() {
+    let code = r#"
+        let outer = 1;
+        let global = 2;
+        let defer = 3;
+        let typeof = 4;
+        let is = 5;
+        let do = 6;
+        let break = 7;
+        outer = outer + 10;
+        global = global + 10;
+        fn sum(is, typeof) { return is + typeof; }
+        let next = break + 1;
+        let checked = is is number;
+        return [outer, global, defer, typeof, is, do, next, sum(1, 2), checked];
+    "#;
+    let expected: Vec<Value> = [11.0, 12.0, 3.0, 4.0, 5.0, 6.0, 8.0, 3.0]
+        .into_iter()
+        .map(Value::Number)
+        .chain([Value::Boolean(true)])
+        .collect();
+    assert_eq!(run(code), Value::List(expected.into()));
+}
+
+#[test]
+fn strict_types_check_reassignment() {
+    let codes = [
+        "let a: number = 1; a = \"x\";",
+        "fn f(n: number) { n = \"x\"; } f(1);",
+        "let a: number = 1; fn f() { outer a = \"x\"; } f();",
+    ];
+    for code in codes {
+        for engine in [Engine::Interpreter, Engine::Bytecode] {
+            let mut runtime = Runtime::new();
+            runtime.set_engine(engine);
+            runtime.set_strict_types(true);
+            let error = runtime.execute(code).unwrap_err();
+            assert_eq!(error.code(), "E0021", "{error}");
+        }
+    }
+    assert_eq!(
+        run("let a: number = 1; a = \"x\"; return a;"),
+        Value::String("x".into())
+    );
+}
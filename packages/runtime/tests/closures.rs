@@ -0,0 +1,102 @@
+mod common;
+
+use common::run;
+use dioscript_parser::ast::parse_function;
+use dioscript_runtime::types::Value;
+
+fn captures(code: &str) -> Vec<String> {
+    let define = parse_function(code).unwrap();
+    define.captures.iter().map(|v| v.to_string()).collect()
+}
+
+#[test]
+fn only_free_variables_are_captured() {
+    let code = r#"
+        fn (a) {
+            let b = a + c;
+            for item in list {
+                b = b + item;
+            }
+            fn inner(x) { return x + d + b; }
+            return inner(e);
+        }
+    "#;
+    assert_eq!(captures(code), vec!["c", "list", "d", "e"]);
+}
+
+#[test]
+fn counters_keep_their_own_state() {
+    let code = r#"
+        fn make_counter() {
+            let count = 0;
+            return fn () {
+                count = count + 1;
+                return count;
+            };
+        }
+        let a = make_counter();
+        let b = make_counter();
+        a();
+        a();
+        return [a(), b()];
+    "#;
+    let expected = Value::List(vec![Value::Number(3.0), Value::Number(1.0)].into());
+    assert_eq!(run(code), expected);
+}
+
+#[test]
+fn nested_closures_capture_through_levels() {
+    let code = r#"
+        fn level(x) {
+            return fn (y) {
+                return fn (z) { return x + y + z; };
+            };
+        }
+        let f = level(1);
+        let g = f(10);
+        let x = 1000;
+        return g(100);
+    "#;
+    assert_eq!(run(code), Value::Number(111.0));
+}
+
+#[test]
+fn loop_iterations_capture_own_variable() {
+    let code = r#"
+        let fns = {};
+        for name in ["a", "b", "c"] {
+            fns[name] = fn () { return "item " + name; };
+        }
+        let first = fns["a"];
+        let last = fns["c"];
+        return [first(), last()];
+    "#;
+    let expected = Value::List(
+        vec![
+            Value::String("item a".into()),
+            Value::String("item c".into()),
+        ]
+        .into(),
+    );
+    assert_eq!(run(code), expected);
+}
+
+#[test]
+fn nested_function_recursion() {
+    let code = r#"
+        fn make() {
+            fn fib(n) {
+                if n < 2 {
+                    return n;
+                }
+                let a = n - 1;
+                let b = n - 2;
+                return fib(a) + fib(b);
+            }
+            return fib;
+        }
+        let fib = make();
+        return fib(10);
+    "#;
+    assert_eq!(run(code), Value::Number(55.0));
+}
@@ -0,0 +1,24 @@
+// every test file uses only some of these.
+#![allow(dead_code)]
+
+use dioscript_runtime::{bytecode::Engine, error::Error, types::Value, Runtime};
+
+// result of the script in both engines, they must agree.
+pub fn run(code: &str) -> Value {
+    let tree = Runtime::new().execute(code).unwrap();
+    let mut runtime = Runtime::new();
+    runtime.set_engine(Engine::Bytecode);
+    let bytecode = runtime.execute(code).unwrap();
+    assert_eq!(tree, bytecode);
+    tree
+}
+
+// error of the script, both engines must fail with the same message.
+pub fn run_err(code: &str) -> Error {
+    let tree = Runtime::new().execute(code).unwrap_err();
+    let mut runtime = Runtime::new();
+    runtime.set_engine(Engine::Bytecode);
+    let bytecode = runtime.execute(code).unwrap_err();
+    assert_eq!(tree.to_string(), bytecode.to_string());
+    tree
+}
@@ -0,0 +1,132 @@
+use dioscript_parser::{
+    ast::DioscriptAst, error::ParseError, session::ParseSession, stream::StreamParser,
+};
+
+const CODE: &str = "let a = 1;\nfn f(x) {\n    let in = x;\n    return in;\n}\n";
+
+#[test]
+fn checker_errors_mark_innermost_statement() {
+    let error = DioscriptAst::from_string(CODE).unwrap_err();
+    assert!(
+        matches!(error, ParseError::ReservedKeyword { .. }),
+        "{error}"
+    );
+    let diagnostic = error.diagnostic();
+    assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(5)));
+    let span = diagnostic.span.unwrap();
+    assert_eq!(&CODE[span], "let in = x;");
+}
+
+#[test]
+fn lossy_session_and_stream_errors_agree() {
+    let expected = DioscriptAst::from_string(CODE).unwrap_err().diagnostic();
+    let (_, diagnostics) = DioscriptAst::from_string_lossy(CODE);
+    assert_eq!(diagnostics, vec![expected.clone()]);
+
+    let error = ParseSession::new(CODE).ast().unwrap_err();
+    assert_eq!(error.diagnostic(), expected);
+
+    let mut parser = StreamParser::new();
+    for line in CODE.split_inclusive('\n') {
+        parser.feed(line);
+    }
+    let error = parser.finish().unwrap_err();
+    assert_eq!(error.diagnostic(), expected);
+}
+
+#[test]
+fn syntax_error_points_at_failure() {
+    let code = "let a = 1;\nlet b = [1, 2;\n";
+    let error = DioscriptAst::from_string(code).unwrap_err();
+    let diagnostic = error.diagnostic();
+    assert_eq!((diagnostic.line, diagnostic.column), (Some(2), Some(14)));
+    assert!(error.to_string().contains("(parsing list)"), "{error}");
+    assert_eq!(diagnostic.context, vec!["list"]);
+    assert_eq!(diagnostic.expected, vec!["`,`", "`]`"]);
+
+    let (_, diagnostics) = DioscriptAst::from_string_lossy(code);
+    assert_eq!(diagnostics, vec![diagnostic]);
+}
+
+#[test]
+fn lossy_skips_whole_failed_statement() {
+    let code = "return div { class: };\nlet a = 1;\nlet b = ;\n";
+    let (ast, diagnostics) = DioscriptAst::from_string_lossy(code);
+    let lines: Vec<_> = diagnostics.iter().map(|v| v.line).collect();
+    assert_eq!(lines, vec![Some(1), Some(3)]);
+    assert_eq!(ast.stats.len(), 1);
+}
+
+#[test]
+fn duplicate_parameter_has_span() {
+    let code = "fn f(a, a) { return a; }";
+    let diagnostic = DioscriptAst::from_string(code).unwrap_err().diagnostic();
+    assert_eq!((diagnostic.line, diagnostic.column), (Some(1), Some(1)));
+    assert_eq!(diagnostic.span, Some(0..code.len()));
+}
+
+#[test]
+fn duplicate_variant_has_span() {
+    let code = "let a = 1;\nenum Color { Red, Red }";
+    let error = DioscriptAst::from_string(code).unwrap_err();
+    assert!(
+        matches!(error, ParseError::DuplicateVariant { .. }),
+        "{error}"
+    );
+    let diagnostic = error.diagnostic();
+    assert_eq!((diagnostic.line, diagnostic.column), (Some(2), Some(1)));
+    assert_eq!(diagnostic.span, Some(11..code.len()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn diagnostics_serialize() {
+    use dioscript_runtime::Runtime;
+
+    let diagnostic = DioscriptAst::from_string(CODE).unwrap_err().diagnostic();
+    let json = serde_json::to_value(&diagnostic).unwrap();
+    assert_eq!(json["line"], 3);
+    assert_eq!(json["span"]["start"], 25);
+
+    let error = Runtime::new().execute("return 1 / \"a\";").unwrap_err();
+    let json = serde_json::to_value(error.info()).unwrap();
+    assert_eq!(json["stage"], "Runtime");
+}
+
+#[test]
+fn fragment_parsers_return_rest_and_parse_errors() {
+    let (rest, _) = dioscript_parser::parser::parse_expr("1 + 2; let a = 1;").unwrap();
+    assert_eq!(rest, "; let a = 1;");
+
+    let error = dioscript_parser::parser::parse_function_define("\nfn (a, b) { + }").unwrap_err();
+    assert!(matches!(error, ParseError::ParseFailure { .. }), "{error}");
+}
+
+#[test]
+fn unknown_annotation_type_is_rejected() {
+    let code = "let a = 1;\nlet b: foo = 1;\n";
+    let error = DioscriptAst::from_string(code).unwrap_err();
+    assert!(matches!(error, ParseError::UnknownType { .. }), "{error}");
+    let expected = error.diagnostic();
+    assert_eq!((expected.line, expected.column), (Some(2), Some(1)));
+
+    let (ast, diagnostics) = DioscriptAst::from_string_lossy(code);
+    assert_eq!(diagnostics, vec![expected.clone()]);
+    assert_eq!(ast.stats.len(), 1);
+    let error = ParseSession::new(code).ast().unwrap_err();
+    assert_eq!(error.diagnostic(), expected);
+    let mut parser = StreamParser::new();
+    for line in code.split_inclusive('\n') {
+        parser.feed(line);
+    }
+    assert_eq!(parser.finish().unwrap_err().diagnostic(), expected);
+
+    // objects may be used as types before they are defined.
+    let code = "fn f(c: Counter) { return c; }\nobject Counter { n = 0; }\n";
+    assert!(DioscriptAst::from_string(code).is_ok());
+    let mut parser = StreamParser::new();
+    for line in code.split_inclusive('\n') {
+        parser.feed(line);
+    }
+    assert!(parser.finish().is_ok());
+}
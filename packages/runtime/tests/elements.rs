@@ -0,0 +1,36 @@
+mod common;
+
+use common::{run, run_err};
+use dioscript_runtime::types::Value;
+
+fn class(value: Value) -> Value {
+    match value {
+        Value::Element(e) => e.attributes["class"].clone(),
+        value => panic!("element expected, got {value:?}"),
+    }
+}
+
+#[test]
+fn shorthand_class_is_merged_with_variable() {
+    let code = r#"
+        let c = "wide";
+        return div.card#main { class: c };
+    "#;
+    assert_eq!(class(run(code)), Value::String("card wide".into()));
+}
+
+#[test]
+fn shorthand_class_is_merged_with_literal_and_block() {
+    let literal = r#"return div.card.big { class: "wide" };"#;
+    assert_eq!(class(run(literal)), Value::String("card big wide".into()));
+    let block = r#"return div.card { if true { return ("class", "wide"); } };"#;
+    assert_eq!(class(run(block)), Value::String("card wide".into()));
+    let alone = r#"return div.card {};"#;
+    assert_eq!(class(run(alone)), Value::String("card".into()));
+}
+
+#[test]
+fn shorthand_class_rejects_other_types() {
+    let error = run_err(r#"return div.card { class: [1] };"#);
+    assert!(error.to_string().contains("attribute `class`"), "{error}");
+}
@@ -0,0 +1,61 @@
+use dioscript_runtime::{types::Value, Runtime};
+
+#[test]
+fn eval_expr_rejects_returned_functions() {
+    let mut runtime = Runtime::new();
+    for code in ["fn (x) { return x; }", "|x| x", "[1, |x| x]"] {
+        let error = runtime.eval_expr(code).unwrap_err();
+        assert_eq!(error.code(), "E0034", "{code}: {error}");
+    }
+}
+
+#[test]
+fn eval_expr_returns_data() {
+    let mut runtime = Runtime::new();
+    assert_eq!(runtime.eval_expr("1 + 2").unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn ast_parsed_on_another_thread() {
+    let ast = std::thread::spawn(|| {
+        dioscript_parser::ast::DioscriptAst::from_string("let name = \"a\"; return name + \"b\";")
+            .unwrap()
+    })
+    .join()
+    .unwrap();
+    let mut runtime = Runtime::new();
+    assert_eq!(
+        runtime.execute_ast(ast).unwrap(),
+        Value::String("ab".into())
+    );
+}
+
+#[test]
+fn eval_expr_rejects_statements() {
+    let mut runtime = Runtime::new();
+    for code in [
+        "while true { }",
+        "for i in [1] { break i; }",
+        "div { while true { } }",
+        "[1, div { do { 1 } }]",
+        "std::execute(\"while true { }\")",
+        "execute(\"return 1;\")",
+    ] {
+        let error = runtime.eval_expr(code).unwrap_err();
+        assert_eq!(error.code(), "E0034", "{code}: {error}");
+    }
+}
+
+#[test]
+fn eval_expr_resets_limits() {
+    let mut runtime = Runtime::new();
+    let code = "fn spin(n) { let i = 0; while i < n { i = i + 1; } return i; }";
+    let ast = dioscript_parser::ast::DioscriptAst::from_string(code).unwrap();
+    runtime.execute_ast_in_scope(ast).unwrap();
+    runtime.set_limits(dioscript_runtime::report::ExecutionLimits {
+        fuel: Some(20),
+        ..Default::default()
+    });
+    assert_eq!(runtime.eval_expr("spin(100)").unwrap_err().code(), "E0036");
+    assert_eq!(runtime.eval_expr("spin(1)").unwrap(), Value::Number(1.0));
+}
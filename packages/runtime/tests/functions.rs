@@ -0,0 +1,182 @@
+mod common;
+
+use common::{run, run_err};
+use dioscript_parser::ast::{DioAstStatement, DioscriptAst};
+use dioscript_runtime::types::Value;
+
+#[test]
+fn apply_propagates_errors() {
+    let code = r#"
+        use std::fn;
+        fn check(x) { assert x == 1, "apply callback"; return x; }
+        return fn::apply(check, [2]);
+    "#;
+    let error = run_err(code);
+    assert!(error.to_string().contains("apply callback"), "{error}");
+}
+
+#[test]
+fn memoize_keys_on_values() {
+    let code = r#"
+        use std::fn;
+        let calls = 0;
+        fn count(v) {
+            calls = calls + 1;
+            return calls;
+        }
+        let cached = fn::memoize(count);
+        let a = cached({ a: 1, b: 2, c: 3, d: 4 });
+        let b = cached({ d: 4, c: 3, b: 2, a: 1 });
+        let c = cached(1);
+        let d = cached("1");
+        return [a, b, c, d];
+    "#;
+    let expected: Vec<Value> = [1.0, 1.0, 2.0, 3.0].map(Value::Number).to_vec();
+    assert_eq!(run(code), Value::List(std::rc::Rc::new(expected)));
+}
+
+#[test]
+fn memoize_propagates_errors() {
+    let code = r#"
+        use std::fn;
+        let calls = 0;
+        fn fail(v) {
+            calls = calls + 1;
+            assert calls > 1, "first call fails";
+            return calls;
+        }
+        let cached = fn::memoize(fail);
+        let a = cached(1);
+        return a;
+    "#;
+    let error = run_err(code);
+    assert!(error.to_string().contains("first call fails"), "{error}");
+}
+
+#[test]
+fn let_locals_in_blocks() {
+    let code = r#"
+        fn sum(list) {
+            let total = 0;
+            let count = 0;
+            for v in list {
+                let double = v * 2;
+                if double > 2 {
+                    let extra = double + total;
+                    total = extra;
+                }
+                count = count + 1;
+            }
+            let total = total * 10;
+            return [total, count];
+        }
+        return sum([1, 2, 3]);
+    "#;
+    let result = run(code);
+    let expected = Value::List(vec![Value::Number(100.0), Value::Number(3.0)].into());
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn let_locals_shadow_and_capture() {
+    let code = r#"
+        fn make(x) {
+            let y = x + 1;
+            let x = y * 2;
+            let (a, b) = (x, y);
+            fn get() { return a + b; }
+            return get;
+        }
+        let get = make(1);
+        return get();
+    "#;
+    assert_eq!(run(code), Value::Number(6.0));
+}
+
+#[test]
+fn comments_between_items() {
+    let code = r#"
+        fn add(
+            a, // first
+            b, // second
+        ) {
+            return a + b;
+        }
+        let list = [
+            // numbers
+            1,
+            2, // last
+        ];
+        let dict = {
+            // key comment
+            "a": 1,
+        };
+        let sum = add(
+            list[0], // one
+            dict["a"],
+        );
+        let text = "a b"
+            // split the words
+            .split(" ");
+        return [sum, text[1]];
+    "#;
+    let expected = Value::List(vec![Value::Number(2.0), Value::String("b".into())].into());
+    assert_eq!(run(code), expected);
+}
+
+#[test]
+fn anonymous_functions_as_arguments() {
+    let code = r#"
+        fn apply(f, v) {
+            return f(v);
+        }
+        let a = apply(fn (x) { return x * 2; }, 3);
+        let b = apply(fn(x){return x + 1;}, 1);
+        let c = [1, 2].map(
+            fn (x) {
+                return x * 10;
+            }
+        );
+        let d = (fn (x) { return x + 1; })(41);
+        return [a, b, c, d];
+    "#;
+    let expected = Value::List(
+        vec![
+            Value::Number(6.0),
+            Value::Number(2.0),
+            Value::List(vec![Value::Number(10.0), Value::Number(20.0)].into()),
+            Value::Number(42.0),
+        ]
+        .into(),
+    );
+    assert_eq!(run(code), expected);
+}
+
+#[test]
+fn nested_anonymous_function_arguments() {
+    let code = r#"
+        fn apply(f, v) {
+            return f(v);
+        }
+        return apply(fn (x) {
+            return apply(fn (y) { return apply(|z| z + y, x); }, 10);
+        }, 5);
+    "#;
+    assert_eq!(run(code), Value::Number(15.0));
+}
+
+#[test]
+fn comments_between_items_are_kept() {
+    let code = "let list = [\n    1, // one\n    2,\n];\nfn f(a) {\n    return f(\n        a, // arg\n    );\n}\n";
+    let ast = DioscriptAst::from_string(code).unwrap();
+    let comments: Vec<_> = ast.stats[0].comments.iter().map(|v| &v.text).collect();
+    assert_eq!(comments, vec!["one"]);
+    let span = ast.stats[0].comments[0].span;
+    assert_eq!(&code[span.start..span.end], "// one");
+    // comments of nested statements belong to them.
+    assert!(ast.stats[1].comments.is_empty());
+    match &ast.stats[1].kind {
+        DioAstStatement::FunctionDefine(f) => assert_eq!(f.inner[0].comments[0].text, "arg"),
+        other => panic!("{other:?}"),
+    }
+}
@@ -0,0 +1,59 @@
+use dioscript_runtime::{bytecode::Engine, error::RuntimeError, report::ExecutionLimits, Runtime};
+
+const RECURSION: &str = r#"
+    fn f(n) {
+        return f(n);
+    }
+    return f(1);
+"#;
+
+fn depth_error(engine: Engine, limits: ExecutionLimits, code: &str) -> RuntimeError {
+    let mut runtime = Runtime::new();
+    runtime.set_engine(engine);
+    runtime.set_limits(limits);
+    let ast = dioscript_parser::ast::DioscriptAst::from_string(code).unwrap();
+    runtime.execute_ast(ast).unwrap_err()
+}
+
+#[test]
+fn recursion_stops_at_max_depth() {
+    let limits = ExecutionLimits {
+        max_depth: Some(16),
+        ..Default::default()
+    };
+    for engine in [Engine::Interpreter, Engine::Bytecode] {
+        let error = depth_error(engine, limits, RECURSION);
+        assert!(
+            matches!(error.root(), RuntimeError::ExecutionLimitExceeded { .. }),
+            "{error}"
+        );
+        assert_eq!(error.trace().len(), 16);
+    }
+}
+
+#[test]
+fn recursion_is_limited_by_default() {
+    // runs on the stack of the test thread, the default must hold there in debug builds too.
+    let codes = [
+        "fn f(n) { let m = n + 1; return f(m); }\nf(0);",
+        "object Counter { fn count(n) { return self.count(n); } }\nlet c = Counter();\nc.count(1);",
+    ];
+    for code in codes {
+        for engine in [Engine::Interpreter, Engine::Bytecode] {
+            let error = depth_error(engine, ExecutionLimits::default(), code);
+            assert!(
+                matches!(error.root(), RuntimeError::ExecutionLimitExceeded { .. }),
+                "{error}"
+            );
+        }
+    }
+}
+
+#[test]
+fn report_is_kept_when_execution_fails() {
+    let code = "fn f(x) { return x; }\nlet a = f(1);\nlet b = f(2);\nassert a == b;\n";
+    let (result, report) = Runtime::new().execute_with_report(code);
+    assert!(result.is_err());
+    assert_eq!(report.functions_called, 2);
+    assert!(report.statements >= 4, "{report:?}");
+}
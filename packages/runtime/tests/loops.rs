@@ -0,0 +1,90 @@
+mod common;
+
+use common::run;
+use dioscript_runtime::types::Value;
+
+#[test]
+fn elements_in_block_head() {
+    let code = r#"
+        let name = "";
+        for x in [div { "a" }, p { "b" }] {
+            name = x.name;
+        }
+        let same = false;
+        if [div { "a" }] == [div { "a" }] {
+            same = true;
+        }
+        return (name, same);
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((
+            Box::new(Value::String("p".into())),
+            Box::new(Value::Boolean(true))
+        ))
+    );
+}
+
+#[test]
+fn block_after_name_in_head() {
+    let code = r#"
+        let items = [1, 2];
+        let sum = 0;
+        for v in items { sum = sum + v; }
+        if sum == 3 { sum = sum * 10; } else { sum = 0; }
+        while sum < 32 { sum = sum + 1; }
+        return sum;
+    "#;
+    assert_eq!(run(code), Value::Number(32.0));
+}
+
+#[test]
+fn element_block_after_name_in_head() {
+    let code = r#"
+        fn pick(flag) {
+            if flag {
+                div { "yes" }
+            } else {
+                p { "no" }
+            }
+        }
+        let a = pick(true).name;
+        let b = pick(false).name;
+        return (a, b);
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((
+            Box::new(Value::String("div".into())),
+            Box::new(Value::String("p".into()))
+        ))
+    );
+}
+
+#[test]
+fn return_in_loop_expression() {
+    let code = r#"
+        fn f() {
+            let x = for v in [1] { return 5; };
+            return 1;
+        }
+        fn g() {
+            let x = while true { if true { return 7; } };
+            return 1;
+        }
+        return (f(), g());
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((Box::new(Value::Number(5.0)), Box::new(Value::Number(7.0))))
+    );
+}
+
+#[test]
+fn break_value_of_loop_expression() {
+    let code = r#"
+        let x = for v in [1, 2, 3] { if v == 2 { break v * 10; } };
+        return x;
+    "#;
+    assert_eq!(run(code), Value::Number(20.0));
+}
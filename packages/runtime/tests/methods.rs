@@ -0,0 +1,118 @@
+mod common;
+
+use common::{run, run_err};
+use dioscript_runtime::types::Value;
+
+fn numbers(list: &[f64]) -> Value {
+    Value::List(std::rc::Rc::new(
+        list.iter().map(|v| Value::Number(*v)).collect(),
+    ))
+}
+
+const COUNTER: &str = r#"
+object Counter {
+    n = 0;
+    fn inc() {
+        self.n = self.n + 1;
+        return self.n;
+    }
+}
+"#;
+
+#[test]
+fn method_updates_variable() {
+    let code = format!(
+        "{COUNTER}
+        let c = Counter();
+        let a = c.inc();
+        let b = c.inc();
+        let d = c.inc();
+        let n = c.n;
+        return [a, b, d, n];"
+    );
+    assert_eq!(run(&code), numbers(&[1.0, 2.0, 3.0, 3.0]));
+}
+
+#[test]
+fn method_updates_local_slot() {
+    let code = format!(
+        "{COUNTER}
+        fn count(times) {{
+            let c = Counter();
+            for v in times {{
+                let n = c.inc();
+            }}
+            let n = c.n;
+            return n;
+        }}
+        return [count([1, 2]), count([1, 2, 3, 4])];"
+    );
+    assert_eq!(run(&code), numbers(&[2.0, 4.0]));
+}
+
+#[test]
+fn method_updates_field() {
+    let code = format!(
+        "{COUNTER}
+        let page = {{ counter: Counter() }};
+        let a = page.counter.inc();
+        let b = page.counter.inc();
+        let n = page.counter.n;
+        return n;"
+    );
+    assert_eq!(run(&code), Value::Number(2.0));
+}
+
+#[test]
+fn list_callbacks_propagate_errors() {
+    let map = r#"
+        fn check(x) { assert x == 1, "map callback"; return x; }
+        let l = [1, 2];
+        let r = l.map(check);
+        return r;
+    "#;
+    let error = run_err(map);
+    assert!(error.to_string().contains("map callback"), "{error}");
+    let filter = r#"
+        fn check(x) { let y = x.nope(); return true; }
+        let l = [1, 2];
+        let r = l.filter(check);
+        return r;
+    "#;
+    let error = run_err(filter);
+    assert!(error.to_string().contains("unknown method `nope`"), "{error}");
+}
+
+#[test]
+fn list_and_dict_functions_check_arguments() {
+    let cases = [
+        ("use std::list; return list::len(\"x\");", "E0021"),
+        ("use std::dict; return dict::keys(5);", "E0021"),
+        ("let a = [1]; let r = a.map(5); return r;", "E0021"),
+        ("let a = [1]; let r = a.filter(); return r;", "E0022"),
+    ];
+    for (code, expected) in cases {
+        let error = run_err(code);
+        assert_eq!(error.code(), expected, "{code}: {error}");
+    }
+}
+
+#[test]
+fn method_updates_list_item() {
+    let code = r#"
+        object Counter {
+            let n = 0;
+            fn inc() { self.n = self.n + 1; }
+        }
+        let counters = [Counter(), Counter()];
+        counters[1].inc();
+        counters[1].inc();
+        let first = counters[0];
+        let second = counters[1];
+        return first.n, second.n;
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((Box::new(Value::Number(0.0)), Box::new(Value::Number(2.0))))
+    );
+}
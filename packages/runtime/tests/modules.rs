@@ -0,0 +1,83 @@
+mod common;
+
+use common::{run, run_err};
+use dioscript_runtime::{bytecode::Engine, error::Error, types::Value, Runtime};
+
+#[test]
+fn callee_does_not_see_caller_use() {
+    let code = r#"
+        fn g() { return ss::len("abc"); }
+        fn f() {
+            use std::string as ss;
+            return g();
+        }
+        return f();
+    "#;
+    let error = run_err(code);
+    assert!(error.to_string().contains("`ss` not found"), "{error}");
+}
+
+#[test]
+fn function_sees_use_where_it_is_defined() {
+    let code = r#"
+        use std::string as top;
+        fn make() {
+            use std::string as ss;
+            fn inner() { return ss::len("abcd"); }
+            return inner;
+        }
+        fn from_top() { return top::len("ab"); }
+        let h = make();
+        let a = h();
+        let b = from_top();
+        return (a, b);
+    "#;
+    assert_eq!(
+        run(code),
+        Value::Tuple((Box::new(Value::Number(4.0)), Box::new(Value::Number(2.0))))
+    );
+}
+
+#[test]
+fn error_in_used_file_is_located_in_that_file() {
+    let dir = std::env::temp_dir().join(format!("ds-use-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("helper.ds"),
+        "fn fail() {\n    let x = 1;\n    assert x == 2;\n}\n",
+    )
+    .unwrap();
+    let code = "use \"helper.ds\";\nlet a = 1;\nhelper::fail();\n";
+    for engine in [Engine::Interpreter, Engine::Bytecode] {
+        let mut runtime = Runtime::new();
+        runtime.set_root_path(&dir);
+        runtime.set_engine(engine);
+        let error = match runtime.execute(code).unwrap_err() {
+            Error::Runtime(e) => e,
+            e => panic!("{e}"),
+        };
+        let location = error.location().unwrap();
+        assert_eq!(location.file.as_deref(), Some("helper.ds"));
+        assert_eq!(location.line, 3);
+        assert_eq!(location.snippet, "assert x == 2;");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn used_file_does_not_replace_bound_module() {
+    let dir = std::env::temp_dir().join(format!("ds-clash-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("std.ds"), "fn len(x) { return 0; }\n").unwrap();
+    std::fs::write(dir.join("util.ds"), "fn one() { return 1; }\n").unwrap();
+    let mut runtime = Runtime::new();
+    runtime.set_root_path(&dir);
+    let error = runtime.execute("use \"std.ds\";").unwrap_err();
+    assert!(
+        error.to_string().contains("module `std` is already bound"),
+        "{error}"
+    );
+    let code = "use \"util.ds\";\nuse \"util.ds\";\nreturn util::one();\n";
+    assert_eq!(runtime.execute(code).unwrap(), Value::Number(1.0));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
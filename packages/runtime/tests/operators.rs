@@ -0,0 +1,113 @@
+mod common;
+
+use common::{run, run_err};
+use dioscript_runtime::types::Value;
+
+#[test]
+fn coalesce_falls_back_on_missing_value() {
+    let code = r#"
+        let d = { a: 1 };
+        let l = [1];
+        let a = d.b ?? 2;
+        let b = l[3] ?? 3;
+        let c = d.a ?? 4;
+        return [a, b, c];
+    "#;
+    let expected: Vec<Value> = [2.0, 3.0, 1.0].map(Value::Number).to_vec();
+    assert_eq!(run(code), Value::List(std::rc::Rc::new(expected)));
+}
+
+#[test]
+fn coalesce_keeps_errors_of_called_functions() {
+    let code = r#"
+        fn get() { let d = {}; return d.missing; }
+        return get() ?? 1;
+    "#;
+    let error = run_err(code);
+    assert!(error.to_string().contains("missing"), "{error}");
+    let error = run_err("return undefined_name ?? 1;");
+    assert!(error.to_string().contains("undefined_name"), "{error}");
+}
+
+#[test]
+fn shift_amount_out_of_range_fails() {
+    for code in ["return 1 << -1;", "return 1 << 64;", "return 8 >> 100;"] {
+        let error = run_err(code);
+        assert_eq!(error.code(), "E0038", "{error}");
+    }
+    assert_eq!(run("return 1 << 3;"), Value::Number(8.0));
+    assert_eq!(run("return 8 >> 3;"), Value::Number(1.0));
+}
+
+#[test]
+fn index_accepts_expressions() {
+    let code = r#"
+        let l = [10, 20, 30, 40];
+        let d = { ab: 1 };
+        let i = 1;
+        let a = l[i + 1];
+        let b = d["a" + "b"];
+        let c = l[i:i + 2];
+        l[i * 3] = 0;
+        return [a, b, c, l[3]];
+    "#;
+    let slice = Value::List(std::rc::Rc::new(vec![
+        Value::Number(20.0),
+        Value::Number(30.0),
+    ]));
+    let expected = vec![
+        Value::Number(30.0),
+        Value::Number(1.0),
+        slice,
+        Value::Number(0.0),
+    ];
+    assert_eq!(run(code), Value::List(std::rc::Rc::new(expected)));
+}
+
+const MONEY: &str = r#"
+    fn eq(a, b) { return a.cents == b.cents; }
+    fn lt(a, b) { return a.cents < b.cents; }
+    fn add(a, b) { let c = a.cents + b.cents; return money(c); }
+    fn radd(a, b) { let c = a.cents + b; return money(c); }
+    fn money(cents) {
+        return { "cents": cents, "__eq__": eq, "__lt__": lt, "__add__": add, "__radd__": radd };
+    }
+    let a = money(1);
+    let b = money(2);
+"#;
+
+#[test]
+fn overloaded_operators_are_symmetric() {
+    let code = format!(
+        "{MONEY}
+        let same = money(1);
+        assert a == same;
+        assert same == a;
+        assert (a != same) == false;
+        assert a != b;
+        assert a < b;
+        assert b > a;
+        assert (a > b) == false;
+        assert a <= same;
+        assert b >= a;
+        let sum = a + b;
+        let left = 5 + a;
+        return sum.cents, left.cents;"
+    );
+    assert_eq!(
+        run(&code),
+        Value::Tuple((Box::new(Value::Number(3.0)), Box::new(Value::Number(6.0))))
+    );
+}
+
+#[test]
+fn comparison_overload_must_return_boolean() {
+    let code = r#"
+        fn eq(a, b) { return 1; }
+        let a = { "__eq__": eq };
+        let b = { "__eq__": eq };
+        return a == b;
+    "#;
+    let error = run_err(code);
+    assert!(error.to_string().contains("`__eq__`"), "{error}");
+}
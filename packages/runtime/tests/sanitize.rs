@@ -0,0 +1,60 @@
+use dioscript_runtime::{types::Value, Runtime};
+
+fn sanitized_html(code: &str) -> String {
+    let mut value = Runtime::new().execute(code).unwrap();
+    value.sanitize();
+    match value {
+        Value::Element(e) => e.to_html(),
+        value => panic!("element expected, got {value:?}"),
+    }
+}
+
+#[test]
+fn attribute_values_are_escaped() {
+    let code = r#"return div { title: "x\" onmouseover=\"alert(1)" };"#;
+    let html = match Runtime::new().execute(code).unwrap() {
+        Value::Element(e) => e.to_html(),
+        value => panic!("element expected, got {value:?}"),
+    };
+    assert_eq!(
+        html,
+        r#"<div title="x\&quot; onmouseover=\&quot;alert(1)"></div>"#
+    );
+}
+
+#[test]
+fn text_is_escaped() {
+    let html = sanitized_html(r#"return div { "<img src=x onerror=alert(1)>" };"#);
+    assert_eq!(html, "<div>&lt;img src=x onerror=alert(1)&gt;</div>");
+}
+
+#[test]
+fn blocked_elements_are_removed() {
+    let code = r#"
+        return div {
+            iframe { src: "https://example.com" }
+            object {}
+            embed {}
+            style { "body { display: none; }" }
+            script { "alert(1)" }
+            p { "kept" }
+        };
+    "#;
+    assert_eq!(sanitized_html(code), "<div><p>kept</p></div>");
+}
+
+#[test]
+fn script_urls_are_removed() {
+    let code = r#"
+        return div {
+            a { href: "javascript:alert(1)" }
+            a { href: "VBScript:msgbox(1)" }
+            a { href: " data:text/html,<script>alert(1)</script>" }
+            a { href: "https://example.com/a:b" }
+        };
+    "#;
+    assert_eq!(
+        sanitized_html(code),
+        "<div><a></a><a></a><a></a><a href=\"https://example.com/a:b\"></a></div>"
+    );
+}
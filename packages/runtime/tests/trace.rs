@@ -0,0 +1,36 @@
+mod common;
+
+use common::run_err;
+use dioscript_runtime::error::Error;
+
+#[test]
+fn frames_keep_call_sites() {
+    let code = "fn inner() {\n    assert 1 == 2;\n}\nfn outer_fn() {\n    let a = 1;\n    inner();\n}\nlet b = 2;\nouter_fn();\n";
+    let error = match run_err(code) {
+        Error::Runtime(e) => e,
+        e => panic!("{e}"),
+    };
+    let lines: Vec<(String, usize)> = error
+        .trace()
+        .iter()
+        .map(|v| (v.function.clone(), v.location.as_ref().unwrap().line))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![("inner".to_string(), 6), ("outer_fn".to_string(), 9)]
+    );
+    assert!(
+        error.to_string().contains("at `inner`, called at line 6"),
+        "{error}"
+    );
+}
+
+#[test]
+fn call_site_is_restored_after_a_call() {
+    let code = "fn ok() {\n    let x = 1;\n    return x;\n}\nfn bad() {\n    assert false;\n}\nlet v = ok() + bad();\n";
+    let error = match run_err(code) {
+        Error::Runtime(e) => e,
+        e => panic!("{e}"),
+    };
+    assert_eq!(error.trace()[0].location.as_ref().unwrap().line, 8);
+}